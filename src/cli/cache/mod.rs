@@ -0,0 +1,195 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+use clap::Parser;
+use colored::Colorize;
+
+use crate::cli::lib::cache::{
+    clear_local_cache, invalidate_local, list_cached_local, CacheEntryInfo, NOTION_NAMESPACE,
+    SLACK_NAMESPACE,
+};
+
+#[derive(Parser, Debug, Clone)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    action: CacheAction,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum CacheAction {
+    /// list cached entries with their ages and sizes, newest first
+    #[command(name = "list", aliases=["l", "ls"])]
+    List {
+        /// output as JSON
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+    /// remove cached entries, so they're fetched again when next needed
+    #[command(name = "clear")]
+    Clear {
+        /// only remove the entry with this key, such as `slack/slack_activity_C123`
+        #[arg(long, conflicts_with = "namespace")]
+        key: Option<String>,
+        /// only remove the entries of this data source
+        #[arg(long, value_enum)]
+        namespace: Option<CacheNamespace>,
+    },
+}
+
+/// The data sources with a namespace in the local cache.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheNamespace {
+    Slack,
+    Notion,
+}
+
+impl CacheNamespace {
+    fn name(self) -> &'static str {
+        match self {
+            CacheNamespace::Slack => SLACK_NAMESPACE,
+            CacheNamespace::Notion => NOTION_NAMESPACE,
+        }
+    }
+}
+
+/// Format an age in seconds as a short human readable duration, e.g. "2d 3h".
+fn format_age(age_secs: u64) -> String {
+    let (days, hours, minutes) = (
+        age_secs / 86400,
+        (age_secs % 86400) / 3600,
+        (age_secs % 3600) / 60,
+    );
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Lay the entries out as a table, newest first.
+fn render_table(entries: &mut [CacheEntryInfo]) -> String {
+    entries.sort_by_key(|e| e.age_secs);
+    let rows: Vec<[String; 4]> = entries
+        .iter()
+        .map(|e| {
+            [
+                e.key.clone(),
+                e.size.to_string(),
+                format_age(e.age_secs),
+                if e.expired { "yes" } else { "no" }.to_owned(),
+            ]
+        })
+        .collect();
+    let header = ["KEY", "BYTES", "AGE", "EXPIRED"].map(str::to_owned);
+    let width = |column: usize| {
+        std::iter::once(&header)
+            .chain(rows.iter())
+            .map(|row| row[column].len())
+            .max()
+            .unwrap_or_default()
+    };
+    let widths = [width(0), width(1), width(2)];
+    std::iter::once(&header)
+        .chain(rows.iter())
+        .map(|row| {
+            format!(
+                "{:<w0$}  {:>w1$}  {:>w2$}  {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2]
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub async fn cache_cmd(args: &CacheArgs) -> Result<()> {
+    match &args.action {
+        CacheAction::List { json } => {
+            let mut entries = list_cached_local()?;
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else if entries.is_empty() {
+                println!("Nothing is cached");
+            } else {
+                println!("{}", render_table(&mut entries));
+            }
+        }
+        CacheAction::Clear {
+            key: Some(key),
+            namespace: _,
+        } => {
+            invalidate_local(key)?;
+            println!("Removed {} from the cache", key.bright_purple());
+        }
+        CacheAction::Clear {
+            key: None,
+            namespace,
+        } => {
+            let removed = clear_local_cache(namespace.map(CacheNamespace::name))?;
+            println!("Removed {} cached entries", removed);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespace_outside_the_cache_is_refused() {
+        for namespace in ["/tmp/x", "..", "slack/../.."] {
+            let parsed = CacheArgs::try_parse_from(["cache", "clear", "--namespace", namespace]);
+            assert!(parsed.is_err(), "{}", namespace);
+            assert!(clear_local_cache(Some(namespace)).is_err(), "{}", namespace);
+        }
+        let parsed =
+            CacheArgs::try_parse_from(["cache", "clear", "--namespace", "notion"]).unwrap();
+        assert!(matches!(
+            parsed.action,
+            CacheAction::Clear {
+                namespace: Some(CacheNamespace::Notion),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(59), "0m");
+        assert_eq!(format_age(61 * 60), "1h 1m");
+        assert_eq!(format_age(2 * 86400 + 3 * 3600 + 59), "2d 3h");
+    }
+
+    #[test]
+    fn test_render_table_newest_first() {
+        let entry = |key: &str, size: u64, age_secs: u64, expired: bool| CacheEntryInfo {
+            key: key.to_owned(),
+            size,
+            modified: chrono::Utc::now(),
+            age_secs,
+            expired,
+        };
+        let mut entries = vec![
+            entry("users", 123456, 2 * 86400, true),
+            entry("channels", 42, 90, false),
+        ];
+        assert_eq!(
+            render_table(&mut entries),
+            [
+                "KEY        BYTES    AGE  EXPIRED",
+                "channels      42     1m  no",
+                "users     123456  2d 0h  yes",
+            ]
+            .join("\n")
+        );
+    }
+}
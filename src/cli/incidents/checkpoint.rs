@@ -0,0 +1,97 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::cli::lib::cache::{cache_raw, get_cached, invalidate};
+use crate::cli::notion::ids::DatabaseId;
+
+/// Remembers which incidents were inserted into a Notion database, so an insert
+/// run that died partway can be rerun without inserting them twice.
+///
+/// The checkpoint is written after every insert and cleared once a batch is
+/// done, so only a run that failed partway leaves one behind. It is only trusted
+/// for as long as other cached values, after which the same incidents would be up
+/// for review again anyway.
+#[derive(Debug, Default)]
+pub struct InsertCheckpoint {
+    /// Where the checkpoint is saved, or `None` to only keep it in memory
+    location: Option<(PathBuf, String)>,
+    inserted: Mutex<BTreeSet<u64>>,
+}
+
+impl InsertCheckpoint {
+    /// Load the checkpoint for inserts into `db` from `cache_dir`, starting afresh
+    /// if there is none or it has expired.
+    pub fn load(cache_dir: &Path, db: &DatabaseId) -> Result<Self> {
+        let key = format!("insert_checkpoint_{}", db);
+        let inserted = match get_cached::<BTreeSet<u64>>(&key, cache_dir)? {
+            Some(cached) if !cached.is_expired() => cached.value,
+            _ => BTreeSet::new(),
+        };
+        Ok(Self {
+            location: Some((cache_dir.to_owned(), key)),
+            inserted: Mutex::new(inserted),
+        })
+    }
+
+    /// Whether the incident was already inserted.
+    pub fn contains(&self, number: u64) -> bool {
+        self.inserted.lock().unwrap().contains(&number)
+    }
+
+    /// Record that the incident was inserted, saving the checkpoint right away.
+    pub fn record(&self, number: u64) -> Result<()> {
+        let mut inserted = self.inserted.lock().unwrap();
+        inserted.insert(number);
+        if let Some((cache_dir, key)) = &self.location {
+            create_dir_all(cache_dir)?;
+            cache_raw(key, serde_json::to_string(&*inserted)?, cache_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Forget every recorded insert once the whole batch is in, so a later run
+    /// inserts the incidents again, updating their pages.
+    pub fn clear(&self) -> Result<()> {
+        self.inserted.lock().unwrap().clear();
+        if let Some((cache_dir, key)) = &self.location {
+            invalidate(key, cache_dir)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::incidents::target::Target;
+
+    #[test]
+    fn test_checkpoint_survives_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint =
+            InsertCheckpoint::load(dir.path(), &Target::Debug.incident_db_id()).unwrap();
+        assert!(!checkpoint.contains(1));
+        checkpoint.record(1).unwrap();
+        checkpoint.record(3).unwrap();
+
+        let reloaded = InsertCheckpoint::load(dir.path(), &Target::Debug.incident_db_id()).unwrap();
+        assert!(reloaded.contains(1));
+        assert!(!reloaded.contains(2));
+        assert!(reloaded.contains(3));
+        // each database has its own checkpoint
+        let other = InsertCheckpoint::load(dir.path(), &Target::Prod.incident_db_id()).unwrap();
+        assert!(!other.contains(1));
+
+        reloaded.clear().unwrap();
+        assert!(!reloaded.contains(1));
+        let cleared = InsertCheckpoint::load(dir.path(), &Target::Debug.incident_db_id()).unwrap();
+        assert!(!cleared.contains(1));
+        assert!(!cleared.contains(3));
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+
+use crate::cli::notion::ids::DatabaseId;
+
+use super::prompt::Prompter;
+use super::target::Target;
+
+/// An incident selection database the review results can be inserted into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewDatabase {
+    /// The friendly name the database is chosen by
+    pub name: String,
+    pub id: DatabaseId,
+}
+
+impl ReviewDatabase {
+    /// The built-in database of `target`.
+    pub fn of(target: Target) -> Self {
+        Self {
+            name: target.incident_db_name().to_owned(),
+            id: target.incident_db_id(),
+        }
+    }
+}
+
+/// Parse the review databases configured as `NAME=ID` pairs separated by commas,
+/// such as `SUIOP_REVIEW_DATABASES=Wallet=a8da55da...,Infra=10e6d9dc...`.
+pub fn parse_databases(value: &str) -> Result<Vec<ReviewDatabase>> {
+    let mut databases: Vec<ReviewDatabase> = vec![];
+    for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (name, id) = pair
+            .split_once('=')
+            .map(|(name, id)| (name.trim(), id.trim()))
+            .filter(|(name, id)| !name.is_empty() && !id.is_empty())
+            .ok_or_else(|| anyhow!("expected NAME=DATABASE_ID, got {:?}", pair))?;
+        if databases.iter().any(|d| d.name.eq_ignore_ascii_case(name)) {
+            return Err(anyhow!(
+                "the review database {:?} is configured twice",
+                name
+            ));
+        }
+        databases.push(ReviewDatabase {
+            name: name.to_owned(),
+            id: DatabaseId::from_str(id)?,
+        });
+    }
+    Ok(databases)
+}
+
+/// The review databases from `SUIOP_REVIEW_DATABASES`, if any are configured.
+pub fn configured_databases() -> Result<Vec<ReviewDatabase>> {
+    parse_databases(&std::env::var("SUIOP_REVIEW_DATABASES").unwrap_or_default())
+}
+
+/// Pick the database to insert into from the configured ones: the one `name`
+/// asks for, ignoring case; the only one; or the one the operator chooses when
+/// several are configured. `None` when none are configured.
+pub fn choose_database(
+    databases: &[ReviewDatabase],
+    name: Option<&str>,
+    prompter: &impl Prompter,
+) -> Result<Option<ReviewDatabase>> {
+    if let Some(name) = name {
+        return databases
+            .iter()
+            .find(|d| d.name.eq_ignore_ascii_case(name.trim()))
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| {
+                anyhow!(
+                    "there is no review database {:?}, the configured ones are: {}",
+                    name,
+                    databases
+                        .iter()
+                        .map(|d| d.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            });
+    }
+    Ok(match databases {
+        [] => None,
+        [only] => Some(only.clone()),
+        several => {
+            let names: Vec<&str> = several.iter().map(|d| d.name.as_str()).collect();
+            Some(several[prompter.select_database(&names)?].clone())
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::incidents::prompt::ReviewChoice;
+    use crate::cli::incidents::{incident::Incident, user::User};
+    use crate::cli::slack::Channel;
+
+    /// Chooses the database at a fixed index, failing every other question.
+    struct DatabasePrompter(usize);
+
+    impl Prompter for DatabasePrompter {
+        fn confirm(&self, _message: &str, _default: bool) -> Result<bool> {
+            unreachable!()
+        }
+        fn choose_review(&self, _message: &str) -> Result<ReviewChoice> {
+            unreachable!()
+        }
+        fn select_pocs(&self, _users: Vec<User>) -> Result<Vec<User>> {
+            unreachable!()
+        }
+        fn select_canonical(&self, _incidents: &[Incident]) -> Result<usize> {
+            unreachable!()
+        }
+        fn select_channel(
+            &self,
+            _incident: &Incident,
+            _channels: &[&Channel],
+        ) -> Result<Option<usize>> {
+            unreachable!()
+        }
+        fn select_database(&self, _names: &[&str]) -> Result<usize> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn test_name_to_id_resolution() {
+        let databases = parse_databases(
+            "Wallet=a8da55dadb524e7db202b4dfd799d9ce, Infra = 10e6d9dcb4e980f8ae73c4aa2da176cd",
+        )
+        .unwrap();
+        let id = |name| {
+            choose_database(&databases, Some(name), &DatabasePrompter(0))
+                .unwrap()
+                .unwrap()
+                .id
+                .to_string()
+        };
+        assert_eq!(id("infra"), "10e6d9dcb4e980f8ae73c4aa2da176cd");
+        assert_eq!(id("Wallet"), "a8da55dadb524e7db202b4dfd799d9ce");
+        let err = choose_database(&databases, Some("DeFi"), &DatabasePrompter(0)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "there is no review database \"DeFi\", the configured ones are: Wallet, Infra"
+        );
+
+        // without a name the operator picks one of several
+        let chosen = choose_database(&databases, None, &DatabasePrompter(1)).unwrap();
+        assert_eq!(chosen.unwrap().name, "Infra");
+        // a single database is used without asking
+        let chosen = choose_database(&databases[..1], None, &DatabasePrompter(9)).unwrap();
+        assert_eq!(chosen.unwrap().name, "Wallet");
+        assert_eq!(
+            choose_database(&[], None, &DatabasePrompter(0)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_databases_rejects_malformed_pairs() {
+        assert!(parse_databases("").unwrap().is_empty());
+        assert!(parse_databases("Wallet").is_err());
+        assert!(parse_databases("=a8da55da").is_err());
+        assert!(parse_databases("Wallet=a8da,wallet=10e6").is_err());
+    }
+}
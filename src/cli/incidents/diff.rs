@@ -0,0 +1,195 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+use crate::cli::notion::models::Page;
+
+/// How one property of an incident's page would change.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PropertyChange {
+    pub property: String,
+    /// The value on the existing page, `None` if it's unset or there is no page
+    pub current: Option<String>,
+    pub intended: Option<String>,
+}
+
+/// A comparable rendering of a property value, in either the page create body or
+/// a fetched page: the text of titles and rich text, urls, the names of statuses
+/// and selects, and the sorted ids of people and relations.
+///
+/// Unset and empty values are `None`.
+fn property_text(value: &Value) -> Option<String> {
+    let text = if let Some(items) = value.get("title").or_else(|| value.get("rich_text")) {
+        items
+            .as_array()?
+            .iter()
+            .filter_map(|item| {
+                item["text"]["content"]
+                    .as_str()
+                    .or_else(|| item["plain_text"].as_str())
+            })
+            .collect::<String>()
+    } else if let Some(url) = value.get("url") {
+        url.as_str()?.to_owned()
+    } else if let Some(option) = value.get("status").or_else(|| value.get("select")) {
+        option["name"].as_str()?.to_owned()
+    } else if let Some(refs) = value.get("people").or_else(|| value.get("relation")) {
+        let ids: BTreeSet<&str> = refs
+            .as_array()?
+            .iter()
+            .filter_map(|r| r["id"].as_str())
+            .collect();
+        ids.into_iter().collect::<Vec<_>>().join(", ")
+    } else {
+        return None;
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// The properties of the page create `body` whose values differ from `page`, in
+/// property name order. Every set property is a change when there is no page yet.
+pub fn diff_page(page: Option<&Page>, body: &Value) -> Vec<PropertyChange> {
+    let Some(intended) = body["properties"].as_object() else {
+        return vec![];
+    };
+    let mut names: Vec<&String> = intended.keys().collect();
+    names.sort();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let current = page
+                .and_then(|p| p.properties.properties.get(name))
+                .and_then(|v| serde_json::to_value(v).ok())
+                .and_then(|v| property_text(&v));
+            let intended = property_text(&intended[name]);
+            (current != intended).then(|| PropertyChange {
+                property: name.clone(),
+                current,
+                intended,
+            })
+        })
+        .collect()
+}
+
+/// Describe the changes to an incident's page, one property per line.
+pub fn render_diff(number: u64, exists: bool, changes: &[PropertyChange]) -> String {
+    let heading = match (exists, changes.is_empty()) {
+        (_, true) => return format!("{}: no changes", number),
+        (true, false) => format!("{}: the existing page differs", number),
+        (false, false) => format!("{}: would create", number),
+    };
+    let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "(unset)".to_owned());
+    let mut lines = vec![heading];
+    lines.extend(changes.iter().map(|c| {
+        format!(
+            "  {}: {} -> {}",
+            c.property,
+            show(&c.current),
+            show(&c.intended)
+        )
+    }));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn page_with_pocs(ids: &[&str]) -> Page {
+        serde_json::from_value(json!({
+            "object": "page",
+            "id": "7c3f0d42-5a1e-4e8b-9b2a-0f6e1d2c3b44",
+            "created_time": "2024-05-01T09:00:00.000Z",
+            "last_edited_time": "2024-05-01T09:30:00.000Z",
+            "archived": false,
+            "parent": {
+                "type": "database_id",
+                "database_id": "10e6d9dc-b4e9-8062-a12a-d2e2f8b6b1f6"
+            },
+            "properties": {
+                "Name": {
+                    "type": "title",
+                    "id": "title",
+                    "title": [{
+                        "type": "text",
+                        "plain_text": "42: Validators halted",
+                        "text": { "content": "42: Validators halted" }
+                    }]
+                },
+                "PoC(s)": {
+                    "type": "people",
+                    "id": "poc",
+                    "people": ids.iter().map(|id| json!({
+                        "object": "user",
+                        "id": id,
+                        "name": "Someone",
+                        "type": "person",
+                        "person": { "email": format!("{}@mystenlabs.com", id) }
+                    })).collect::<Vec<_>>()
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn body_with_pocs(ids: &[&str]) -> Value {
+        json!({
+            "parent": { "database_id": "10e6d9dc-b4e9-8062-a12a-d2e2f8b6b1f6" },
+            "properties": {
+                "Name": { "title": [{ "text": { "content": "42: Validators halted" } }] },
+                "link": { "url": "https://pagerduty.com/incidents/42" },
+                "PoC(s)": {
+                    "people": ids.iter().map(|id| json!({ "object": "user", "id": id })).collect::<Vec<_>>()
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_diff_changed_pocs() {
+        let page = page_with_pocs(&["u1", "u2"]);
+        let changes = diff_page(Some(&page), &body_with_pocs(&["u3", "u1"]));
+        assert_eq!(
+            changes,
+            vec![
+                PropertyChange {
+                    property: "PoC(s)".to_owned(),
+                    current: Some("u1, u2".to_owned()),
+                    intended: Some("u1, u3".to_owned()),
+                },
+                PropertyChange {
+                    property: "link".to_owned(),
+                    current: None,
+                    intended: Some("https://pagerduty.com/incidents/42".to_owned()),
+                },
+            ]
+        );
+        assert_eq!(
+            render_diff(42, true, &changes),
+            "42: the existing page differs\n  PoC(s): u1, u2 -> u1, u3\n  link: (unset) -> https://pagerduty.com/incidents/42"
+        );
+
+        // the same POCs in another order are no change
+        let changes = diff_page(Some(&page), &body_with_pocs(&["u2", "u1"]));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].property, "link");
+    }
+
+    #[test]
+    fn test_diff_without_page_creates_everything() {
+        let changes = diff_page(None, &body_with_pocs(&[]));
+        // the empty POC list is unset on both sides
+        assert_eq!(
+            changes
+                .iter()
+                .map(|c| c.property.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Name", "link"]
+        );
+        assert!(render_diff(42, false, &changes).starts_with("42: would create\n"));
+        assert_eq!(render_diff(42, true, &[]), "42: no changes");
+    }
+}
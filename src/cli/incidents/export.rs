@@ -0,0 +1,172 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::frame::MessageFrame;
+use super::incident::Incident;
+use super::markdown::{render_markdown, ReviewFormat};
+use super::selection::{render_review_message, GreetingOptions};
+
+/// The outcome of an interactive review, saved so it can be replayed offline.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ReviewExport {
+    pub generated_at: DateTime<Utc>,
+    pub to_review: Vec<Incident>,
+    pub excluded: Vec<Incident>,
+}
+
+impl ReviewExport {
+    pub fn new(to_review: &[Incident], excluded: &[Incident]) -> Self {
+        Self {
+            generated_at: Utc::now(),
+            to_review: to_review.to_vec(),
+            excluded: excluded.to_vec(),
+        }
+    }
+
+    /// Write the export into `dir`, returning the path of the file written.
+    pub fn save(&self, dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        let path = dir.join(format!(
+            "review-{}.json",
+            self.generated_at.format("%Y%m%d-%H%M%S")
+        ));
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("{} is not a review export", path.display()))
+    }
+
+    /// Counts of what the review kept and dropped.
+    pub fn stats(&self) -> String {
+        let merged = self
+            .to_review
+            .iter()
+            .filter(|i| i.merged_into.is_some())
+            .count();
+        format!(
+            "Reviewed on {}: {} selected ({} merged into another incident), {} excluded",
+            self.generated_at.format("%Y-%m-%d %H:%M UTC"),
+            self.to_review.len(),
+            merged,
+            self.excluded.len()
+        )
+    }
+
+    /// Re-render the review message, greeting with the day the review was run.
+    pub fn render_message(&self, greeting: &GreetingOptions, frame: &MessageFrame) -> String {
+        let day = self.generated_at.format("%A").to_string();
+        frame.apply(
+            render_review_message(
+                greeting.line(&day).as_deref(),
+                None,
+                &self.to_review,
+                &self.excluded,
+            ),
+            self.generated_at.date_naive(),
+            self.to_review.len(),
+        )
+    }
+}
+
+/// Print the stats and message for a saved review without contacting any service,
+/// or the review as a Markdown document when `format` asks for one.
+pub fn replay_review(input: &Path, greeting: &GreetingOptions, format: ReviewFormat) -> Result<()> {
+    let export = ReviewExport::load(input)?;
+    match format {
+        ReviewFormat::Slack => {
+            println!("{}", export.stats());
+            println!(
+                "{}",
+                export.render_message(greeting, &MessageFrame::from_env())
+            );
+        }
+        ReviewFormat::Markdown => println!(
+            "{}",
+            render_markdown(
+                export.generated_at.date_naive(),
+                &export.to_review,
+                &export.excluded
+            )
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("tests/review_export.json");
+
+    fn sample() -> ReviewExport {
+        serde_json::from_str(SAMPLE).unwrap()
+    }
+
+    #[test]
+    fn test_stats() {
+        assert_eq!(
+            sample().stats(),
+            "Reviewed on 2024-03-04 17:30 UTC: 2 selected (1 merged into another incident), 1 excluded"
+        );
+    }
+
+    #[test]
+    fn test_render_message() {
+        let message =
+            sample().render_message(&GreetingOptions::default(), &MessageFrame::default());
+        assert!(message.starts_with(
+            "\nThis batch: 1×P1, 1×P2\n\nHello everyone and happy Monday!\n\nWe have selected"
+        ));
+        assert!(message
+            .contains("• 1234 (<#C0INC1234>) 03/01/24 Fullnode RPC latency spike <@U012AB3CD>"));
+        assert!(message.contains("1240"));
+        assert!(message.contains("excluded from review:\n"));
+        assert!(message.contains("1250"));
+
+        let message = sample().render_message(
+            &GreetingOptions {
+                no_greeting: true,
+                ..Default::default()
+            },
+            &MessageFrame::default(),
+        );
+        assert!(!message.contains("Hello everyone"));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = sample();
+        let path = export.save(&dir.path().join("reviews")).unwrap();
+        assert_eq!(
+            path.file_name().unwrap().to_str().unwrap(),
+            "review-20240304-173000.json"
+        );
+        let loaded = ReviewExport::load(&path).unwrap();
+        assert_eq!(
+            loaded.render_message(&GreetingOptions::default(), &MessageFrame::default()),
+            export.render_message(&GreetingOptions::default(), &MessageFrame::default())
+        );
+    }
+
+    #[test]
+    fn test_load_rejects_other_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("other.json");
+        fs::write(&path, "{\"incidents\": []}").unwrap();
+        let err = ReviewExport::load(&path).unwrap_err();
+        assert!(err.to_string().contains("is not a review export"));
+    }
+}
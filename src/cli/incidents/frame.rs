@@ -0,0 +1,94 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use chrono::NaiveDate;
+
+/// Text wrapped around the rendered review message, for what a team adds to
+/// every review such as a link to its rotation or compliance boilerplate.
+///
+/// The header and footer may use `{date}`, `{day}` and `{count}`, the number of
+/// incidents selected; other braces are kept as they are.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageFrame {
+    /// Text put before the message, from `SUIOP_REVIEW_HEADER`
+    pub header: Option<String>,
+    /// Text put after the message, from `SUIOP_REVIEW_FOOTER`
+    pub footer: Option<String>,
+}
+
+impl MessageFrame {
+    pub fn from_env() -> Self {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name: &str| lookup(name).filter(|value| !value.trim().is_empty());
+        Self {
+            header: var("SUIOP_REVIEW_HEADER"),
+            footer: var("SUIOP_REVIEW_FOOTER"),
+        }
+    }
+
+    /// Put the header and footer around `message`, each separated from it by a
+    /// blank line, for a review held on `date` selecting `count` incidents.
+    pub fn apply(&self, message: String, date: NaiveDate, count: usize) -> String {
+        let (header, footer) = self.filled(date, count);
+        let mut framed = message;
+        if let Some(header) = header {
+            framed = format!("{}\n\n{}", header, framed);
+        }
+        if let Some(footer) = footer {
+            framed = format!("{}\n\n{}", framed, footer);
+        }
+        framed
+    }
+
+    /// The header and footer for a review held on `date` selecting `count`
+    /// incidents, with their placeholders filled in.
+    pub fn filled(&self, date: NaiveDate, count: usize) -> (Option<String>, Option<String>) {
+        let fill = |text: &String| {
+            text.replace("{date}", &date.format("%Y-%m-%d").to_string())
+                .replace("{day}", &date.format("%A").to_string())
+                .replace("{count}", &count.to_string())
+        };
+        (
+            self.header.as_ref().map(fill),
+            self.footer.as_ref().map(fill),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_footer_and_header() {
+        let frame = MessageFrame::from_lookup(|name| match name {
+            "SUIOP_REVIEW_FOOTER" => {
+                Some("On call this week: <https://rotation> ({count} on {day})".to_owned())
+            }
+            "SUIOP_REVIEW_HEADER" => Some(" ".to_owned()),
+            _ => None,
+        });
+        assert_eq!(frame.header, None);
+        let date = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        assert_eq!(
+            frame.apply("We have selected".to_owned(), date, 3),
+            "We have selected\n\nOn call this week: <https://rotation> (3 on Monday)"
+        );
+
+        let frame = MessageFrame {
+            header: Some("Review {date} {unknown}".to_owned()),
+            footer: None,
+        };
+        assert_eq!(
+            frame.apply("We have selected".to_owned(), date, 3),
+            "Review 2024-03-04 {unknown}\n\nWe have selected"
+        );
+        assert_eq!(
+            MessageFrame::default().apply("We have selected".to_owned(), date, 3),
+            "We have selected"
+        );
+    }
+}
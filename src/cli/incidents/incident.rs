@@ -2,11 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::Result;
+use chrono::DateTime;
 use chrono::NaiveDateTime;
 use chrono::Utc;
 use colored::{ColoredString, Colorize};
 use serde::{Deserialize, Serialize};
 
+use super::number::NUMBER_FORMAT;
 use super::pd::PagerDutyIncident;
 use super::pd::Priority;
 use super::user::User;
@@ -19,37 +21,79 @@ const DATE_FORMAT_OUT_SHORT: &str = "%m/%d/%y";
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Incident {
     pub number: u64,
+    /// The incident's id in its source, e.g. the PagerDuty incident id
+    #[serde(default)]
+    pub source_id: Option<String>,
     pub title: String,
+    /// The longer description of the incident in its source, if it has one
+    #[serde(default)]
+    pub description: Option<String>,
+    /// The incident's state in its source when it was fetched, e.g. "resolved"
+    #[serde(default)]
+    pub status: Option<String>,
     pub created_at: Option<String>,
     pub resolved_at: Option<String>,
     pub html_url: String,
     /// The users responsible for reporting
-    #[serde(skip_deserializing)]
+    #[serde(default)]
     pub poc_users: Option<Vec<User>>,
     pub priority: Option<Priority>,
     pub slack_channel: Option<Channel>,
+    /// How many alerts or events the source grouped into the incident, if it says
+    #[serde(default)]
+    pub count: Option<u64>,
+    /// Labels from the source, such as the owning service and teams
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The canonical incident this one was reviewed together with, if any
+    #[serde(default)]
+    pub merged_into: Option<u64>,
+    /// When the incident's Slack channel last had a message, if looked up
+    #[serde(default)]
+    pub last_activity: Option<DateTime<Utc>>,
+    /// The team that owns the incident, which decides where it is reviewed
+    #[serde(default)]
+    pub owning_team: Option<String>,
 }
 
 impl From<PagerDutyIncident> for Incident {
     fn from(p: PagerDutyIncident) -> Self {
+        let tags = p.tags();
+        let priority = p.priority_or_urgency();
+        let owning_team = p.teams.first().map(|t| t.summary.clone());
         Self {
             number: p.number,
+            source_id: p.id,
             title: p.title,
+            description: p.description,
+            status: p.status,
             created_at: p.created_at,
             resolved_at: p.resolved_at,
             html_url: p.html_url,
             poc_users: None,
-            priority: p.priority,
+            priority,
             slack_channel: None,
+            count: p.alert_counts.map(|c| c.all),
+            tags,
+            merged_into: None,
+            last_activity: None,
+            owning_team,
         }
     }
 }
 
 impl Incident {
     pub fn print(&self, long_output: bool) -> Result<()> {
+        println!("{}", self.summary(long_output)?);
+        Ok(())
+    }
+
+    /// The human readable summary printed by [`Incident::print`].
+    pub fn summary(&self, long_output: bool) -> Result<String> {
         let priority = self.priority();
+        let mut lines = vec![];
         if long_output {
-            println!(
+            lines.push(format!(
                 "Incident #: {} {}",
                 self.number.to_string().bright_purple(),
                 if priority.is_empty() {
@@ -57,31 +101,34 @@ impl Incident {
                 } else {
                     format!("({})", priority)
                 }
-            );
-            println!("Title: {}", self.title.green());
+            ));
+            lines.push(format!("Title: {}", self.title.green()));
             if let Some(created_at) = self.created_at.clone() {
-                println!(
+                lines.push(format!(
                     "Created at: {}",
                     NaiveDateTime::parse_from_str(&created_at, DATE_FORMAT_IN)?
                         .format(DATE_FORMAT_OUT)
                         .to_string()
                         .yellow()
-                );
+                ));
             }
             if let Some(resolved_at) = self.resolved_at.clone() {
-                println!(
+                lines.push(format!(
                     "Resolved at: {}",
                     NaiveDateTime::parse_from_str(&resolved_at, DATE_FORMAT_IN)?
                         .format(DATE_FORMAT_OUT)
                         .to_string()
                         .yellow()
-                );
+                ));
             }
-            println!("URL: {}", self.html_url.bright_purple());
+            lines.push(format!("URL: {}", self.html_url.bright_purple()));
             if let Some(channel) = self.slack_channel.clone() {
-                println!("Predicted Slack channel: {}", channel.url().bright_purple());
+                lines.push(format!(
+                    "Predicted Slack channel: {}",
+                    channel.url().bright_purple()
+                ));
             }
-            println!("---");
+            lines.push("---".to_string());
         } else {
             let resolved_at = if let Some(resolved_at) = self.resolved_at.clone() {
                 let now = Utc::now().naive_utc();
@@ -90,8 +137,8 @@ impl Incident {
             } else {
                 None
             };
-            println!(
-                "{}:{}{} {} ({})",
+            lines.push(format!(
+                "{}:{}{} {} ({}){}",
                 self.number.to_string().bright_purple(),
                 resolved_at
                     .map(|v| (v.num_days().to_string() + "d").yellow())
@@ -106,10 +153,89 @@ impl Incident {
                     format!("({})", channel.url().bright_magenta())
                 } else {
                     self.html_url.bright_purple().to_string()
-                }
-            );
+                },
+                self.last_activity
+                    .map(|at| format!(" last activity: {} ago", format_age(Utc::now() - at)))
+                    .unwrap_or_default()
+            ));
         }
-        Ok(())
+        Ok(lines.join("\n"))
+    }
+
+    /// How many of the optional fields are populated, used to pick the more complete
+    /// of two records for the same incident.
+    fn completeness(&self) -> usize {
+        [
+            !self.title.is_empty(),
+            self.created_at.is_some(),
+            self.resolved_at.is_some(),
+            !self.html_url.is_empty(),
+            self.poc_users.is_some(),
+            self.priority.is_some(),
+            self.slack_channel.is_some(),
+            !self.tags.is_empty(),
+        ]
+        .iter()
+        .filter(|populated| **populated)
+        .count()
+    }
+
+    /// Merge two records of the same incident, keeping the more complete one and
+    /// filling its missing fields from the other.
+    pub fn merge(self, other: Incident) -> Incident {
+        let (mut base, other) = if other.completeness() > self.completeness() {
+            (other, self)
+        } else {
+            (self, other)
+        };
+        if base.title.is_empty() {
+            base.title = other.title;
+        }
+        if base.html_url.is_empty() {
+            base.html_url = other.html_url;
+        }
+        base.source_id = base.source_id.or(other.source_id);
+        base.description = base.description.or(other.description);
+        base.status = base.status.or(other.status);
+        base.created_at = base.created_at.or(other.created_at);
+        base.resolved_at = base.resolved_at.or(other.resolved_at);
+        base.poc_users = base.poc_users.or(other.poc_users);
+        base.priority = base.priority.or(other.priority);
+        base.slack_channel = base.slack_channel.or(other.slack_channel);
+        base.count = base.count.or(other.count);
+        base.merged_into = base.merged_into.or(other.merged_into);
+        base.last_activity = base.last_activity.or(other.last_activity);
+        base.owning_team = base.owning_team.or(other.owning_team);
+        for tag in other.tags {
+            if !base.tags.contains(&tag) {
+                base.tags.push(tag);
+            }
+        }
+        base
+    }
+
+    /// Whether the source reported the incident resolved, with a time it was resolved.
+    pub fn is_resolved(&self) -> bool {
+        self.resolved_at.is_some()
+            && self
+                .status
+                .as_deref()
+                .is_some_and(|s| s.eq_ignore_ascii_case("resolved"))
+    }
+
+    /// When the incident was created, if known and parseable.
+    pub fn created_at_utc(&self) -> Option<DateTime<Utc>> {
+        self.created_at
+            .as_deref()
+            .and_then(|c| NaiveDateTime::parse_from_str(c, DATE_FORMAT_IN).ok())
+            .map(|c| c.and_utc())
+    }
+
+    /// Whether the incident carries any of the given tags, ignoring case.
+    pub fn has_any_tag(&self, tags: &[String]) -> bool {
+        self.tags
+            .iter()
+            .any(|t| tags.iter().any(|wanted| t.eq_ignore_ascii_case(wanted)))
     }
 
     pub fn priority(&self) -> ColoredString {
@@ -124,13 +250,18 @@ impl Incident {
         }
     }
 
+    /// The incident number as its source writes it, e.g. `INC-0042`.
+    pub fn display_number(&self) -> String {
+        NUMBER_FORMAT.format(self.number)
+    }
+
     pub fn short_fmt(&self) -> String {
         format!(
             "• {} {} {} {}",
             if let Some(channel) = self.slack_channel.clone() {
-                format!("{} (<#{}>)", self.number, channel.id)
+                format!("{} (<#{}>)", self.display_number(), channel.id)
             } else {
-                self.number.to_string()
+                self.display_number()
             },
             self.resolved_at
                 .clone()
@@ -140,18 +271,183 @@ impl Incident {
                     .to_string())
                 .unwrap_or("".to_owned()),
             self.title,
-            self.poc_users.as_ref().map_or_else(
-                || "".to_string(),
-                |u| u
-                    .iter()
-                    .map(|u| {
-                        u.slack_user
-                            .as_ref()
-                            .map_or("".to_owned(), |su| format!("<@{}>", su.id))
-                    })
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            )
+            self.poc_mentions()
         )
     }
+
+    /// Slack mentions of the POCs, or the names of those without a Slack user,
+    /// separated by commas.
+    fn poc_mentions(&self) -> String {
+        self.poc_users.as_ref().map_or_else(
+            || "".to_string(),
+            |u| u.iter().map(User::mention).collect::<Vec<_>>().join(", "),
+        )
+    }
+
+    /// Render the incident's line in the review message from `template`, or in the
+    /// `short_fmt` format without one.
+    ///
+    /// The template may use `{number}`, `{title}`, `{priority}`, `{pocs}`, `{channel}`
+    /// and `{url}`; any other braces are kept as they are.
+    pub fn render_line(&self, template: Option<&str>) -> String {
+        let Some(template) = template else {
+            return self.short_fmt();
+        };
+        let mut line = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            line.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let value = rest.find('}').and_then(|end| {
+                let value = match &rest[1..end] {
+                    "number" => self.display_number(),
+                    "title" => self.title.clone(),
+                    "priority" => self
+                        .priority
+                        .as_ref()
+                        .map_or("".to_owned(), |p| p.name.clone()),
+                    "pocs" => self.poc_mentions(),
+                    "channel" => self
+                        .slack_channel
+                        .as_ref()
+                        .map_or("".to_owned(), |c| format!("<#{}>", c.id)),
+                    "url" => self.html_url.clone(),
+                    _ => return None,
+                };
+                Some((value, end))
+            });
+            match value {
+                Some((value, end)) => {
+                    line.push_str(&value);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    line.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        line.push_str(rest);
+        line
+    }
+}
+
+/// A rough age such as "3d", "5h" or "12m".
+fn format_age(age: chrono::Duration) -> String {
+    if age.num_days() > 0 {
+        format!("{}d", age.num_days())
+    } else if age.num_hours() > 0 {
+        format!("{}h", age.num_hours())
+    } else {
+        format!("{}m", age.num_minutes().max(0))
+    }
+}
+
+/// Combine incidents from multiple sources, merging records that share an incident
+/// number. The order of first appearance is kept.
+///
+/// Numbers are compared as numbers, so sources writing `INC-0042` and `42` agree.
+pub fn dedup_incidents(incidents: Vec<Incident>) -> Vec<Incident> {
+    let mut merged: Vec<Incident> = Vec::with_capacity(incidents.len());
+    for incident in incidents {
+        match merged.iter().position(|i| i.number == incident.number) {
+            Some(index) => {
+                let existing = std::mem::take(&mut merged[index]);
+                merged[index] = existing.merge(incident);
+            }
+            None => merged.push(incident),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_incidents_merges_partial_records() {
+        let from_pagerduty = Incident {
+            number: 42,
+            title: "Validators halted".to_owned(),
+            html_url: "https://pagerduty.com/incidents/42".to_owned(),
+            resolved_at: Some("2024-03-04T18:02:00Z".to_owned()),
+            priority: Some(Priority::from_name("P1")),
+            tags: vec!["validator".to_owned()],
+            ..Default::default()
+        };
+        let from_file = Incident {
+            number: 42,
+            title: "validators halted".to_owned(),
+            created_at: Some("2024-03-04T17:00:00Z".to_owned()),
+            tags: vec!["validator".to_owned(), "consensus".to_owned()],
+            ..Default::default()
+        };
+        let other = Incident {
+            number: 7,
+            title: "RPC latency".to_owned(),
+            ..Default::default()
+        };
+
+        let merged = dedup_incidents(vec![from_pagerduty, other, from_file]);
+
+        assert_eq!(merged.len(), 2);
+        let incident = &merged[0];
+        assert_eq!(incident.number, 42);
+        // the pagerduty record is more complete so its values win
+        assert_eq!(incident.title, "Validators halted");
+        assert_eq!(incident.html_url, "https://pagerduty.com/incidents/42");
+        assert_eq!(incident.priority.as_ref().unwrap().name, "P1");
+        // missing fields are filled from the other record
+        assert_eq!(incident.created_at.as_deref(), Some("2024-03-04T17:00:00Z"));
+        assert_eq!(
+            incident.resolved_at.as_deref(),
+            Some("2024-03-04T18:02:00Z")
+        );
+        assert_eq!(incident.tags, vec!["validator", "consensus"]);
+        assert_eq!(merged[1].number, 7);
+    }
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(chrono::Duration::hours(50)), "2d");
+        assert_eq!(format_age(chrono::Duration::minutes(190)), "3h");
+        assert_eq!(format_age(chrono::Duration::seconds(30)), "0m");
+    }
+
+    #[test]
+    fn test_render_line_template() {
+        let incident = Incident {
+            number: 42,
+            title: "Validators halted".to_owned(),
+            html_url: "https://pagerduty.com/incidents/42".to_owned(),
+            priority: Some(Priority::from_name("P1")),
+            slack_channel: Some(Channel {
+                id: "C042".to_owned(),
+                name: "incident-42".to_owned(),
+            }),
+            poc_users: Some(vec![User::new(
+                Some(crate::cli::slack::SlackUser {
+                    id: "U1".to_owned(),
+                    name: "jdoe".to_owned(),
+                    profile: None,
+                }),
+                None,
+            )
+            .unwrap()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            incident.render_line(Some(
+                "• [{priority}] {number} {channel} {title} ({pocs}) <{url}|link> {unknown}"
+            )),
+            "• [P1] 42 <#C042> Validators halted (<@U1>) <https://pagerduty.com/incidents/42|link> {unknown}"
+        );
+        // placeholders in the values aren't expanded again
+        let incident = Incident {
+            title: "{url}".to_owned(),
+            ..incident
+        };
+        assert_eq!(incident.render_line(Some("{title}")), "{url}");
+        assert_eq!(incident.render_line(None), incident.short_fmt());
+    }
 }
@@ -0,0 +1,96 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use std::fs::create_dir_all;
+use std::path::Path;
+
+use crate::cli::lib::cache::{cache_raw, get_cached};
+
+use super::incident::Incident;
+
+/// The cache key the time of the last completed review is stored under.
+const LAST_RUN_CACHE_KEY: &str = "incident_review_last_run";
+
+/// When the last completed review started, or `None` if none was recorded in `cache_dir`.
+pub fn load_last_run(cache_dir: &Path) -> Result<Option<DateTime<Utc>>> {
+    // the timestamp doesn't expire like other cached values, so the age is ignored
+    Ok(get_cached(LAST_RUN_CACHE_KEY, cache_dir)?.map(|cached| cached.value))
+}
+
+/// Record that a review which started at `started_at` completed.
+pub fn save_last_run(cache_dir: &Path, started_at: DateTime<Utc>) -> Result<()> {
+    create_dir_all(cache_dir)?;
+    cache_raw(
+        LAST_RUN_CACHE_KEY,
+        serde_json::to_string(&started_at)?,
+        cache_dir,
+    )?;
+    Ok(())
+}
+
+/// Keep only the incidents created after the last completed review, returning
+/// them and how many were left out.
+///
+/// Incidents without a known creation date are kept.
+pub fn filter_incidents_since_last_run(
+    cache_dir: &Path,
+    incidents: Vec<Incident>,
+) -> Result<(Vec<Incident>, usize)> {
+    let since = load_last_run(cache_dir)?.ok_or_else(|| {
+        anyhow!("no completed review is recorded yet, run one without --since-last-run first")
+    })?;
+    let before = incidents.len();
+    let kept: Vec<Incident> = incidents
+        .into_iter()
+        .filter(|i| i.created_at_utc().is_none_or(|created| created > since))
+        .collect();
+    let left_out = before - kept.len();
+    Ok((kept, left_out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn incident_created(number: u64, created: DateTime<Utc>) -> Incident {
+        Incident {
+            number,
+            created_at: Some(created.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_stored_run_filters_older_incidents() {
+        let dir = tempfile::tempdir().unwrap();
+        let last_run = "2024-03-04T17:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        save_last_run(dir.path(), last_run).unwrap();
+        assert_eq!(load_last_run(dir.path()).unwrap(), Some(last_run));
+
+        let incidents = vec![
+            incident_created(1, last_run - Duration::hours(1)),
+            incident_created(2, last_run + Duration::hours(1)),
+            Incident {
+                number: 3,
+                ..Default::default()
+            },
+        ];
+        let (kept, left_out) = filter_incidents_since_last_run(dir.path(), incidents).unwrap();
+        assert_eq!(
+            kept.iter().map(|i| i.number).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(left_out, 1);
+    }
+
+    #[test]
+    fn test_no_recorded_run_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(load_last_run(dir.path()).unwrap(), None);
+        let err = filter_incidents_since_last_run(dir.path(), vec![]).unwrap_err();
+        assert!(err.to_string().contains("no completed review is recorded"));
+    }
+}
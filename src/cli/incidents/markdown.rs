@@ -0,0 +1,160 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use chrono::NaiveDate;
+
+use super::incident::Incident;
+use super::selection::priority_counts;
+
+/// How a review's selection is rendered.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReviewFormat {
+    /// the message posted to Slack
+    #[default]
+    Slack,
+    /// a Markdown document, for pasting into meeting notes
+    Markdown,
+}
+
+/// Escape the characters that would break out of a table cell or start emphasis.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '|' | '*' | '_' | '[' | ']' | '`' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The names of the incident's POCs, preferring their Slack names.
+fn poc_names(incident: &Incident) -> Vec<String> {
+    incident
+        .poc_users
+        .iter()
+        .flatten()
+        .filter_map(|u| {
+            u.slack_user
+                .as_ref()
+                .map(|s| s.name.clone())
+                .or_else(|| u.notion_user.as_ref().map(|n| n.name.clone()))
+        })
+        .collect()
+}
+
+/// One list item linking to the incident, with its priority, POCs and any merge.
+fn incident_item(incident: &Incident) -> String {
+    let number = if incident.html_url.is_empty() {
+        incident.display_number()
+    } else {
+        format!("[{}]({})", incident.display_number(), incident.html_url)
+    };
+    let mut item = format!("- {} {}", number, escape(&incident.title));
+    if let Some(priority) = incident.priority.as_ref().filter(|p| !p.name.is_empty()) {
+        item.push_str(&format!(" ({})", priority.name));
+    }
+    let pocs = poc_names(incident);
+    if !pocs.is_empty() {
+        item.push_str(&format!(" — POCs: {}", escape(&pocs.join(", "))));
+    }
+    if let Some(canonical) = incident.merged_into {
+        item.push_str(&format!(", reviewed with {}", canonical));
+    }
+    item
+}
+
+fn incident_list(incidents: &[Incident]) -> String {
+    if incidents.is_empty() {
+        return "_None_".to_owned();
+    }
+    incidents
+        .iter()
+        .map(incident_item)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// How many selected and excluded incidents have each priority, as a table.
+fn priority_table(to_review: &[Incident], excluded: &[Incident]) -> String {
+    let (selected, selected_unknown) = priority_counts(to_review);
+    let (dropped, dropped_unknown) = priority_counts(excluded);
+    let mut priorities: Vec<u8> = selected.keys().chain(dropped.keys()).copied().collect();
+    priorities.sort();
+    priorities.dedup();
+    let mut rows = vec![
+        "| Priority | Selected | Excluded |".to_owned(),
+        "| --- | --- | --- |".to_owned(),
+    ];
+    rows.extend(priorities.into_iter().map(|p| {
+        format!(
+            "| P{} | {} | {} |",
+            p,
+            selected.get(&p).copied().unwrap_or_default(),
+            dropped.get(&p).copied().unwrap_or_default()
+        )
+    }));
+    if selected_unknown > 0 || dropped_unknown > 0 {
+        rows.push(format!(
+            "| unknown | {} | {} |",
+            selected_unknown, dropped_unknown
+        ));
+    }
+    rows.join("\n")
+}
+
+/// Render the review held on `day` as a Markdown document: a table of priorities,
+/// then the selected and the excluded incidents, each linking to the incident.
+pub fn render_markdown(day: NaiveDate, to_review: &[Incident], excluded: &[Incident]) -> String {
+    format!(
+        "# Incident review for {}\n\n{}\n\n## Selected for review\n\n{}\n\n## Excluded from review\n\n{}\n",
+        day.format("%Y-%m-%d"),
+        priority_table(to_review, excluded),
+        incident_list(to_review),
+        incident_list(excluded)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::incidents::export::ReviewExport;
+
+    #[test]
+    fn test_render_markdown() {
+        let export: ReviewExport =
+            serde_json::from_str(include_str!("tests/review_export.json")).unwrap();
+        let markdown = render_markdown(
+            export.generated_at.date_naive(),
+            &export.to_review,
+            &export.excluded,
+        );
+        assert_eq!(
+            markdown,
+            "# Incident review for 2024-03-04
+
+| Priority | Selected | Excluded |
+| --- | --- | --- |
+| P1 | 1 | 0 |
+| P2 | 1 | 0 |
+| unknown | 0 | 1 |
+
+## Selected for review
+
+- [1234](https://mystenlabs.pagerduty.com/incidents/Q2XYZ9ABCDEF) Fullnode RPC latency spike (P1) — POCs: alice
+- [1240](https://mystenlabs.pagerduty.com/incidents/Q3ABC) Fullnode RPC latency spikes (P2), reviewed with 1234
+
+## Excluded from review
+
+- [1250](https://mystenlabs.pagerduty.com/incidents/Q4DEF) Indexer lag
+"
+        );
+        assert!(render_markdown(export.generated_at.date_naive(), &[], &[])
+            .contains("## Selected for review\n\n_None_\n"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a|b *c* [d]"), "a\\|b \\*c\\* \\[d\\]");
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::Path;
+
+use super::incident::Incident;
+use super::selection::priority_counts;
+
+/// What a review run found, for dashboards.
+#[derive(Debug)]
+pub struct ReviewMetrics<'a> {
+    pub finished_at: DateTime<Utc>,
+    pub to_review: &'a [Incident],
+    pub excluded: &'a [Incident],
+    /// Notion people that couldn't be matched to a Slack user
+    pub unmatched_people: usize,
+}
+
+/// Append a gauge with its help and type lines, one sample per set of labels.
+fn gauge(lines: &mut Vec<String>, name: &str, help: &str, samples: &[(String, usize)]) {
+    lines.push(format!("# HELP {} {}", name, help));
+    lines.push(format!("# TYPE {} gauge", name));
+    for (labels, value) in samples {
+        lines.push(format!("{}{} {}", name, labels, value));
+    }
+}
+
+/// The priority samples of one set of incidents, labelled with the set's name.
+fn priority_samples(set: &str, incidents: &[Incident]) -> Vec<(String, usize)> {
+    let (counts, unknown) = priority_counts(incidents);
+    let mut samples: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(priority, count)| {
+            (
+                format!("{{set=\"{}\",priority=\"P{}\"}}", set, priority),
+                count,
+            )
+        })
+        .collect();
+    if unknown > 0 {
+        samples.push((format!("{{set=\"{}\",priority=\"unknown\"}}", set), unknown));
+    }
+    samples
+}
+
+impl ReviewMetrics<'_> {
+    /// Render the metrics in the Prometheus text format read by node_exporter's
+    /// textfile collector.
+    pub fn render(&self) -> String {
+        let merged = self
+            .to_review
+            .iter()
+            .filter(|i| i.merged_into.is_some())
+            .count();
+        let unmatched_pocs = self
+            .to_review
+            .iter()
+            .flat_map(|i| i.poc_users.iter().flatten())
+            .filter(|u| !u.has_slack_user())
+            .count();
+        let mut lines = vec![];
+        gauge(
+            &mut lines,
+            "suiop_incident_review_incidents",
+            "Incidents in the last review, by whether they were selected or excluded.",
+            &[
+                ("{set=\"selected\"}".to_owned(), self.to_review.len()),
+                ("{set=\"excluded\"}".to_owned(), self.excluded.len()),
+            ],
+        );
+        gauge(
+            &mut lines,
+            "suiop_incident_review_merged_incidents",
+            "Selected incidents reviewed as part of another incident.",
+            &[(String::new(), merged)],
+        );
+        let mut by_priority = priority_samples("selected", self.to_review);
+        by_priority.extend(priority_samples("excluded", self.excluded));
+        gauge(
+            &mut lines,
+            "suiop_incident_review_incidents_by_priority",
+            "Incidents in the last review by set and priority.",
+            &by_priority,
+        );
+        gauge(
+            &mut lines,
+            "suiop_incident_review_unmatched_pocs",
+            "POCs of selected incidents without a Slack user to mention.",
+            &[(String::new(), unmatched_pocs)],
+        );
+        gauge(
+            &mut lines,
+            "suiop_incident_review_unmatched_people",
+            "Notion people that couldn't be matched to a Slack user.",
+            &[(String::new(), self.unmatched_people)],
+        );
+        gauge(
+            &mut lines,
+            "suiop_incident_review_last_run_timestamp_seconds",
+            "When the last review finished, in seconds since the epoch.",
+            &[(String::new(), self.finished_at.timestamp() as usize)],
+        );
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// Write the metrics to `path`, through a temporary file renamed into place so
+    /// the collector never reads a partly written file.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, self.render())
+            .with_context(|| format!("failed to write {}", Path::new(&temporary).display()))?;
+        fs::rename(&temporary, path).with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::incidents::export::ReviewExport;
+
+    #[test]
+    fn test_metrics_lines_and_labels() {
+        let export: ReviewExport =
+            serde_json::from_str(include_str!("tests/review_export.json")).unwrap();
+        let metrics = ReviewMetrics {
+            finished_at: export.generated_at,
+            to_review: &export.to_review,
+            excluded: &export.excluded,
+            unmatched_people: 4,
+        };
+        let rendered = metrics.render();
+        for line in [
+            "# TYPE suiop_incident_review_incidents gauge",
+            "suiop_incident_review_incidents{set=\"selected\"} 2",
+            "suiop_incident_review_incidents{set=\"excluded\"} 1",
+            "suiop_incident_review_merged_incidents 1",
+            "suiop_incident_review_incidents_by_priority{set=\"selected\",priority=\"P1\"} 1",
+            "suiop_incident_review_incidents_by_priority{set=\"selected\",priority=\"P2\"} 1",
+            "suiop_incident_review_incidents_by_priority{set=\"excluded\",priority=\"unknown\"} 1",
+            "suiop_incident_review_unmatched_pocs 0",
+            "suiop_incident_review_unmatched_people 4",
+            "suiop_incident_review_last_run_timestamp_seconds 1709573400",
+        ] {
+            assert!(
+                rendered.lines().any(|l| l == line),
+                "missing {:?} in\n{}",
+                line,
+                rendered
+            );
+        }
+        assert!(rendered.ends_with('\n'));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("incident_review.prom");
+        metrics.write(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), rendered);
+        assert!(!dir.path().join("incident_review.prom.tmp").exists());
+    }
+}
@@ -1,22 +1,51 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+mod checkpoint;
+mod database;
+mod diff;
+mod export;
+mod frame;
 mod incident;
 mod jira;
+mod last_run;
+mod markdown;
+mod metrics;
 pub(crate) mod notion;
+mod number;
+mod output;
+mod overrides;
 mod pd;
+mod poc_map;
+mod priority;
+mod prompt;
+mod reconcile;
+mod routing;
 mod selection;
-mod user;
+mod snooze;
+mod source;
+pub(crate) mod target;
+pub(crate) mod user;
 
 use crate::cli::slack::Slack;
-use anyhow::Result;
+use crate::DEBUG_MODE;
+use anyhow::{anyhow, Result};
 use chrono::{Duration, Local};
 use clap::Parser;
+use export::replay_review;
 use incident::Incident;
 use jira::generate_follow_up_tasks;
-use pd::print_recent_incidents;
-use selection::review_recent_incidents;
+use markdown::ReviewFormat;
+use notion::Notion;
+use pd::{print_recent_incidents, PagerDutySource};
+use poc_map::{load_poc_map, render_unresolved, unresolved_pocs};
+use priority::{parse_priority_mapping, PriorityMap};
+use prompt::InquirePrompter;
+use reconcile::{find_reopened, render_reopened};
+use selection::{review_recent_incidents, GreetingOptions, ReviewOptions};
+use source::IncidentSource;
 use std::path::PathBuf;
+use target::Target;
 use tracing::{debug, info};
 
 #[derive(Parser, Debug, Clone)]
@@ -48,6 +77,13 @@ pub enum IncidentsAction {
         /// output as JSON
         #[arg(long, default_value = "false", conflicts_with = "interactive")]
         json: bool,
+        /// map a severity from the incident source onto a priority, e.g. `SEV1=P1`
+        /// (repeat for each severity); unmapped severities are treated as P4
+        #[arg(long = "map-priority", value_name = "SEVERITY=PRIORITY", value_parser = parse_priority_mapping)]
+        priority_mappings: Vec<(String, String)>,
+        /// options for interactive review
+        #[command(flatten)]
+        review: Box<ReviewOptions>,
     },
     /// generate Jira tasks for incident follow ups
     #[command(name = "generate follow up tasks", aliases=["g", "gen", "generate"])]
@@ -56,29 +92,64 @@ pub enum IncidentsAction {
         #[arg(short, long)]
         input_filename: PathBuf,
     },
+    /// list reviewed incidents that have been reopened since, and need another review
+    #[command(name = "reconcile")]
+    Reconcile {
+        /// the Notion database to check [default: debug in debug mode, otherwise prod]
+        #[arg(long = "db", value_enum)]
+        db_target: Option<Target>,
+    },
+    /// check that every email in a POC map file belongs to someone in Notion or Slack
+    #[command(name = "validate-poc-map")]
+    ValidatePocMap {
+        /// the POC map, a JSON object of incident tags to lists of POC emails
+        path: PathBuf,
+    },
+    /// re-render the message and stats of a review saved with `--output-dir`, offline
+    #[command(name = "replay")]
+    Replay {
+        /// the review file written to the `--output-dir` directory
+        input: PathBuf,
+        /// how to open the review message
+        #[command(flatten)]
+        greeting: GreetingOptions,
+        /// how to render the reviewed incidents
+        #[arg(long, value_enum, default_value_t)]
+        format: ReviewFormat,
+    },
 }
 
 /// - Fetch incidents from the PagerDuty API.
-/// - Associate slack channels when they exist.
+/// - Map their severities onto the internal priorities.
+/// - Associate slack channels when they exist, asking which one is meant when
+///   several match and `interactive` is set.
 /// - Return the combined incident list.
-async fn get_incidents(limit: &usize, days: &usize) -> Result<Vec<Incident>> {
+async fn get_incidents(
+    source: &impl IncidentSource,
+    limit: &usize,
+    days: &usize,
+    interactive: bool,
+    priorities: &PriorityMap,
+) -> Result<Vec<Incident>> {
     let current_time = Local::now();
     info!("going back {} days", days);
     let start_time = current_time - Duration::days(*days as i64);
-    let slack = Slack::new().await;
-    Ok(pd::fetch_incidents(*limit, start_time, current_time)
-        .await?
-        .into_iter()
-        // Change into more robust Incident type
-        .map(incident::Incident::from)
-        .map(|mut incident| {
-            // Add associated slack channel if it exists
-            debug!("Checking if incidents list contains {}", incident.number);
-            incident.slack_channel = selection::get_channel_for(&incident, &slack).cloned();
-            debug!("Found channel: {:?}", incident.slack_channel);
-            incident
-        })
-        .collect())
+    let slack = Slack::new().await?;
+    let mut incidents = source
+        .fetch_recent(*limit, start_time, current_time)
+        .await?;
+    priorities.apply(&mut incidents);
+    // Only ask which channel is meant when reviewing interactively
+    let prompter = interactive.then_some(&InquirePrompter);
+    // Merge any incident reported more than once across sources
+    let mut incidents = incident::dedup_incidents(incidents);
+    for incident in incidents.iter_mut() {
+        // Add associated slack channel if it exists
+        debug!("Checking if incidents list contains {}", incident.number);
+        incident.slack_channel = selection::resolve_channel_for(incident, &slack, prompter)?;
+        debug!("Found channel: {:?}", incident.slack_channel);
+    }
+    Ok(incidents)
 }
 
 pub async fn incidents_cmd(args: &IncidentsArgs) -> Result<()> {
@@ -90,10 +161,14 @@ pub async fn incidents_cmd(args: &IncidentsArgs) -> Result<()> {
             with_priority,
             interactive,
             json,
+            priority_mappings,
+            review,
         } => {
-            let incidents = get_incidents(limit, days).await?;
+            let source = PagerDutySource::new();
+            let priorities = PriorityMap::new(priority_mappings);
+            let incidents = get_incidents(&source, limit, days, *interactive, &priorities).await?;
             if *interactive {
-                review_recent_incidents(incidents).await?
+                review_recent_incidents(incidents, &source, review).await?
             } else {
                 print_recent_incidents(incidents, *long, *with_priority, *json).await?
             }
@@ -101,6 +176,38 @@ pub async fn incidents_cmd(args: &IncidentsArgs) -> Result<()> {
         IncidentsAction::GenerateFollowUpTasks { input_filename } => {
             generate_follow_up_tasks(input_filename).await?
         }
+        IncidentsAction::Reconcile { db_target } => {
+            let source = PagerDutySource::new();
+            let notion = Notion::new(Target::resolve(*db_target, *DEBUG_MODE));
+            let diverged = find_reopened(&notion, &source).await?;
+            println!("{}", render_reopened(&diverged));
+        }
+        IncidentsAction::ValidatePocMap { path } => {
+            let map = load_poc_map(path)?;
+            let slack = Slack::new().await?;
+            // emails missing from a partial directory would be reported as unresolved
+            user::check_slack_directory(
+                slack.users_incomplete.as_deref(),
+                slack.users.len(),
+                false,
+            )?;
+            let notion = Notion::new(Target::resolve(None, *DEBUG_MODE));
+            let users = user::combine_users(notion.get_all_people().await?, &slack.users);
+            let unresolved = unresolved_pocs(&map, &users);
+            println!("{}", render_unresolved(&map, &unresolved));
+            if !unresolved.is_empty() {
+                return Err(anyhow!(
+                    "{} POC emails in {} can't be resolved",
+                    unresolved.len(),
+                    path.display()
+                ));
+            }
+        }
+        IncidentsAction::Replay {
+            input,
+            greeting,
+            format,
+        } => replay_review(input, greeting, *format)?,
     }
     Ok(())
 }
@@ -1,41 +1,88 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::cli::notion::ids::DatabaseId;
-use crate::cli::notion::models::search::DatabaseQuery;
+use crate::cli::lib::cache::{cache, get_cached, namespace_dir, NOTION_NAMESPACE};
+use crate::cli::lib::http::build_http_client;
+use crate::cli::lib::rate_limit::RateLimiter;
+use crate::cli::notion::ids::{BlockId, DatabaseId, PageId};
+use crate::cli::notion::models::block::Block;
+use crate::cli::notion::models::error::ErrorResponse;
+use crate::cli::notion::models::paging::Pageable;
+use crate::cli::notion::models::properties::{PropertyConfiguration, PropertyValue};
+use crate::cli::notion::models::search::{
+    DatabaseQuery, FilterCondition, PropertyCondition, TextCondition,
+};
 use crate::cli::notion::models::{ListResponse, Page};
-use crate::cli::notion::NotionApi;
+use crate::cli::notion::notion_version;
+use crate::command::{run_cmd, CommandOptions};
 use anyhow::{Context, Result};
-use once_cell::sync::Lazy;
+use chrono::{DateTime, Utc};
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
 use std::env;
-use std::str::FromStr;
-use tracing::{debug, info};
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{debug, info, warn};
 
 use crate::DEBUG_MODE;
 
+use super::database::ReviewDatabase;
+use super::diff::{diff_page, render_diff};
 use super::incident::Incident;
+use super::number::{NumberFormat, NUMBER_FORMAT};
+use super::target::Target;
 
-// incident selection db
-pub static INCIDENT_DB_ID: Lazy<DatabaseId> = Lazy::new(|| {
-    if *DEBUG_MODE {
-        // incident selection db for testing
-        DatabaseId::from_str("10e6d9dcb4e980f8ae73c4aa2da176cd").expect("Invalid Database ID")
-    } else {
-        // incident selection db for production
-        DatabaseId::from_str("a8da55dadb524e7db202b4dfd799d9ce").expect("Invalid Database ID")
-    }
-});
+/// The title property of the incident selection database
+const NAME_PROPERTY: &str = "Name";
 
-// incident selection db names
-pub static INCIDENT_DB_NAME: Lazy<String> = Lazy::new(|| {
-    if *DEBUG_MODE {
-        "Incident Selection (Debug)".to_owned()
-    } else {
-        "Incident Selection".to_owned()
-    }
-});
+/// The status property of the incident selection database
+const STATUS_PROPERTY: &str = "Status";
+
+/// The status of incidents whose review is done
+const REVIEWED_STATUS: &str = "Reviewed";
+
+/// The status of newly inserted incidents
+const TO_REVIEW_STATUS: &str = "To Review";
+
+/// The select property holding an incident's priority, e.g. "P1"
+const PRIORITY_PROPERTY: &str = "Priority";
+
+/// The date property recording when an incident was created in its source
+const CREATED_PROPERTY: &str = "Created";
+
+/// The url property linking to the incident in its source
+const LINK_PROPERTY: &str = "link";
+
+/// The property linking to an incident's postmortem doc
+const POSTMORTEM_PROPERTY: &str = "Postmortem";
+
+/// The default name of the people property holding an incident's POCs
+const DEFAULT_POC_PROPERTY: &str = "PoC(s)";
+
+/// The date property recording when an incident was selected for review
+const SELECTED_AT_PROPERTY: &str = "Selected At";
+
+/// The text property recording who selected an incident for review
+const SELECTED_BY_PROPERTY: &str = "Selected By";
+
+/// The default name of the relation property linking an incident to its services
+const DEFAULT_SERVICES_PROPERTY: &str = "Services";
+
+/// How long the schema of the incident selection database is cached for, in seconds
+const SCHEMA_TTL_SECS: u64 = 600;
+
+/// The Notion API used by the raw requests that bypass `NotionApi`
+const NOTION_API_URL: &str = "https://api.notion.com/v1";
+
+/// Notion allows an average of three requests per second per integration
+const DEFAULT_RATE_LIMIT: f64 = 3.0;
+
+/// How many times a raw request rate limited or conflicting in Notion is retried
+const MAX_RETRIES: u32 = 3;
 
 /// Macro for debugging Notion database properties.
 ///
@@ -61,12 +108,11 @@ pub static INCIDENT_DB_NAME: Lazy<String> = Lazy::new(|| {
 #[allow(unused_macros)]
 macro_rules! debug_prop {
     ($notion:expr, $prop:expr) => {
-        let db = $notion
-            .client
-            .get_database(INCIDENT_DB_ID.clone())
+        let properties = $notion
+            .database_properties()
             .await
             .expect("Failed to get database");
-        let prop = db.properties.get($prop).unwrap();
+        let prop = properties.get($prop).unwrap();
         match prop {
             PropertyConfiguration::MultiSelect {
                 multi_select,
@@ -95,8 +141,95 @@ macro_rules! debug_prop {
 }
 
 pub struct Notion {
-    client: NotionApi,
     token: String,
+    /// The people property incident POCs are written to, from `NOTION_POC_PROPERTY`
+    poc_property: String,
+    /// The pages of the services incidents are related to
+    services: ServicePages,
+    /// Who is running the review, from `SUIOP_OPERATOR` or the git user name
+    operator: Option<String>,
+    /// Caps the rate of every request to the Notion API, from `NOTION_RATE_LIMIT`
+    limiter: RateLimiter,
+    /// The base url for raw API requests, without a trailing slash
+    base_url: String,
+    /// The Notion-Version header for raw API requests, from `NOTION_VERSION`
+    version: String,
+    /// The proxy aware client for raw API requests
+    http: reqwest::Client,
+    /// The name of the incident selection database, for messages
+    db_name: String,
+    db_id: DatabaseId,
+    /// Where the database schema is cached
+    cache_dir: PathBuf,
+}
+
+/// Resolves an incident's service tags to pages of the "Services" database.
+#[derive(Debug, Clone, Default)]
+pub struct ServicePages {
+    /// The relation property the pages are written to, from `NOTION_SERVICES_PROPERTY`
+    property: String,
+    /// Page ids by service tag, from the JSON file at `NOTION_SERVICES_FILE`
+    pages: HashMap<String, String>,
+}
+
+impl ServicePages {
+    pub fn new(property: &str, pages: HashMap<String, String>) -> Self {
+        Self {
+            property: property.to_owned(),
+            pages,
+        }
+    }
+
+    /// Read the service pages from the environment. Without `NOTION_SERVICES_FILE`
+    /// no services are known.
+    fn from_env() -> Result<Self> {
+        let property = env::var("NOTION_SERVICES_PROPERTY")
+            .unwrap_or_else(|_| DEFAULT_SERVICES_PROPERTY.to_owned());
+        let pages = match env::var("NOTION_SERVICES_FILE") {
+            Ok(path) => {
+                let contents = std::fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {}", path))?;
+                serde_json::from_str(&contents).with_context(|| {
+                    format!("{} is not a JSON object of service tags to page ids", path)
+                })?
+            }
+            Err(_) => HashMap::new(),
+        };
+        Ok(Self::new(&property, pages))
+    }
+
+    /// The ids of the pages for the incident's tags, in tag order.
+    fn related_pages(&self, incident: &Incident) -> Vec<String> {
+        let mut ids: Vec<String> = vec![];
+        for id in incident.tags.iter().filter_map(|tag| self.pages.get(tag)) {
+            if !ids.contains(id) {
+                ids.push(id.clone());
+            }
+        }
+        ids
+    }
+}
+
+/// Who is running the review: `SUIOP_OPERATOR` if set, otherwise the git user name.
+fn operator() -> Option<String> {
+    env::var("SUIOP_OPERATOR")
+        .ok()
+        .or_else(|| {
+            let output = run_cmd(
+                vec!["git", "config", "--get", "user.name"],
+                Some(CommandOptions::new(false, false)),
+            )
+            .ok()?;
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        })
+        .filter(|operator| !operator.trim().is_empty())
+}
+
+/// When and by whom an incident was selected for review.
+#[derive(Debug, Clone)]
+pub struct Selection<'a> {
+    pub at: DateTime<Utc>,
+    pub by: Option<&'a str>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -113,44 +246,293 @@ pub struct NotionPersonDetails {
     pub email: String,
 }
 impl Notion {
-    pub fn new() -> Self {
+    pub fn new(target: Target) -> Self {
         let token = env::var("NOTION_API_TOKEN")
             .expect("Please set the NOTION_API_TOKEN environment variable");
         debug!("using notion token {}", token);
-        let client = NotionApi::new(token.clone()).expect("Failed to create Notion API client");
-        Self { client, token }
+        let version = notion_version().expect("Invalid NOTION_VERSION");
+        Self::with_base_url(token, NOTION_API_URL, version, target)
+    }
+
+    /// Build a client whose raw requests go to `base_url` instead of the Notion API,
+    /// sending `version` as their Notion-Version header
+    pub fn with_base_url(token: String, base_url: &str, version: String, target: Target) -> Self {
+        let poc_property =
+            env::var("NOTION_POC_PROPERTY").unwrap_or_else(|_| DEFAULT_POC_PROPERTY.to_owned());
+        let rate_limit = env::var("NOTION_RATE_LIMIT")
+            .ok()
+            .map(|r| {
+                r.parse::<f64>()
+                    .ok()
+                    .filter(|r| *r > 0.0)
+                    .expect("NOTION_RATE_LIMIT must be a positive number of requests per second")
+            })
+            .unwrap_or(DEFAULT_RATE_LIMIT);
+        Self {
+            token,
+            poc_property,
+            services: ServicePages::from_env().expect("Invalid NOTION_SERVICES_FILE"),
+            operator: operator(),
+            limiter: RateLimiter::new(rate_limit),
+            base_url: base_url.to_owned(),
+            version,
+            http: build_http_client().expect("failed to build reqwest client"),
+            db_name: target.incident_db_name().to_owned(),
+            db_id: target.incident_db_id(),
+            cache_dir: namespace_dir(NOTION_NAMESPACE).expect("invalid notion cache namespace"),
+        }
+    }
+
+    /// Use `db` as the incident selection database instead of the target's.
+    pub fn with_database(mut self, db: &ReviewDatabase) -> Self {
+        self.db_name = db.name.clone();
+        self.db_id = db.id.clone();
+        self
+    }
+
+    fn schema_cache_key(&self) -> String {
+        format!("notion_schema_{}", self.db_id)
+    }
+
+    /// The properties of the incident selection database by name, cached for
+    /// [`SCHEMA_TTL_SECS`] since every check would otherwise fetch them again.
+    pub async fn database_properties(&self) -> Result<HashMap<String, PropertyConfiguration>> {
+        let key = self.schema_cache_key();
+        if let Some(cached) =
+            get_cached::<HashMap<String, PropertyConfiguration>>(&key, &self.cache_dir)?
+        {
+            let age_secs = cached
+                .metadata
+                .modified()?
+                .elapsed()
+                .map_or(0, |age| age.as_secs());
+            if age_secs < SCHEMA_TTL_SECS {
+                debug!("Using the cached schema of database {}", self.db_id);
+                return Ok(cached.value);
+            }
+        }
+        let url = format!("{}/databases/{}", self.base_url, self.db_id);
+        let request = self
+            .http
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .header("Notion-Version", &self.version);
+        let response = self
+            .notion_request_with_retry(request)
+            .await
+            .context("getting incident selection database")?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to get database {}: {}",
+                self.db_id,
+                response.text().await.unwrap_or_default()
+            ));
+        }
+        let db = response.json::<serde_json::Value>().await?;
+        let properties: HashMap<String, PropertyConfiguration> =
+            serde_json::from_value(db["properties"].clone())
+                .context("parsing the incident selection database properties")?;
+        create_dir_all(&self.cache_dir)?;
+        cache(&key, properties, &self.cache_dir)
+    }
+
+    /// Drop the cached database schema, so the next check fetches it again.
+    pub fn forget_schema(&self) -> Result<()> {
+        let path = self.cache_dir.join(self.schema_cache_key());
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Check that the incident selection database has the properties we write to
+    ///
+    /// The POC property must be there. Any other property that's missing or of a
+    /// type we can't write is left out of inserted pages, with a warning.
+    pub async fn check_schema(&self) -> Result<()> {
+        let properties = self.database_properties().await?;
+        match properties.get(&self.poc_property) {
+            Some(PropertyConfiguration::People { .. }) => {}
+            Some(other) => {
+                return Err(anyhow::anyhow!(
+                    "Notion property '{}' is not a people property: {:?}",
+                    self.poc_property,
+                    other
+                ))
+            }
+            None => return Err(anyhow::anyhow!(
+                "Notion database {:?} has no '{}' property, set NOTION_POC_PROPERTY to the name of its POC property",
+                self.db_name,
+                self.poc_property
+            )),
+        }
+        let (_, problems) = PageSchema::read(&properties);
+        for problem in problems {
+            warn!(
+                "Notion database {:?}: {}, so it's left out of inserted pages",
+                self.db_name, problem
+            );
+        }
+        Ok(())
+    }
+
+    /// How inserted pages have to be written for the incident selection database
+    async fn page_schema(&self) -> Result<PageSchema> {
+        Ok(PageSchema::read(&self.database_properties().await?).0)
     }
 
     /// Get all incidents from the incident selection database
     #[allow(dead_code)]
     pub async fn get_incident_selection_incidents(&self) -> Result<ListResponse<Page>> {
         // Retrieve the db
-        self.client
-            .query_database(INCIDENT_DB_ID.clone(), DatabaseQuery::default())
+        self.query_pages(&DatabaseQuery::default()).await
+    }
+
+    /// Get the incident selection page for an incident number, if one exists
+    pub async fn get_incident_page(&self, number: u64) -> Result<Option<Page>> {
+        let pages = self
+            .query_pages(&incident_page_query(number))
             .await
-            .map_err(|e| anyhow::anyhow!(e))
+            .with_context(|| format!("querying notion for incident {}", number))?;
+        Ok(pick_incident_page(pages, number))
+    }
+
+    /// Query the incident selection database for a page of results
+    async fn query_pages(&self, query: &DatabaseQuery) -> Result<ListResponse<Page>> {
+        let url = format!("{}/databases/{}/query", self.base_url, self.db_id);
+        let request = self
+            .http
+            .post(url)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .header("Notion-Version", &self.version)
+            .json(query);
+        let response = self.notion_request_with_retry(request).await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to query database {}: {}",
+                self.db_id,
+                response.text().await.unwrap_or_default()
+            ));
+        }
+        response
+            .json::<ListResponse<Page>>()
+            .await
+            .context("parsing the database query results")
+    }
+
+    /// The selection recorded on pages inserted now
+    fn selection(&self) -> Selection<'_> {
+        Selection {
+            at: Utc::now(),
+            by: self.operator.as_deref(),
+        }
+    }
+
+    /// Describe how inserting the incident would change its page, writing nothing
+    pub async fn diff_incident(&self, incident: &Incident) -> Result<String> {
+        let page = self.get_incident_page(incident.number).await?;
+        let schema = self.page_schema().await?;
+        let body = incident_page_body(
+            incident,
+            &self.db_id,
+            &self.poc_property,
+            &self.services,
+            &self.selection(),
+            &schema,
+        );
+        // an existing page is updated, which keeps its status
+        let body = match page {
+            Some(_) => incident_page_update(&body),
+            None => body,
+        };
+        Ok(render_diff(
+            incident.number,
+            page.is_some(),
+            &diff_page(page.as_ref(), &body),
+        ))
+    }
+
+    /// Whether the incident's page links to a postmortem doc
+    pub async fn has_postmortem(&self, number: u64) -> Result<bool> {
+        Ok(self
+            .get_incident_page(number)
+            .await?
+            .as_ref()
+            .is_some_and(page_has_postmortem))
+    }
+
+    /// Get the incidents whose review is marked as done
+    pub async fn get_reviewed_incidents(&self) -> Result<Vec<Incident>> {
+        let mut incidents = vec![];
+        let mut cursor = None;
+        loop {
+            let pages = self
+                .query_pages(&DatabaseQuery::default().start_from(cursor))
+                .await
+                .context("querying notion for reviewed incidents")?;
+            incidents.extend(pages.results.iter().filter_map(reviewed_incident));
+            if !pages.has_more {
+                return Ok(incidents);
+            }
+            cursor = pages.next_cursor;
+        }
+    }
+
+    /// Send a raw request, waiting as long as Notion's `Retry-After` header says and
+    /// sending it again when it's rate limited (429) or conflicts with another
+    /// write (409), up to [`MAX_RETRIES`] times. Any other response is returned as is.
+    async fn notion_request_with_retry(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let mut retries = 0;
+        loop {
+            let attempt = request
+                .try_clone()
+                .context("a Notion request with a streamed body can't be retried")?;
+            self.limiter.acquire().await;
+            let response = attempt.send().await?;
+            let status = response.status();
+            if retries >= MAX_RETRIES
+                || !matches!(status, StatusCode::TOO_MANY_REQUESTS | StatusCode::CONFLICT)
+            {
+                return Ok(response);
+            }
+            let wait_secs = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(1);
+            retries += 1;
+            warn!(
+                "Notion answered {}, retrying in {}s (retry {} of {})",
+                status, wait_secs, retries, MAX_RETRIES
+            );
+            tokio::time::sleep(Duration::from_secs(wait_secs)).await;
+        }
     }
 
     /// Get all people objects from the Notion API
     pub async fn get_all_people(&self) -> Result<Vec<NotionPerson>> {
-        let url = "https://api.notion.com/v1/users";
-        let client = reqwest::Client::new();
+        let url = format!("{}/users", self.base_url);
+        let client = &self.http;
         let mut all_people = Vec::new();
         let mut has_more = true;
         let mut start_cursor: Option<String> = None;
 
         while has_more {
             let mut request = client
-                .get(url)
+                .get(&url)
                 .header("Authorization", format!("Bearer {}", self.token))
-                .header("Notion-Version", "2022-06-28");
+                .header("Notion-Version", &self.version);
 
             if let Some(ref cursor) = start_cursor {
                 request = request.query(&[("start_cursor", cursor)]);
             }
 
-            let response = request
-                .send()
+            let response = self
+                .notion_request_with_retry(request)
                 .await
                 .map_err(|e| anyhow::anyhow!("Failed to send request: {}", e))?;
 
@@ -176,9 +558,9 @@ impl Notion {
             // Extract people from this page
             let people: Vec<NotionPerson> =
                 serde_json::from_value(json_response["results"].clone())
-                    .map(|s: Vec<NotionPerson>| {
+                    .inspect(|s: &Vec<NotionPerson>| {
                         if *DEBUG_MODE {
-                            for person in &s {
+                            for person in s {
                                 debug!(
                                     "Notion person: id={}, name={}, has_person={}",
                                     person.id,
@@ -190,7 +572,6 @@ impl Notion {
                                 }
                             }
                         }
-                        s
                     })
                     .map_err(|e| anyhow::anyhow!("Failed to deserialize people: {}", e))?;
 
@@ -216,68 +597,1385 @@ impl Notion {
         Ok(all_people)
     }
 
+    /// Get the text of the paragraph and bulleted list blocks in the body of a page
+    #[allow(dead_code)]
+    pub async fn get_page_blocks(&self, page_id: &PageId) -> Result<Vec<String>> {
+        let block_id = BlockId::from(page_id.clone());
+        let url = format!("{}/blocks/{}/children", self.base_url, block_id);
+        let mut lines = Vec::new();
+        let mut start_cursor = None;
+        loop {
+            let mut request = self
+                .http
+                .get(&url)
+                .header("Authorization", format!("Bearer {}", self.token))
+                .header("Notion-Version", &self.version);
+            if let Some(cursor) = &start_cursor {
+                request = request.query(&[("start_cursor", cursor)]);
+            }
+            let response = self
+                .notion_request_with_retry(request)
+                .await
+                .context("getting page blocks")?;
+            if !response.status().is_success() {
+                return Err(anyhow::anyhow!(
+                    "Failed to get the blocks of page {}: {}",
+                    page_id,
+                    response.text().await.unwrap_or_default()
+                ));
+            }
+            let response = response
+                .json::<ListResponse<Block>>()
+                .await
+                .context("parsing page blocks")?;
+            lines.extend(response.results.iter().filter_map(|b| b.plain_text()));
+            if !response.has_more || response.next_cursor.is_none() {
+                break;
+            }
+            start_cursor = response.next_cursor;
+        }
+        debug!("read {} text blocks from page {}", lines.len(), page_id);
+        Ok(lines)
+    }
+
     /// Get the shape of the incident selection database to understand the data model
     #[allow(dead_code)]
     pub async fn get_shape(self) -> Result<()> {
-        let db = self.client.get_database(INCIDENT_DB_ID.clone()).await?;
-        println!("{:#?}", db.properties);
+        println!("{:#?}", self.database_properties().await?);
         Ok(())
     }
 
-    /// Insert a suiop incident into the incident selection database
-    pub async fn insert_incident(&self, incident: Incident) -> Result<()> {
-        let url = "https://api.notion.com/v1/pages";
-        let body = json!({
-            "parent": { "database_id": INCIDENT_DB_ID.to_string() },
-            "properties": {
-                "Name": {
-                    "title": [{
-                        "text": {
-                            "content":format!("{}: {}", incident.number, incident.title)
-                        }
-                    }]
-                },
-                "link": {
-                    "url": incident.html_url,
-                },
-                "PoC(s)": {
-                    "people": incident.poc_users.unwrap_or_else(|| panic!("no poc users for incident {}", incident.number)).iter().filter_map(|u| {
-                        u.notion_user.as_ref().map(|u| {
-                            json!({
-                                "object": "user",
-                                "id": u.id.clone(),
-                            })
-                        })
-                    }).collect::<Vec<_>>(),
-                },
+    /// Insert a suiop incident into the incident selection database, or update its
+    /// page if it already has one, so inserting an incident again doesn't duplicate it.
+    ///
+    /// A new page's body holds the `description`, if given, and a link to the
+    /// incident's Slack channel. An existing page's body is left as it is.
+    ///
+    /// Returns the url of the page.
+    pub async fn insert_incident(
+        &self,
+        incident: Incident,
+        description: Option<&str>,
+    ) -> Result<String> {
+        let schema = self.page_schema().await?;
+        let body = incident_page_body(
+            &incident,
+            &self.db_id,
+            &self.poc_property,
+            &self.services,
+            &self.selection(),
+            &schema,
+        );
+        let page_id = self.get_incident_page(incident.number).await?.map(|p| p.id);
+        let request = match page_id {
+            Some(page_id) => {
+                debug!(
+                    "updating the page {} of incident {}",
+                    page_id, incident.number
+                );
+                self.http
+                    .patch(format!("{}/pages/{}", self.base_url, page_id))
+                    .json(&incident_page_update(&body))
             }
-        });
+            None => {
+                let children = incident_page_children(&incident, description);
+                let mut body = body;
+                if !children.is_empty() {
+                    body["children"] = json!(children);
+                }
+                self.http
+                    .post(format!("{}/pages", self.base_url))
+                    .json(&body)
+            }
+        };
 
-        let client = reqwest::ClientBuilder::new()
-            // .default_headers(headers)
-            .build()
-            .expect("failed to build reqwest client");
-        let response = client
-            .post(url)
+        let request = request
             .header("Authorization", format!("Bearer {}", self.token))
             .header("Content-Type", "application/json")
-            .header("Notion-Version", "2021-05-13")
-            .json(&body)
-            .send()
+            .header("Notion-Version", &self.version);
+        let response = self
+            .notion_request_with_retry(request)
             .await
             .context("sending insert db row")?;
 
         if response.status().is_success() {
-            debug!(
-                "inserted incident: {:?}",
-                response.text().await.context("getting response text")?
-            );
-            Ok(())
+            let text = response.text().await.context("getting response text")?;
+            debug!("inserted incident: {:?}", text);
+            let page: serde_json::Value =
+                serde_json::from_str(&text).context("parsing the inserted page")?;
+            page["url"]
+                .as_str()
+                .map(str::to_owned)
+                .with_context(|| format!("the page of incident {} has no url", incident.number))
         } else {
-            Err(anyhow::anyhow!(
-                "Failed to insert incident: {:?}",
-                response.text().await.context("getting response text")?
+            Err(insert_error(
+                &response.text().await.context("getting response text")?,
             ))
         }
     }
 }
+
+/// The query for the pages whose title starts with the incident's number.
+fn incident_page_query(number: u64) -> DatabaseQuery {
+    DatabaseQuery {
+        filter: Some(FilterCondition::Property {
+            property: NAME_PROPERTY.to_owned(),
+            condition: PropertyCondition::Title(TextCondition::StartsWith(incident_title_prefix(
+                &NUMBER_FORMAT,
+                number,
+            ))),
+        }),
+        ..Default::default()
+    }
+}
+
+/// The start of the title of an incident's page, e.g. "42: "
+fn incident_title_prefix(format: &NumberFormat, number: u64) -> String {
+    format!("{}: ", format.format(number))
+}
+
+/// Split a page title into the incident number and the incident's own title.
+fn split_title<'a>(format: &NumberFormat, title: &'a str) -> Option<(u64, &'a str)> {
+    let (number, title) = title.split_once(": ")?;
+    Some((format.parse(number)?, title))
+}
+
+/// Pick the page for an incident number out of query results.
+///
+/// The query matches on a title prefix, so the titles are checked again here.
+fn pick_incident_page(pages: ListResponse<Page>, number: u64) -> Option<Page> {
+    let prefix = incident_title_prefix(&NUMBER_FORMAT, number);
+    pages
+        .results
+        .into_iter()
+        .find(|page| page.title().is_some_and(|t| t.starts_with(&prefix)))
+}
+
+/// The incident an incident selection page is about, if its review is done.
+///
+/// Only what the page records is filled in: the number and title from the page
+/// title, and the url and source id from its link.
+fn reviewed_incident(page: &Page) -> Option<Incident> {
+    if page_status(page)? != REVIEWED_STATUS {
+        return None;
+    }
+    let title = page.title()?;
+    let (number, title) = split_title(&NUMBER_FORMAT, &title)?;
+    let html_url = text_property(page, LINK_PROPERTY).unwrap_or_default();
+    // the incident's id in the source is the last segment of its url
+    let source_id = html_url
+        .rsplit('/')
+        .next()
+        .filter(|id| !id.is_empty())
+        .map(str::to_owned);
+    Some(Incident {
+        number,
+        title: title.to_owned(),
+        source_id,
+        html_url,
+        ..Default::default()
+    })
+}
+
+/// The value of a url or text property of a page, if it is set and not blank.
+fn text_property(page: &Page, name: &str) -> Option<String> {
+    let value = match page.properties.properties.get(name)? {
+        PropertyValue::Url { url, .. } => url.clone()?,
+        PropertyValue::Text { rich_text, .. } => rich_text.iter().map(|t| t.plain_text()).collect(),
+        _ => return None,
+    };
+    (!value.trim().is_empty()).then_some(value)
+}
+
+/// Whether an incident selection page links to a postmortem doc.
+fn page_has_postmortem(page: &Page) -> bool {
+    text_property(page, POSTMORTEM_PROPERTY).is_some()
+}
+
+/// The value of an incident selection page's status property, which may be a
+/// status or a select property.
+fn page_status(page: &Page) -> Option<String> {
+    match page.properties.properties.get(STATUS_PROPERTY)? {
+        PropertyValue::Status { status, .. } => status.as_ref()?.name.clone(),
+        PropertyValue::Select { select, .. } => select.as_ref()?.name.clone(),
+        _ => None,
+    }
+}
+
+/// How the incident selection database stores the status of its pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatusType {
+    /// A Notion status property
+    Status,
+    /// A select property
+    Select,
+}
+
+/// Which of the properties inserts write, besides the title, link and POCs, the
+/// incident selection database has with a type we can write, and how it stores
+/// the status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PageSchema {
+    /// `None` when the database has no status or select Status property
+    status: Option<StatusType>,
+    priority: bool,
+    created: bool,
+    selected_at: bool,
+    selected_by: bool,
+}
+
+impl Default for PageSchema {
+    /// A database with every property, whose Status is a select
+    fn default() -> Self {
+        Self {
+            status: Some(StatusType::Select),
+            priority: true,
+            created: true,
+            selected_at: true,
+            selected_by: true,
+        }
+    }
+}
+
+impl PageSchema {
+    /// Read the schema from the database's `properties`, along with why each
+    /// property that's left out of inserted pages is.
+    fn read(properties: &HashMap<String, PropertyConfiguration>) -> (Self, Vec<String>) {
+        let mut problems = vec![];
+        let mut check = |name: &str, expected: &str, matches: bool| {
+            match properties.get(name) {
+                None => problems.push(format!("there is no '{}' property", name)),
+                Some(_) if !matches => {
+                    problems.push(format!("'{}' isn't a {} property", name, expected))
+                }
+                Some(_) => {}
+            }
+            matches
+        };
+        let status = match properties.get(STATUS_PROPERTY) {
+            Some(PropertyConfiguration::Status { .. }) => Some(StatusType::Status),
+            Some(PropertyConfiguration::Select { .. }) => Some(StatusType::Select),
+            _ => {
+                check(STATUS_PROPERTY, "status or select", false);
+                None
+            }
+        };
+        let schema = Self {
+            status,
+            priority: check(
+                PRIORITY_PROPERTY,
+                "select",
+                matches!(
+                    properties.get(PRIORITY_PROPERTY),
+                    Some(PropertyConfiguration::Select { .. })
+                ),
+            ),
+            created: check(
+                CREATED_PROPERTY,
+                "date",
+                matches!(
+                    properties.get(CREATED_PROPERTY),
+                    Some(PropertyConfiguration::Date { .. })
+                ),
+            ),
+            selected_at: check(
+                SELECTED_AT_PROPERTY,
+                "date",
+                matches!(
+                    properties.get(SELECTED_AT_PROPERTY),
+                    Some(PropertyConfiguration::Date { .. })
+                ),
+            ),
+            selected_by: check(
+                SELECTED_BY_PROPERTY,
+                "text",
+                matches!(
+                    properties.get(SELECTED_BY_PROPERTY),
+                    Some(PropertyConfiguration::Text { .. })
+                ),
+            ),
+        };
+        (schema, problems)
+    }
+}
+
+/// Build the page create body for an incident in the `db_id` database, writing its
+/// POCs to `poc_property`, relating it to its known `services` and recording its
+/// `selection`. An incident without POC users gets an empty people list, and its
+/// priority and creation date are left out when unknown. Properties `schema` says
+/// the database doesn't have are left out too, and the status is written the way
+/// the database stores it.
+fn incident_page_body(
+    incident: &Incident,
+    db_id: &DatabaseId,
+    poc_property: &str,
+    services: &ServicePages,
+    selection: &Selection,
+    schema: &PageSchema,
+) -> serde_json::Value {
+    let mut body = json!({
+        "parent": { "database_id": db_id.to_string() },
+        "properties": {
+            NAME_PROPERTY: {
+                "title": [{
+                    "text": {
+                        "content": format!("{}{}", incident_title_prefix(&NUMBER_FORMAT, incident.number), incident.title)
+                    }
+                }]
+            },
+            LINK_PROPERTY: {
+                "url": incident.html_url,
+            },
+        }
+    });
+    if schema.selected_at {
+        body["properties"][SELECTED_AT_PROPERTY] = json!({
+            "date": { "start": selection.at.format("%Y-%m-%d").to_string() },
+        });
+    }
+    match schema.status {
+        Some(StatusType::Status) => {
+            body["properties"][STATUS_PROPERTY] = json!({
+                "status": { "name": TO_REVIEW_STATUS },
+            })
+        }
+        Some(StatusType::Select) => {
+            body["properties"][STATUS_PROPERTY] = json!({
+                "select": { "name": TO_REVIEW_STATUS },
+            })
+        }
+        None => {}
+    }
+    if let Some(priority) = incident.priority.as_ref().filter(|_| schema.priority) {
+        body["properties"][PRIORITY_PROPERTY] = json!({
+            "select": { "name": priority.name },
+        });
+    }
+    if let Some(created) = incident.created_at_utc().filter(|_| schema.created) {
+        body["properties"][CREATED_PROPERTY] = json!({
+            "date": { "start": created.to_rfc3339() },
+        });
+    }
+    if let Some(by) = selection.by.filter(|_| schema.selected_by) {
+        body["properties"][SELECTED_BY_PROPERTY] = json!({
+            "rich_text": [{ "text": { "content": by } }],
+        });
+    }
+    body["properties"][poc_property] = json!({
+        "people": incident.poc_users.iter().flatten().filter_map(|u| {
+            u.notion_user.as_ref().map(|u| {
+                json!({
+                    "object": "user",
+                    "id": u.id.clone(),
+                })
+            })
+        }).collect::<Vec<_>>(),
+    });
+    let related = services.related_pages(incident);
+    // the property is left out rather than cleared when no services are known
+    if !related.is_empty() {
+        body["properties"][&services.property] = json!({
+            "relation": related.iter().map(|id| json!({ "id": id })).collect::<Vec<_>>(),
+        });
+    }
+    body
+}
+
+/// Notion rejects rich text whose content is longer than this.
+const MAX_RICH_TEXT: usize = 2000;
+
+/// Rich text holding `text`, split into as many pieces as Notion's length limit needs.
+fn rich_text(text: &str) -> Vec<serde_json::Value> {
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .chunks(MAX_RICH_TEXT)
+        .map(|chunk| {
+            json!({
+                "type": "text",
+                "text": { "content": chunk.iter().collect::<String>() },
+            })
+        })
+        .collect()
+}
+
+/// The blocks of a new incident page: the `description` under a heading, one
+/// paragraph per paragraph of it, and a link to the incident's Slack channel.
+fn incident_page_children(
+    incident: &Incident,
+    description: Option<&str>,
+) -> Vec<serde_json::Value> {
+    let mut children = vec![];
+    let paragraphs: Vec<&str> = description
+        .into_iter()
+        .flat_map(|d| d.split("\n\n"))
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+    if !paragraphs.is_empty() {
+        children.push(json!({
+            "object": "block",
+            "type": "heading_2",
+            "heading_2": { "rich_text": rich_text("Description") },
+        }));
+        children.extend(paragraphs.into_iter().map(|p| {
+            json!({
+                "object": "block",
+                "type": "paragraph",
+                "paragraph": { "rich_text": rich_text(p) },
+            })
+        }));
+    }
+    if let Some(channel) = &incident.slack_channel {
+        children.push(json!({
+            "object": "block",
+            "type": "paragraph",
+            "paragraph": {
+                "rich_text": [
+                    { "type": "text", "text": { "content": "Slack channel: " } },
+                    {
+                        "type": "text",
+                        "text": {
+                            "content": format!("#{}", channel.name),
+                            "link": { "url": channel.clone().url() },
+                        },
+                    },
+                ],
+            },
+        }));
+    }
+    children
+}
+
+/// The update of an existing page to the properties of the page create `body`,
+/// keeping its status so an incident inserted again isn't put back up for review.
+fn incident_page_update(body: &serde_json::Value) -> serde_json::Value {
+    let mut properties = body["properties"].clone();
+    if let Some(properties) = properties.as_object_mut() {
+        properties.remove(STATUS_PROPERTY);
+    }
+    json!({ "properties": properties })
+}
+
+/// Build the error for a failed insert, naming the rejected property when Notion
+/// reports a validation error about one.
+fn insert_error(response_text: &str) -> anyhow::Error {
+    match serde_json::from_str::<ErrorResponse>(response_text) {
+        Ok(error) => match error.property() {
+            Some(property) => {
+                anyhow::anyhow!("Notion rejected property '{}': {}", property, error.message)
+            }
+            None => anyhow::anyhow!("Failed to insert incident: {}", error.message),
+        },
+        Err(_) => anyhow::anyhow!("Failed to insert incident: {:?}", response_text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::incidents::pd::Priority;
+    use crate::cli::incidents::user::User;
+    use crate::cli::notion::{parse_notion_version, NOTION_API_VERSION};
+    use crate::cli::slack::Channel;
+
+    fn notion_person(id: &str) -> NotionPerson {
+        NotionPerson {
+            object: "user".to_owned(),
+            id: id.to_owned(),
+            name: "Jane".to_owned(),
+            avatar_url: None,
+            person: None,
+        }
+    }
+
+    fn selection(by: Option<&str>) -> Selection<'_> {
+        Selection {
+            at: "2024-03-04T17:30:00Z".parse().unwrap(),
+            by,
+        }
+    }
+
+    #[test]
+    fn test_incident_page_body_records_selection() {
+        let incident = Incident {
+            number: 42,
+            poc_users: Some(vec![]),
+            ..Default::default()
+        };
+        let body = incident_page_body(
+            &incident,
+            &Target::Debug.incident_db_id(),
+            DEFAULT_POC_PROPERTY,
+            &ServicePages::default(),
+            &selection(Some("Jane Doe")),
+            &PageSchema::default(),
+        );
+        assert_eq!(
+            body["properties"]["Selected At"],
+            json!({"date": {"start": "2024-03-04"}})
+        );
+        assert_eq!(
+            body["properties"]["Selected By"],
+            json!({"rich_text": [{"text": {"content": "Jane Doe"}}]})
+        );
+
+        // without a known operator only the date is recorded
+        let body = incident_page_body(
+            &incident,
+            &Target::Debug.incident_db_id(),
+            DEFAULT_POC_PROPERTY,
+            &ServicePages::default(),
+            &selection(None),
+            &PageSchema::default(),
+        );
+        assert!(body["properties"].get("Selected By").is_none());
+        assert!(body["properties"].get("Selected At").is_some());
+    }
+
+    #[test]
+    fn test_incident_page_body_uses_poc_property() {
+        let incident = Incident {
+            number: 42,
+            title: "Validators halted".to_owned(),
+            html_url: "https://pagerduty.com/incidents/42".to_owned(),
+            poc_users: Some(vec![User::new(None, Some(notion_person("abc"))).unwrap()]),
+            ..Default::default()
+        };
+
+        let body = incident_page_body(
+            &incident,
+            &Target::Debug.incident_db_id(),
+            "Owners",
+            &ServicePages::default(),
+            &selection(None),
+            &PageSchema::default(),
+        );
+
+        assert_eq!(
+            body["properties"]["Owners"],
+            json!({"people": [{"object": "user", "id": "abc"}]})
+        );
+        assert!(body["properties"].get(DEFAULT_POC_PROPERTY).is_none());
+        assert_eq!(
+            body["properties"]["Name"]["title"][0]["text"]["content"],
+            "42: Validators halted"
+        );
+    }
+
+    #[test]
+    fn test_incident_page_body_records_priority_created_and_status() {
+        let incident = Incident {
+            number: 42,
+            priority: Some(Priority::from_name("P1")),
+            created_at: Some("2024-03-04T17:00:00Z".to_owned()),
+            ..Default::default()
+        };
+        let body = incident_page_body(
+            &incident,
+            &Target::Debug.incident_db_id(),
+            DEFAULT_POC_PROPERTY,
+            &ServicePages::default(),
+            &selection(None),
+            &PageSchema::default(),
+        );
+        assert_eq!(
+            body["properties"]["Priority"],
+            json!({"select": {"name": "P1"}})
+        );
+        assert_eq!(
+            body["properties"]["Created"],
+            json!({"date": {"start": "2024-03-04T17:00:00+00:00"}})
+        );
+        assert_eq!(
+            body["properties"]["Status"],
+            json!({"select": {"name": "To Review"}})
+        );
+        // updating an existing page keeps its status
+        let update = incident_page_update(&body);
+        assert!(update["properties"].get("Status").is_none());
+        assert_eq!(
+            update["properties"]["Priority"],
+            body["properties"]["Priority"]
+        );
+
+        // unknown values are left out rather than sent as null
+        let body = incident_page_body(
+            &Incident {
+                number: 43,
+                created_at: Some("yesterday".to_owned()),
+                ..Default::default()
+            },
+            &Target::Debug.incident_db_id(),
+            DEFAULT_POC_PROPERTY,
+            &ServicePages::default(),
+            &selection(None),
+            &PageSchema::default(),
+        );
+        assert!(body["properties"].get("Priority").is_none());
+        assert!(body["properties"].get("Created").is_none());
+    }
+
+    #[test]
+    fn test_incident_page_children() {
+        let incident = Incident {
+            number: 42,
+            slack_channel: Some(Channel {
+                id: "C0123".to_owned(),
+                name: "incident-42".to_owned(),
+            }),
+            ..Default::default()
+        };
+        let description = format!("Validators halted.\n\n{}", "x".repeat(4500));
+        let children = incident_page_children(&incident, Some(&description));
+
+        let types: Vec<&str> = children
+            .iter()
+            .map(|c| c["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            vec!["heading_2", "paragraph", "paragraph", "paragraph"]
+        );
+        assert_eq!(
+            children[1]["paragraph"]["rich_text"][0]["text"]["content"],
+            "Validators halted."
+        );
+        // the long paragraph is split to fit Notion's rich text limit
+        let pieces: Vec<usize> = children[2]["paragraph"]["rich_text"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["text"]["content"].as_str().unwrap().len())
+            .collect();
+        assert_eq!(pieces, vec![2000, 2000, 500]);
+        assert_eq!(
+            children[3]["paragraph"]["rich_text"][1]["text"],
+            json!({
+                "content": "#incident-42",
+                "link": { "url": "https://mysten-labs.slack.com/archives/C0123" },
+            })
+        );
+
+        // without a description or channel there is no body
+        assert!(incident_page_children(&Incident::default(), Some("  ")).is_empty());
+    }
+
+    #[test]
+    fn test_incident_page_body_without_pocs() {
+        let incident = Incident {
+            number: 42,
+            title: "Validators halted".to_owned(),
+            poc_users: None,
+            ..Default::default()
+        };
+
+        let body = incident_page_body(
+            &incident,
+            &Target::Debug.incident_db_id(),
+            DEFAULT_POC_PROPERTY,
+            &ServicePages::default(),
+            &selection(None),
+            &PageSchema::default(),
+        );
+
+        assert_eq!(
+            body["properties"][DEFAULT_POC_PROPERTY],
+            json!({"people": []})
+        );
+    }
+
+    #[test]
+    fn test_incident_page_body_relates_services() {
+        let incident = Incident {
+            number: 42,
+            title: "Validators halted".to_owned(),
+            poc_users: Some(vec![]),
+            tags: vec![
+                "Validators".to_owned(),
+                "Infra".to_owned(),
+                "Consensus".to_owned(),
+            ],
+            ..Default::default()
+        };
+        let services = ServicePages::new(
+            DEFAULT_SERVICES_PROPERTY,
+            HashMap::from([
+                ("Validators".to_owned(), "page-validators".to_owned()),
+                ("Consensus".to_owned(), "page-consensus".to_owned()),
+            ]),
+        );
+
+        let body = incident_page_body(
+            &incident,
+            &Target::Debug.incident_db_id(),
+            DEFAULT_POC_PROPERTY,
+            &services,
+            &selection(None),
+            &PageSchema::default(),
+        );
+        assert_eq!(
+            body["properties"]["Services"],
+            json!({"relation": [{"id": "page-validators"}, {"id": "page-consensus"}]})
+        );
+
+        let unknown = Incident {
+            tags: vec!["Infra".to_owned()],
+            ..incident
+        };
+        let body = incident_page_body(
+            &unknown,
+            &Target::Debug.incident_db_id(),
+            DEFAULT_POC_PROPERTY,
+            &services,
+            &selection(None),
+            &PageSchema::default(),
+        );
+        assert!(body["properties"].get("Services").is_none());
+    }
+
+    fn mock_notion(server: &mockito::Server, target: Target) -> Notion {
+        let mut notion = Notion::with_base_url(
+            "test-token".to_owned(),
+            &server.url(),
+            NOTION_API_VERSION.to_owned(),
+            target,
+        );
+        // so the schema isn't cached across tests
+        notion.cache_dir = tempfile::tempdir().unwrap().into_path();
+        notion
+    }
+
+    /// The properties of a database with everything inserts write, whose Status
+    /// has the given type
+    fn schema_properties(status_type: &str) -> serde_json::Value {
+        let mut status = json!({ "id": "status", "type": status_type });
+        status[status_type] = match status_type {
+            "status" => json!({ "options": [], "groups": [] }),
+            _ => json!({ "options": [] }),
+        };
+        json!({
+            "Name": { "id": "title", "type": "title", "title": {} },
+            DEFAULT_POC_PROPERTY: { "id": "poc", "type": "people", "people": {} },
+            "Status": status,
+            "Priority": { "id": "priority", "type": "select", "select": { "options": [] } },
+            "Created": { "id": "created", "type": "date", "date": {} },
+            "Selected At": { "id": "selected_at", "type": "date", "date": {} },
+            "Selected By": { "id": "selected_by", "type": "rich_text", "rich_text": {} },
+        })
+    }
+
+    /// Serve `properties` as the schema of the `target` database
+    async fn mock_schema(
+        server: &mut mockito::Server,
+        target: Target,
+        properties: serde_json::Value,
+    ) -> mockito::Mock {
+        let db_id = target.incident_db_id();
+        server
+            .mock("GET", format!("/databases/{}", db_id).as_str())
+            .with_body(
+                json!({ "object": "database", "id": db_id.to_string(), "properties": properties })
+                    .to_string(),
+            )
+            .create_async()
+            .await
+    }
+
+    fn pages(json: &str) -> ListResponse<Page> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_pick_incident_page() {
+        let results = pages(include_str!("tests/incident_query_result.json"));
+
+        let page = pick_incident_page(results.clone(), 42).unwrap();
+        assert_eq!(page.title().as_deref(), Some("42: Validators halted"));
+        // 4 is a prefix of 42 but not of "42: "
+        assert!(pick_incident_page(results.clone(), 4).is_none());
+        assert!(pick_incident_page(results, 7).is_none());
+    }
+
+    #[test]
+    fn test_reviewed_incident() {
+        let results = pages(include_str!("tests/incident_query_result.json"));
+        let reviewed = pick_incident_page(results.clone(), 42).unwrap();
+        assert_eq!(page_status(&reviewed).as_deref(), Some("Reviewed"));
+        let incident = reviewed_incident(&reviewed).unwrap();
+        assert_eq!(incident.number, 42);
+        assert_eq!(incident.title, "Validators halted");
+        assert_eq!(incident.source_id.as_deref(), Some("Q1VALHALT42"));
+
+        let without_status = pick_incident_page(results, 420).unwrap();
+        assert_eq!(page_status(&without_status), None);
+        assert!(reviewed_incident(&without_status).is_none());
+    }
+
+    #[test]
+    fn test_page_has_postmortem() {
+        let results = pages(include_str!("tests/incident_query_result.json"));
+        let with_postmortem = pick_incident_page(results.clone(), 42).unwrap();
+        assert!(page_has_postmortem(&with_postmortem));
+        let without = pick_incident_page(results, 420).unwrap();
+        assert!(!page_has_postmortem(&without));
+
+        // a blank postmortem property doesn't count
+        let mut blank = with_postmortem;
+        blank.properties.properties.insert(
+            POSTMORTEM_PROPERTY.to_owned(),
+            serde_json::from_value(json!({"type": "url", "id": "pm", "url": " "})).unwrap(),
+        );
+        assert!(!page_has_postmortem(&blank));
+    }
+
+    #[test]
+    fn test_pick_incident_page_empty_results() {
+        let results =
+            pages(r#"{"object": "list", "results": [], "next_cursor": null, "has_more": false}"#);
+        assert!(pick_incident_page(results, 42).is_none());
+    }
+
+    #[test]
+    fn test_prefixed_number_titles() {
+        let format = NumberFormat {
+            prefix: "INC-".to_owned(),
+            digits: 4,
+        };
+        assert_eq!(incident_title_prefix(&format, 42), "INC-0042: ");
+        assert_eq!(
+            split_title(&format, "INC-0042: Validators halted"),
+            Some((42, "Validators halted"))
+        );
+        // pages titled before the format was configured still read back
+        assert_eq!(
+            split_title(&format, "42: Validators halted"),
+            Some((42, "Validators halted"))
+        );
+        assert_eq!(split_title(&format, "Validators halted"), None);
+        assert_eq!(incident_title_prefix(&NumberFormat::default(), 42), "42: ");
+    }
+
+    #[tokio::test]
+    async fn test_check_schema_uses_cached_schema() {
+        let mut server = mockito::Server::new_async().await;
+        let db_id = Target::Debug.incident_db_id();
+        let database = server
+            .mock("GET", format!("/databases/{}", db_id).as_str())
+            .with_body(
+                json!({
+                    "object": "database",
+                    "id": db_id.to_string(),
+                    "properties": {
+                        "Name": { "id": "title", "type": "title", "title": {} },
+                        DEFAULT_POC_PROPERTY: { "id": "poc", "type": "people", "people": {} }
+                    }
+                })
+                .to_string(),
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        let mut notion = mock_notion(&server, Target::Debug);
+        let dir = tempfile::tempdir().unwrap();
+        notion.cache_dir = dir.path().to_owned();
+
+        notion.check_schema().await.unwrap();
+        // within the TTL the cached schema is used
+        notion.check_schema().await.unwrap();
+        // until it's forgotten
+        notion.forget_schema().unwrap();
+        notion.check_schema().await.unwrap();
+        database.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_insert_incident_posts_page_body() {
+        let mut server = mockito::Server::new_async().await;
+        mock_schema(&mut server, Target::Prod, schema_properties("select")).await;
+        let incident = Incident {
+            number: 42,
+            title: "Validators halted".to_owned(),
+            html_url: "https://pagerduty.com/incidents/42".to_owned(),
+            poc_users: Some(vec![User::new(None, Some(notion_person("abc"))).unwrap()]),
+            ..Default::default()
+        };
+        let query = server
+            .mock(
+                "POST",
+                format!("/databases/{}/query", Target::Prod.incident_db_id()).as_str(),
+            )
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "filter": { "property": "Name", "title": { "starts_with": "42: " } }
+            })))
+            .with_body(r#"{"object": "list", "results": [], "has_more": false}"#)
+            .create_async()
+            .await;
+        let insert = server
+            .mock("POST", "/pages")
+            .match_header("authorization", "Bearer test-token")
+            // partial, since who selected the incident depends on the environment
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "parent": { "database_id": Target::Prod.incident_db_id().to_string() },
+                "properties": {
+                    "Name": { "title": [{ "text": { "content": "42: Validators halted" } }] },
+                    "link": { "url": "https://pagerduty.com/incidents/42" },
+                    DEFAULT_POC_PROPERTY: { "people": [{ "object": "user", "id": "abc" }] },
+                    "Selected At": { "date": { "start": Utc::now().format("%Y-%m-%d").to_string() } },
+                },
+                "children": [
+                    {
+                        "object": "block",
+                        "type": "heading_2",
+                        "heading_2": { "rich_text": [{ "type": "text", "text": { "content": "Description" } }] },
+                    },
+                    {
+                        "object": "block",
+                        "type": "paragraph",
+                        "paragraph": { "rich_text": [{ "type": "text", "text": { "content": "Validators stopped producing checkpoints." } }] },
+                    },
+                ],
+            })))
+            .with_body(r#"{"object": "page", "url": "https://www.notion.so/42-Validators-halted-7c3f0d425a1e4e8b9b2a0f6e1d2c3b44"}"#)
+            .create_async()
+            .await;
+
+        let notion = mock_notion(&server, Target::Prod);
+        let url = notion
+            .insert_incident(
+                incident.clone(),
+                Some("Validators stopped producing checkpoints."),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            url,
+            "https://www.notion.so/42-Validators-halted-7c3f0d425a1e4e8b9b2a0f6e1d2c3b44"
+        );
+        query.assert_async().await;
+        insert.assert_async().await;
+
+        // a created page without a url is an error rather than a blank link
+        insert.remove_async().await;
+        server
+            .mock("POST", "/pages")
+            .with_body(r#"{"object": "page"}"#)
+            .create_async()
+            .await;
+        let err = notion.insert_incident(incident, None).await.unwrap_err();
+        assert_eq!(err.to_string(), "the page of incident 42 has no url");
+    }
+
+    #[tokio::test]
+    async fn test_insert_writes_a_status_typed_status() {
+        let mut server = mockito::Server::new_async().await;
+        let mut properties = schema_properties("status");
+        properties.as_object_mut().unwrap().remove("Priority");
+        mock_schema(&mut server, Target::Prod, properties).await;
+        server
+            .mock(
+                "POST",
+                format!("/databases/{}/query", Target::Prod.incident_db_id()).as_str(),
+            )
+            .with_body(r#"{"object": "list", "results": [], "has_more": false}"#)
+            .create_async()
+            .await;
+        let insert = server
+            .mock("POST", "/pages")
+            .match_request(|request| {
+                let body: serde_json::Value =
+                    serde_json::from_slice(request.body().unwrap()).unwrap();
+                body["properties"]["Status"] == json!({ "status": { "name": "To Review" } })
+                    && body["properties"].get("Priority").is_none()
+            })
+            .with_body(r#"{"object": "page", "url": "https://www.notion.so/42"}"#)
+            .create_async()
+            .await;
+
+        let notion = mock_notion(&server, Target::Prod);
+        let incident = Incident {
+            number: 42,
+            priority: Some(Priority::from_name("P1")),
+            ..Default::default()
+        };
+        notion.insert_incident(incident, None).await.unwrap();
+        insert.assert_async().await;
+    }
+
+    #[test]
+    fn test_page_schema_reports_what_is_left_out() {
+        let properties: HashMap<String, PropertyConfiguration> =
+            serde_json::from_value(schema_properties("status")).unwrap();
+        let (schema, problems) = PageSchema::read(&properties);
+        assert_eq!(
+            schema,
+            PageSchema {
+                status: Some(StatusType::Status),
+                ..Default::default()
+            }
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+
+        let mut value = schema_properties("select");
+        let object = value.as_object_mut().unwrap();
+        object.remove("Created");
+        object.insert(
+            "Selected By".to_owned(),
+            json!({ "id": "selected_by", "type": "date", "date": {} }),
+        );
+        let properties: HashMap<String, PropertyConfiguration> =
+            serde_json::from_value(value).unwrap();
+        let (schema, problems) = PageSchema::read(&properties);
+        assert_eq!(
+            schema,
+            PageSchema {
+                created: false,
+                selected_by: false,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            problems,
+            vec![
+                "there is no 'Created' property",
+                "'Selected By' isn't a text property"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_existing_incident_updates_its_page() {
+        let mut server = mockito::Server::new_async().await;
+        mock_schema(&mut server, Target::Prod, schema_properties("select")).await;
+        let incident = Incident {
+            number: 42,
+            title: "Validators halted".to_owned(),
+            html_url: "https://pagerduty.com/incidents/42".to_owned(),
+            ..Default::default()
+        };
+        let query = server
+            .mock(
+                "POST",
+                format!("/databases/{}/query", Target::Prod.incident_db_id()).as_str(),
+            )
+            .with_body(include_str!("tests/incident_query_result.json"))
+            .create_async()
+            .await;
+        let update = server
+            .mock("PATCH", "/pages/7c3f0d42-5a1e-4e8b-9b2a-0f6e1d2c3b44")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "properties": {
+                    "link": { "url": "https://pagerduty.com/incidents/42" },
+                }
+            })))
+            .with_body(r#"{"object": "page", "url": "https://www.notion.so/42-Validators-halted-7c3f0d425a1e4e8b9b2a0f6e1d2c3b44"}"#)
+            .create_async()
+            .await;
+        let insert = server.mock("POST", "/pages").expect(0).create_async().await;
+
+        let notion = mock_notion(&server, Target::Prod);
+        notion.insert_incident(incident, None).await.unwrap();
+        query.assert_async().await;
+        update.assert_async().await;
+        insert.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_has_postmortem_looks_up_the_page_on_base_url() {
+        let mut server = mockito::Server::new_async().await;
+        let query = server
+            .mock(
+                "POST",
+                format!("/databases/{}/query", Target::Prod.incident_db_id()).as_str(),
+            )
+            .with_body(include_str!("tests/incident_query_result.json"))
+            .expect(2)
+            .create_async()
+            .await;
+
+        let notion = mock_notion(&server, Target::Prod);
+        assert!(notion.has_postmortem(42).await.unwrap());
+        assert!(!notion.has_postmortem(420).await.unwrap());
+        query.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_reviewed_incidents_follows_the_cursor() {
+        let mut server = mockito::Server::new_async().await;
+        let path = format!("/databases/{}/query", Target::Prod.incident_db_id());
+        let first_page = include_str!("tests/incident_query_result.json")
+            .replace(r#""next_cursor": null"#, r#""next_cursor": "cursor-2""#)
+            .replace(r#""has_more": false"#, r#""has_more": true"#);
+        let first = server
+            .mock("POST", path.as_str())
+            .match_body(mockito::Matcher::Json(json!({})))
+            .with_body(first_page)
+            .create_async()
+            .await;
+        let second = server
+            .mock("POST", path.as_str())
+            .match_body(mockito::Matcher::PartialJson(
+                json!({ "start_cursor": "cursor-2" }),
+            ))
+            .with_body(include_str!("tests/incident_query_result.json"))
+            .create_async()
+            .await;
+
+        let notion = mock_notion(&server, Target::Prod);
+        let incidents = notion.get_reviewed_incidents().await.unwrap();
+        let numbers: Vec<u64> = incidents.iter().map(|i| i.number).collect();
+        assert_eq!(numbers, vec![42, 42]);
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    fn paragraph(id: &str, text: &str) -> serde_json::Value {
+        json!({
+            "object": "block",
+            "id": id,
+            "type": "paragraph",
+            "created_time": "2024-01-01T00:00:00.000Z",
+            "last_edited_time": "2024-01-01T00:00:00.000Z",
+            "has_children": false,
+            "created_by": { "object": "user", "id": "u1" },
+            "last_edited_by": { "object": "user", "id": "u1" },
+            "paragraph": {
+                "rich_text": [{
+                    "type": "text",
+                    "text": { "content": text, "link": null },
+                    "annotations": {
+                        "bold": false,
+                        "italic": false,
+                        "strikethrough": false,
+                        "underline": false,
+                        "code": false,
+                        "color": "default"
+                    },
+                    "plain_text": text,
+                    "href": null
+                }],
+                "color": "default"
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_get_page_blocks_follows_the_cursor() {
+        let mut server = mockito::Server::new_async().await;
+        let page_id: PageId =
+            serde_json::from_value(json!("7c3f0d42-5a1e-4e8b-9b2a-0f6e1d2c3b44")).unwrap();
+        let path = format!("/blocks/{}/children", page_id);
+        let first = server
+            .mock("GET", path.as_str())
+            .match_query(mockito::Matcher::Missing)
+            .with_body(
+                json!({
+                    "object": "list",
+                    "results": [paragraph("b1", "first")],
+                    "next_cursor": "cursor-2",
+                    "has_more": true
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let second = server
+            .mock("GET", path.as_str())
+            .match_query(mockito::Matcher::UrlEncoded(
+                "start_cursor".to_owned(),
+                "cursor-2".to_owned(),
+            ))
+            .with_body(
+                json!({
+                    "object": "list",
+                    "results": [paragraph("b2", "second")],
+                    "next_cursor": null,
+                    "has_more": false
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let notion = mock_notion(&server, Target::Prod);
+        let lines = notion.get_page_blocks(&page_id).await.unwrap();
+        assert_eq!(lines, vec!["first".to_owned(), "second".to_owned()]);
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_people_request_is_retried_when_rate_limited() {
+        let mut server = mockito::Server::new_async().await;
+        let limited = server
+            .mock("GET", "/users")
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .expect(1)
+            .create_async()
+            .await;
+        let people = server
+            .mock("GET", "/users")
+            .with_body(r#"{"object": "list", "results": [], "has_more": false}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let notion = mock_notion(&server, Target::Debug);
+        assert!(notion.get_all_people().await.unwrap().is_empty());
+        limited.assert_async().await;
+        people.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_insert_is_retried_on_conflict() {
+        let mut server = mockito::Server::new_async().await;
+        mock_schema(&mut server, Target::Debug, schema_properties("select")).await;
+        let query = server
+            .mock(
+                "POST",
+                format!("/databases/{}/query", Target::Debug.incident_db_id()).as_str(),
+            )
+            .with_body(r#"{"object": "list", "results": [], "has_more": false}"#)
+            .create_async()
+            .await;
+        let conflict = server
+            .mock("POST", "/pages")
+            .with_status(409)
+            .with_header("retry-after", "0")
+            .with_body(r#"{"object": "error", "status": 409, "code": "conflict_error", "message": "Conflict occurred while saving. Please try again."}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let insert = server
+            .mock("POST", "/pages")
+            .with_body(r#"{"object": "page", "url": "https://www.notion.so/42-Validators-halted-7c3f0d425a1e4e8b9b2a0f6e1d2c3b44"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let notion = mock_notion(&server, Target::Debug);
+        let incident = Incident {
+            number: 42,
+            ..Default::default()
+        };
+        notion.insert_incident(incident, None).await.unwrap();
+        query.assert_async().await;
+        conflict.assert_async().await;
+        insert.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_retries_give_up_after_max_retries() {
+        let mut server = mockito::Server::new_async().await;
+        let limited = server
+            .mock("GET", "/users")
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .expect(MAX_RETRIES as usize + 1)
+            .create_async()
+            .await;
+
+        let notion = mock_notion(&server, Target::Debug);
+        let err = notion.get_all_people().await.unwrap_err();
+        assert!(err.to_string().contains("429"), "{}", err);
+        limited.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_notion_version_override_is_sent() {
+        let mut server = mockito::Server::new_async().await;
+        let people = server
+            .mock("GET", "/users")
+            .match_header("notion-version", "2025-09-03")
+            .with_body(r#"{"object": "list", "results": [], "has_more": false}"#)
+            .create_async()
+            .await;
+
+        let version = parse_notion_version(Some("2025-09-03")).unwrap();
+        let notion = Notion::with_base_url(
+            "test-token".to_owned(),
+            &server.url(),
+            version,
+            Target::Debug,
+        );
+
+        assert!(notion.get_all_people().await.unwrap().is_empty());
+        people.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_people_and_insert_send_the_same_notion_version() {
+        let mut server = mockito::Server::new_async().await;
+        mock_schema(&mut server, Target::Debug, schema_properties("select")).await;
+        let notion = mock_notion(&server, Target::Debug);
+        let version = notion.version.as_str();
+        let people = server
+            .mock("GET", "/users")
+            .match_header("notion-version", version)
+            .with_body(r#"{"object": "list", "results": [], "has_more": false}"#)
+            .create_async()
+            .await;
+        let query = server
+            .mock(
+                "POST",
+                format!("/databases/{}/query", Target::Debug.incident_db_id()).as_str(),
+            )
+            .match_header("notion-version", version)
+            .with_body(r#"{"object": "list", "results": [], "has_more": false}"#)
+            .create_async()
+            .await;
+        let insert = server
+            .mock("POST", "/pages")
+            .match_header("notion-version", version)
+            .with_body(r#"{"object": "page", "url": "https://www.notion.so/42-Validators-halted-7c3f0d425a1e4e8b9b2a0f6e1d2c3b44"}"#)
+            .create_async()
+            .await;
+
+        assert!(notion.get_all_people().await.unwrap().is_empty());
+        let incident = Incident {
+            number: 42,
+            ..Default::default()
+        };
+        notion.insert_incident(incident, None).await.unwrap();
+        people.assert_async().await;
+        query.assert_async().await;
+        insert.assert_async().await;
+    }
+
+    #[test]
+    fn test_parse_notion_version() {
+        assert_eq!(parse_notion_version(None).unwrap(), NOTION_API_VERSION);
+        assert_eq!(
+            parse_notion_version(Some("2025-09-03")).unwrap(),
+            "2025-09-03"
+        );
+        for invalid in ["latest", "2022-6-28", "2022-13-01", ""] {
+            assert!(parse_notion_version(Some(invalid)).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_insert_error_names_rejected_property() {
+        let err = insert_error(
+            r#"{
+                "object": "error",
+                "status": 400,
+                "code": "validation_error",
+                "message": "body failed validation: body.properties.PoC(s).people[0].id should be a valid uuid, instead was `\"abc\"`."
+            }"#,
+        );
+        assert_eq!(
+            err.to_string(),
+            "Notion rejected property 'PoC(s)': body failed validation: body.properties.PoC(s).people[0].id should be a valid uuid, instead was `\"abc\"`."
+        );
+    }
+
+    #[test]
+    fn test_insert_error_falls_back_to_response_text() {
+        let err = insert_error("upstream connect error");
+        assert_eq!(
+            err.to_string(),
+            "Failed to insert incident: \"upstream connect error\""
+        );
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use once_cell::sync::Lazy;
+
+/// How incident numbers are written, from the environment.
+pub static NUMBER_FORMAT: Lazy<NumberFormat> = Lazy::new(NumberFormat::from_env);
+
+/// How incident numbers are written in Notion titles, messages and channel names,
+/// for sources that number incidents like `INC-0042` rather than `42`.
+///
+/// Numbers are still compared as numbers, so `INC-0042` and `42` are the same
+/// incident wherever they come from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NumberFormat {
+    /// Written before the number, from `SUIOP_INCIDENT_PREFIX`
+    pub prefix: String,
+    /// The number is zero-padded to this many digits, from `SUIOP_INCIDENT_DIGITS`
+    pub digits: usize,
+}
+
+impl NumberFormat {
+    pub fn from_env() -> Self {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        Self {
+            prefix: lookup("SUIOP_INCIDENT_PREFIX").unwrap_or_default(),
+            digits: lookup("SUIOP_INCIDENT_DIGITS")
+                .map(|d| {
+                    d.parse()
+                        .expect("SUIOP_INCIDENT_DIGITS must be a number of digits")
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The zero-padded digits of the number, without the prefix.
+    pub fn digits(&self, number: u64) -> String {
+        format!("{:0width$}", number, width = self.digits)
+    }
+
+    /// The number as the source writes it, e.g. `INC-0042`.
+    pub fn format(&self, number: u64) -> String {
+        format!("{}{}", self.prefix, self.digits(number))
+    }
+
+    /// Read a number written with or without the prefix and padding; the prefix
+    /// is matched ignoring case.
+    pub fn parse(&self, text: &str) -> Option<u64> {
+        let text = text.trim();
+        let digits = text
+            .get(..self.prefix.len())
+            .filter(|start| !self.prefix.is_empty() && start.eq_ignore_ascii_case(&self.prefix))
+            .map_or(text, |_| &text[self.prefix.len()..]);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefixed_and_padded() {
+        let format = NumberFormat::from_lookup(|name| match name {
+            "SUIOP_INCIDENT_PREFIX" => Some("INC-".to_owned()),
+            "SUIOP_INCIDENT_DIGITS" => Some("4".to_owned()),
+            _ => None,
+        });
+        assert_eq!(format.format(42), "INC-0042");
+        assert_eq!(format.format(123456), "INC-123456");
+        assert_eq!(format.parse("INC-0042"), Some(42));
+        assert_eq!(format.parse("inc-42"), Some(42));
+        assert_eq!(format.parse("42"), Some(42));
+        assert_eq!(format.parse("INC-"), None);
+        assert_eq!(format.parse("INC-42a"), None);
+
+        let bare = NumberFormat::default();
+        assert_eq!(bare.format(42), "42");
+        assert_eq!(bare.parse("42"), Some(42));
+        assert_eq!(bare.parse("INC-0042"), None);
+    }
+}
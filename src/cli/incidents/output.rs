@@ -0,0 +1,86 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::io::{Stderr, Stdout, Write};
+
+use tracing::debug;
+
+/// Where the review flow writes its human-oriented output.
+///
+/// The outcome of the review, including the rendered message, is always written to
+/// `out`. Progress lines go to `progress` unless `quiet` is set, in which case they
+/// are only logged at debug level. Keeping them apart lets the message be captured
+/// on its own, e.g. by redirecting stdout to a file.
+pub(crate) struct ReviewOutput<O: Write, P: Write> {
+    quiet: bool,
+    out: RefCell<O>,
+    progress: RefCell<P>,
+}
+
+impl ReviewOutput<Stdout, Stderr> {
+    /// Write the outcome to stdout and progress to stderr, next to the prompts.
+    pub fn stdout(quiet: bool) -> Self {
+        Self::new(quiet, std::io::stdout(), std::io::stderr())
+    }
+}
+
+impl<O: Write, P: Write> ReviewOutput<O, P> {
+    pub fn new(quiet: bool, out: O, progress: P) -> Self {
+        Self {
+            quiet,
+            out: RefCell::new(out),
+            progress: RefCell::new(progress),
+        }
+    }
+
+    /// Report progress, suppressed in quiet mode.
+    pub fn progress(&self, line: impl Display) {
+        if self.quiet {
+            debug!("{}", line);
+        } else {
+            writeln!(self.progress.borrow_mut(), "{}", line)
+                .expect("failed to write review progress");
+        }
+    }
+
+    /// Report the outcome of the review.
+    pub fn outcome(&self, line: impl Display) {
+        writeln!(self.out.borrow_mut(), "{}", line).expect("failed to write review output");
+    }
+
+    /// The outcome and progress written so far.
+    #[cfg(test)]
+    pub fn into_inner(self) -> (O, P) {
+        (self.out.into_inner(), self.progress.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_and_outcome_are_kept_apart() {
+        let output = ReviewOutput::new(false, vec![], vec![]);
+        output.progress("Reviewing 2 recent incidents");
+        output.outcome("We have selected the following incidents for review:");
+        let (out, progress) = output.into_inner();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "We have selected the following incidents for review:\n"
+        );
+        assert_eq!(
+            String::from_utf8(progress).unwrap(),
+            "Reviewing 2 recent incidents\n"
+        );
+
+        let output = ReviewOutput::new(true, vec![], vec![]);
+        output.progress("Reviewing 2 recent incidents");
+        output.outcome("done");
+        let (out, progress) = output.into_inner();
+        assert_eq!(out, b"done\n");
+        assert!(progress.is_empty());
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use super::incident::Incident;
+
+/// A rule as written in the overrides file. Every field that is set must match.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRule {
+    number: Option<u64>,
+    priority: Option<String>,
+    /// A regex matched against the incident's title
+    title: Option<String>,
+}
+
+/// An overrides file, written as a JSON object like
+/// `{"include": [{"priority": "P0"}], "exclude": [{"number": 123}, {"title": "^Test"}]}`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawOverrides {
+    #[serde(default)]
+    include: Vec<RawRule>,
+    #[serde(default)]
+    exclude: Vec<RawRule>,
+}
+
+/// Matches incidents by number, priority and title.
+#[derive(Debug)]
+pub struct OverrideRule {
+    number: Option<u64>,
+    priority: Option<String>,
+    title: Option<Regex>,
+}
+
+impl OverrideRule {
+    fn compile(raw: RawRule) -> Result<Self> {
+        if raw.number.is_none() && raw.priority.is_none() && raw.title.is_none() {
+            return Err(anyhow!(
+                "a rule needs at least one of number, priority or title"
+            ));
+        }
+        let title = raw
+            .title
+            .map(|pattern| {
+                Regex::new(&pattern).with_context(|| format!("{:?} is not a valid regex", pattern))
+            })
+            .transpose()?;
+        Ok(Self {
+            number: raw.number,
+            priority: raw.priority,
+            title,
+        })
+    }
+
+    /// Whether the incident matches every part of the rule. Priorities are
+    /// compared case-insensitively.
+    pub fn matches(&self, incident: &Incident) -> bool {
+        let priority = incident.priority.as_ref().map(|p| p.name.as_str());
+        self.number.is_none_or(|n| n == incident.number)
+            && self
+                .priority
+                .as_deref()
+                .is_none_or(|p| priority.is_some_and(|name| name.eq_ignore_ascii_case(p)))
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|t| t.is_match(&incident.title))
+    }
+}
+
+impl fmt::Display for OverrideRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        if let Some(number) = self.number {
+            parts.push(format!("number {}", number));
+        }
+        if let Some(priority) = &self.priority {
+            parts.push(format!("priority {}", priority));
+        }
+        if let Some(title) = &self.title {
+            parts.push(format!("title /{}/", title));
+        }
+        write!(f, "{}", parts.join(" and "))
+    }
+}
+
+/// Rules deciding whether incidents are kept or excluded before the operator is
+/// asked about the rest, for recurring policies like "always review P0".
+#[derive(Debug, Default)]
+pub struct Overrides {
+    include: Vec<OverrideRule>,
+    exclude: Vec<OverrideRule>,
+}
+
+/// An incident whose review was decided by a rule.
+#[derive(Debug)]
+pub struct Overridden<'a> {
+    pub incident: Incident,
+    pub rule: &'a OverrideRule,
+}
+
+/// What applying the overrides decided.
+#[derive(Debug, Default)]
+pub struct OverrideOutcome<'a> {
+    pub kept: Vec<Overridden<'a>>,
+    pub excluded: Vec<Overridden<'a>>,
+}
+
+impl Overrides {
+    pub fn parse(contents: &str) -> Result<Self> {
+        let raw: RawOverrides = serde_json::from_str(contents)?;
+        let compile = |rules: Vec<RawRule>| {
+            rules
+                .into_iter()
+                .map(OverrideRule::compile)
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            include: compile(raw.include)?,
+            exclude: compile(raw.exclude)?,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("{} is not an overrides file", path.display()))
+    }
+
+    /// Take the incidents the rules decide out of their groups, dropping groups
+    /// left empty. Exclude rules win over include rules.
+    pub fn apply(&self, group_map: &mut BTreeMap<String, Vec<Incident>>) -> OverrideOutcome<'_> {
+        let mut outcome = OverrideOutcome::default();
+        for group in group_map.values_mut() {
+            let mut undecided = vec![];
+            for incident in group.drain(..) {
+                if let Some(rule) = self.exclude.iter().find(|r| r.matches(&incident)) {
+                    outcome.excluded.push(Overridden { incident, rule });
+                } else if let Some(rule) = self.include.iter().find(|r| r.matches(&incident)) {
+                    outcome.kept.push(Overridden { incident, rule });
+                } else {
+                    undecided.push(incident);
+                }
+            }
+            *group = undecided;
+        }
+        group_map.retain(|_, group| !group.is_empty());
+        outcome.kept.sort_by_key(|o| o.incident.number);
+        outcome.excluded.sort_by_key(|o| o.incident.number);
+        outcome
+    }
+}
+
+impl OverrideOutcome<'_> {
+    /// Report each decided incident with the rule that decided it.
+    pub fn render(&self) -> String {
+        let mut lines = vec![format!(
+            "The overrides file kept {} and excluded {} incidents:",
+            self.kept.len(),
+            self.excluded.len()
+        )];
+        for (verb, decided) in [("kept", &self.kept), ("excluded", &self.excluded)] {
+            lines.extend(
+                decided
+                    .iter()
+                    .map(|o| format!("• {} {} by {}", o.incident.display_number(), verb, o.rule)),
+            );
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::incidents::pd::Priority;
+
+    fn incident(number: u64, priority: &str, title: &str) -> Incident {
+        Incident {
+            number,
+            title: title.to_owned(),
+            priority: Some(Priority::from_name(priority)),
+            ..Default::default()
+        }
+    }
+
+    fn groups(incidents: Vec<Incident>) -> BTreeMap<String, Vec<Incident>> {
+        incidents
+            .into_iter()
+            .map(|i| (i.title.clone(), vec![i]))
+            .collect()
+    }
+
+    fn numbers(decided: &[Overridden]) -> Vec<u64> {
+        decided.iter().map(|o| o.incident.number).collect()
+    }
+
+    #[test]
+    fn test_number_override() {
+        let overrides = Overrides::parse(r#"{"exclude": [{"number": 123}]}"#).unwrap();
+        let mut group_map = groups(vec![
+            incident(123, "P2", "Flaky alert"),
+            incident(124, "P2", "RPC latency"),
+        ]);
+        let outcome = overrides.apply(&mut group_map);
+        assert!(outcome.kept.is_empty());
+        assert_eq!(numbers(&outcome.excluded), vec![123]);
+        assert_eq!(group_map.keys().collect::<Vec<_>>(), vec!["RPC latency"]);
+        assert_eq!(
+            outcome.render(),
+            "The overrides file kept 0 and excluded 1 incidents:\n• 123 excluded by number 123"
+        );
+    }
+
+    #[test]
+    fn test_priority_override() {
+        let overrides = Overrides::parse(r#"{"include": [{"priority": "p0"}]}"#).unwrap();
+        let mut group_map = groups(vec![
+            incident(1, "P0", "Validators halted"),
+            incident(2, "P1", "RPC latency"),
+            Incident {
+                number: 3,
+                title: "No priority".to_owned(),
+                ..Default::default()
+            },
+        ]);
+        let outcome = overrides.apply(&mut group_map);
+        assert_eq!(numbers(&outcome.kept), vec![1]);
+        assert_eq!(group_map.len(), 2);
+    }
+
+    #[test]
+    fn test_title_regex_override() {
+        let overrides = Overrides::parse(
+            r#"{"include": [{"title": "(?i)halted"}], "exclude": [{"title": "^\\[test\\]"}, {"priority": "P0", "title": "drill"}]}"#,
+        )
+        .unwrap();
+        let mut group_map = BTreeMap::from([(
+            "Validators halted".to_owned(),
+            vec![
+                incident(1, "P0", "Validators halted"),
+                incident(2, "P0", "Validators HALTED in drill"),
+            ],
+        )]);
+        group_map.extend(groups(vec![
+            incident(3, "P3", "[test] synthetic"),
+            incident(4, "P1", "drill for on-call"),
+        ]));
+        let outcome = overrides.apply(&mut group_map);
+        // exclude rules win over include rules
+        assert_eq!(numbers(&outcome.kept), vec![1]);
+        assert_eq!(numbers(&outcome.excluded), vec![2, 3]);
+        assert_eq!(
+            group_map.keys().collect::<Vec<_>>(),
+            vec!["drill for on-call"]
+        );
+        assert_eq!(
+            outcome.excluded[0].rule.to_string(),
+            "priority P0 and title /drill/"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_rules() {
+        assert!(Overrides::parse(r#"{"include": [{}]}"#).is_err());
+        assert!(Overrides::parse(r#"{"include": [{"title": "("}]}"#).is_err());
+        assert!(Overrides::parse(r#"{"include": [{"severity": "P0"}]}"#).is_err());
+    }
+}
@@ -1,7 +1,7 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use chrono::{DateTime, Local};
 use colored::Colorize;
 use reqwest;
@@ -9,11 +9,13 @@ use reqwest::header::HeaderMap;
 use reqwest::header::ACCEPT;
 use reqwest::header::AUTHORIZATION;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use serde_json::Value as JsonValue;
 use std::env;
 use tracing::debug;
 
 use super::incident::Incident;
+use super::source::IncidentSource;
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Priority {
@@ -23,6 +25,23 @@ pub struct Priority {
 }
 
 impl Priority {
+    /// Build a priority with only its name set, e.g. "P2".
+    pub fn from_name(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// The priority implied by a PagerDuty urgency, for incidents without a priority.
+    pub fn from_urgency(urgency: &str) -> Option<Self> {
+        match urgency {
+            "high" => Some(Self::from_name("P1")),
+            "low" => Some(Self::from_name("P3")),
+            _ => None,
+        }
+    }
+
     pub fn u8(&self) -> u8 {
         self.name
             .trim_start_matches("P")
@@ -31,31 +50,156 @@ impl Priority {
     }
 }
 
+/// A reference to another PagerDuty object, such as a service or team.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub(crate) struct PagerDutyReference {
+    pub summary: String,
+}
+
+/// How many alerts PagerDuty grouped into an incident.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub(crate) struct PagerDutyAlertCounts {
+    pub all: u64,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub(crate) struct PagerDutyIncident {
+    pub id: Option<String>,
     #[serde(rename = "incident_number")]
     pub number: u64,
     pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub status: Option<String>,
     pub created_at: Option<String>,
     pub resolved_at: Option<String>,
     pub html_url: String,
     pub priority: Option<Priority>,
+    pub urgency: Option<String>,
+    pub alert_counts: Option<PagerDutyAlertCounts>,
+    pub service: Option<PagerDutyReference>,
+    #[serde(default)]
+    pub teams: Vec<PagerDutyReference>,
 }
 
-/// Fetch incidents from the API using the given parameters until {limit} incidents have been received.
-pub async fn fetch_incidents(
-    limit: usize,
-    start_time: DateTime<Local>,
-    _end_time: DateTime<Local>,
-) -> Result<Vec<PagerDutyIncident>> {
-    let url = "https://api.pagerduty.com/incidents";
+impl PagerDutyIncident {
+    /// The incident's priority, falling back to the one implied by its urgency.
+    pub fn priority_or_urgency(&self) -> Option<Priority> {
+        self.priority
+            .clone()
+            .filter(|p| !p.name.is_empty())
+            .or_else(|| self.urgency.as_deref().and_then(Priority::from_urgency))
+    }
+
+    /// The incident's tags: the name of its service followed by its teams.
+    pub fn tags(&self) -> Vec<String> {
+        self.service
+            .iter()
+            .chain(self.teams.iter())
+            .map(|r| r.summary.clone())
+            .collect()
+    }
+}
+
+const PAGERDUTY_API_URL: &str = "https://api.pagerduty.com";
+
+/// Fetches resolved incidents from the PagerDuty REST API.
+pub(crate) struct PagerDutySource {
+    api_key: String,
+    /// The base url of the API, without a trailing slash
+    base_url: String,
+    /// The email of the PagerDuty user notes are added as, from `PD_FROM_EMAIL`
+    from_email: Option<String>,
+}
 
-    let api_key = env::var("PD_API_KEY").expect("please set the PD_API_KEY env var");
-    if api_key.is_empty() {
-        panic!("PD_API_KEY is not set");
+impl PagerDutySource {
+    pub fn new() -> Self {
+        let api_key = env::var("PD_API_KEY").expect("please set the PD_API_KEY env var");
+        if api_key.is_empty() {
+            panic!("PD_API_KEY is not set");
+        }
+        Self {
+            api_key,
+            base_url: PAGERDUTY_API_URL.to_owned(),
+            from_email: env::var("PD_FROM_EMAIL").ok(),
+        }
     }
+}
 
-    debug!("fetching incidents from pagerduty with {}", api_key);
+impl IncidentSource for PagerDutySource {
+    async fn fetch_recent(
+        &self,
+        limit: usize,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+    ) -> Result<Vec<Incident>> {
+        Ok(
+            fetch_incidents(&self.base_url, &self.api_key, limit, start_time, end_time)
+                .await?
+                .into_iter()
+                // Change into more robust Incident type
+                .map(Incident::from)
+                .collect(),
+        )
+    }
+
+    /// Look up the incident's current status in PagerDuty.
+    async fn fetch_status(&self, incident: &Incident) -> Result<Option<String>> {
+        let id = incident
+            .source_id
+            .as_deref()
+            .with_context(|| format!("incident {} has no PagerDuty id", incident.number))?;
+        let response = reqwest::Client::new()
+            .get(format!("{}/incidents/{}", self.base_url, id))
+            .headers(api_headers(&self.api_key))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Failed to fetch incident {}: {}",
+                incident.number,
+                response.text().await.unwrap_or_default()
+            ));
+        }
+        let response = response.json::<JsonValue>().await?;
+        let current: PagerDutyIncident = serde_json::from_value(response["incident"].clone())
+            .with_context(|| format!("parsing PagerDuty incident {}", incident.number))?;
+        Ok(current.status)
+    }
+
+    /// Add a note to the PagerDuty incident saying it was scheduled for review.
+    async fn mark_reviewed(&self, incident: &Incident) -> Result<()> {
+        let id = incident
+            .source_id
+            .as_deref()
+            .with_context(|| format!("incident {} has no PagerDuty id", incident.number))?;
+        let from_email = self.from_email.as_deref().context(
+            "please set PD_FROM_EMAIL to the email of the PagerDuty user to add notes as",
+        )?;
+        let response = reqwest::Client::new()
+            .post(format!("{}/incidents/{}/notes", self.base_url, id))
+            .headers(api_headers(&self.api_key))
+            .header("From", from_email)
+            .json(&json!({ "note": { "content": REVIEWED_NOTE } }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Failed to mark incident {} as reviewed: {}",
+                incident.number,
+                response.text().await.unwrap_or_default()
+            ));
+        }
+        debug!("marked incident {} as reviewed", incident.number);
+        Ok(())
+    }
+}
+
+/// The note added to incidents that were scheduled for review
+const REVIEWED_NOTE: &str = "Scheduled for incident review";
+
+/// The headers for authenticating to the PagerDuty REST API.
+fn api_headers(api_key: &str) -> HeaderMap {
     let mut headers = HeaderMap::new();
     headers.insert(
         AUTHORIZATION,
@@ -69,6 +213,21 @@ pub async fn fetch_incidents(
             .parse()
             .expect("header parsing"),
     );
+    headers
+}
+
+/// Fetch incidents from the API using the given parameters until {limit} incidents have been received.
+async fn fetch_incidents(
+    base_url: &str,
+    api_key: &str,
+    limit: usize,
+    start_time: DateTime<Local>,
+    _end_time: DateTime<Local>,
+) -> Result<Vec<PagerDutyIncident>> {
+    let url = format!("{}/incidents", base_url);
+
+    debug!("fetching incidents from pagerduty with {}", api_key);
+    let headers = api_headers(api_key);
 
     let mut more_records = true;
     let mut all_incidents = vec![];
@@ -83,7 +242,7 @@ pub async fn fetch_incidents(
         ];
         let client = reqwest::Client::new();
         let response = client
-            .get(url)
+            .get(&url)
             .headers(headers.clone())
             .query(&params)
             .send()
@@ -158,3 +317,152 @@ pub async fn print_recent_incidents(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn sample_incident() -> PagerDutyIncident {
+        serde_json::from_str(include_str!("tests/incident.json")).unwrap()
+    }
+
+    #[test]
+    fn test_incident_from_pagerduty_payload() {
+        let incident = Incident::from(sample_incident());
+
+        assert_eq!(incident.number, 1234);
+        assert_eq!(incident.source_id.as_deref(), Some("Q2XYZ9ABCDEF"));
+        assert_eq!(incident.title, "Fullnode RPC latency above threshold");
+        assert_eq!(
+            incident.description.as_deref(),
+            Some("p99 latency of fullnode RPC reads stayed above 2s for 10 minutes")
+        );
+        assert_eq!(
+            incident.html_url,
+            "https://mystenlabs.pagerduty.com/incidents/Q2XYZ9ABCDEF"
+        );
+        assert_eq!(incident.priority.unwrap().name, "P2");
+        assert_eq!(incident.tags, vec!["Fullnode RPC", "Infra"]);
+        assert_eq!(incident.count, Some(3));
+    }
+
+    #[test]
+    fn test_urgency_maps_to_priority_without_priority() {
+        let mut pd_incident = sample_incident();
+        pd_incident.priority = None;
+        assert_eq!(
+            Incident::from(pd_incident.clone()).priority.unwrap().name,
+            "P1"
+        );
+
+        pd_incident.urgency = Some("low".to_owned());
+        assert_eq!(
+            Incident::from(pd_incident.clone()).priority.unwrap().name,
+            "P3"
+        );
+
+        pd_incident.urgency = None;
+        assert!(Incident::from(pd_incident).priority.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_recent_follows_offset_pagination() {
+        let mut server = mockito::Server::new_async().await;
+        let page = |offset: &str, more: bool| {
+            let mut incident: JsonValue =
+                serde_json::from_str(include_str!("tests/incident.json")).unwrap();
+            incident["incident_number"] = offset.parse::<u64>().unwrap().into();
+            incident["resolved_at"] = Local::now().to_rfc3339().into();
+            serde_json::json!({ "incidents": [incident], "more": more }).to_string()
+        };
+        let first = server
+            .mock("GET", "/incidents")
+            .match_query(mockito::Matcher::UrlEncoded("offset".into(), "0".into()))
+            .match_header("authorization", "Token token=test-key")
+            .with_body(page("0", true))
+            .create_async()
+            .await;
+        let second = server
+            .mock("GET", "/incidents")
+            .match_query(mockito::Matcher::UrlEncoded("offset".into(), "1".into()))
+            .with_body(page("1", false))
+            .create_async()
+            .await;
+
+        let source = PagerDutySource {
+            api_key: "test-key".to_owned(),
+            base_url: server.url(),
+            from_email: None,
+        };
+        let incidents = source
+            .fetch_recent(10, Local::now() - Duration::days(7), Local::now())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            incidents.iter().map(|i| i.number).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_mark_reviewed_adds_note() {
+        let mut server = mockito::Server::new_async().await;
+        let note = server
+            .mock("POST", "/incidents/Q2XYZ9ABCDEF/notes")
+            .match_header("authorization", "Token token=test-key")
+            .match_header("from", "oncall@mystenlabs.com")
+            .match_body(mockito::Matcher::Json(json!({
+                "note": { "content": "Scheduled for incident review" }
+            })))
+            .with_status(201)
+            .with_body(r#"{"note": {"id": "PNOTE1"}}"#)
+            .create_async()
+            .await;
+        let source = PagerDutySource {
+            api_key: "test-key".to_owned(),
+            base_url: server.url(),
+            from_email: Some("oncall@mystenlabs.com".to_owned()),
+        };
+
+        source
+            .mark_reviewed(&Incident::from(sample_incident()))
+            .await
+            .unwrap();
+        note.assert_async().await;
+
+        let without_id = Incident {
+            source_id: None,
+            ..Incident::from(sample_incident())
+        };
+        assert!(source.mark_reviewed(&without_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_status() {
+        let mut server = mockito::Server::new_async().await;
+        let mut reopened: JsonValue =
+            serde_json::from_str(include_str!("tests/incident.json")).unwrap();
+        reopened["status"] = json!("triggered");
+        let get = server
+            .mock("GET", "/incidents/Q2XYZ9ABCDEF")
+            .match_header("authorization", "Token token=test-key")
+            .with_body(json!({ "incident": reopened }).to_string())
+            .create_async()
+            .await;
+        let source = PagerDutySource {
+            api_key: "test-key".to_owned(),
+            base_url: server.url(),
+            from_email: None,
+        };
+
+        let incident = Incident::from(sample_incident());
+        assert_eq!(incident.status.as_deref(), Some("resolved"));
+        let status = source.fetch_status(&incident).await.unwrap();
+        assert_eq!(status.as_deref(), Some("triggered"));
+        get.assert_async().await;
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::cli::lib::email::emails_match;
+
+use super::incident::Incident;
+use super::user::User;
+
+/// A POC map file: the POC emails for each incident tag, such as a service or team,
+/// written as a JSON object like `{"Fullnode RPC": ["jane@mystenlabs.com"]}`.
+pub type PocMap = BTreeMap<String, Vec<String>>;
+
+pub fn load_poc_map(path: &Path) -> Result<PocMap> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&contents)
+        .with_context(|| format!("{} is not a POC map of tags to emails", path.display()))
+}
+
+/// An email in the POC map that matches nobody in the combined user directory.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnresolvedPoc {
+    pub tag: String,
+    pub email: String,
+}
+
+/// Whether `email` belongs to the user in either Notion or Slack.
+fn user_has_email(user: &User, email: &str) -> bool {
+    let notion_email = user
+        .notion_user
+        .as_ref()
+        .and_then(|n| n.person.as_ref())
+        .map(|p| p.email.as_str());
+    let slack_email = user
+        .slack_user
+        .as_ref()
+        .and_then(|s| s.profile.as_ref())
+        .and_then(|p| p.email.as_deref());
+    [notion_email, slack_email]
+        .into_iter()
+        .flatten()
+        .any(|known| emails_match(known, email))
+}
+
+/// The emails in `map` that can't be resolved to any of `users`, in map order.
+pub fn unresolved_pocs(map: &PocMap, users: &[User]) -> Vec<UnresolvedPoc> {
+    map.iter()
+        .flat_map(|(tag, emails)| emails.iter().map(move |email| (tag, email)))
+        .filter(|(_, email)| !users.iter().any(|u| user_has_email(u, email)))
+        .map(|(tag, email)| UnresolvedPoc {
+            tag: tag.clone(),
+            email: email.clone(),
+        })
+        .collect()
+}
+
+/// The users `map` names as POCs of any of the incident's tags.
+fn mapped_pocs(map: &PocMap, incident: &Incident, users: &[User]) -> Vec<User> {
+    let emails: Vec<&String> = map
+        .iter()
+        .filter(|(tag, _)| incident.has_any_tag(std::slice::from_ref(*tag)))
+        .flat_map(|(_, emails)| emails)
+        .collect();
+    users
+        .iter()
+        .filter(|u| emails.iter().any(|email| user_has_email(u, email)))
+        .cloned()
+        .collect()
+}
+
+/// Give every incident without POCs the ones `map` names for its tags, returning
+/// how many incidents got POCs this way.
+pub fn apply_poc_map(map: &PocMap, users: &[User], incidents: &mut [Incident]) -> usize {
+    let mut applied = 0;
+    for incident in incidents
+        .iter_mut()
+        .filter(|i| i.poc_users.as_ref().is_none_or(Vec::is_empty))
+    {
+        let pocs = mapped_pocs(map, incident, users);
+        if !pocs.is_empty() {
+            incident.poc_users = Some(pocs);
+            applied += 1;
+        }
+    }
+    applied
+}
+
+/// Describe what validating the map found.
+pub fn render_unresolved(map: &PocMap, unresolved: &[UnresolvedPoc]) -> String {
+    let total: usize = map.values().map(Vec::len).sum();
+    if unresolved.is_empty() {
+        return format!("All {} POC emails match a known user", total);
+    }
+    let mut lines = vec![format!(
+        "{} of {} POC emails match nobody in Notion or Slack:",
+        unresolved.len(),
+        total
+    )];
+    lines.extend(
+        unresolved
+            .iter()
+            .map(|u| format!("• {} (for {})", u.email, u.tag)),
+    );
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::incidents::notion::{NotionPerson, NotionPersonDetails};
+
+    fn notion_user(email: &str) -> User {
+        User::new(
+            None,
+            Some(NotionPerson {
+                object: "user".to_owned(),
+                id: "N1".to_owned(),
+                name: "Jane Doe".to_owned(),
+                avatar_url: None,
+                person: Some(NotionPersonDetails {
+                    email: email.to_owned(),
+                }),
+            }),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_bogus_email_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pocs.json");
+        fs::write(
+            &path,
+            r#"{"Fullnode RPC": ["Jane@MystenLabs.com", "jnae@mystenlabs.com"]}"#,
+        )
+        .unwrap();
+        let map = load_poc_map(&path).unwrap();
+        let users = [notion_user("jane@mystenlabs.com")];
+
+        let unresolved = unresolved_pocs(&map, &users);
+        assert_eq!(
+            unresolved,
+            vec![UnresolvedPoc {
+                tag: "Fullnode RPC".to_owned(),
+                email: "jnae@mystenlabs.com".to_owned(),
+            }]
+        );
+        assert_eq!(
+            render_unresolved(&map, &unresolved),
+            "1 of 2 POC emails match nobody in Notion or Slack:\n• jnae@mystenlabs.com (for Fullnode RPC)"
+        );
+        assert_eq!(
+            render_unresolved(&map, &[]),
+            "All 2 POC emails match a known user"
+        );
+    }
+
+    #[test]
+    fn test_apply_gives_pocs_to_the_incidents_without_any() {
+        let map = PocMap::from([(
+            "Fullnode RPC".to_owned(),
+            vec!["jane@mystenlabs.com".to_owned()],
+        )]);
+        let users = [notion_user("jane@mystenlabs.com")];
+        let tagged = |number, tag: &str| Incident {
+            number,
+            tags: vec![tag.to_owned()],
+            ..Default::default()
+        };
+        let mut incidents = vec![
+            tagged(1, "fullnode rpc"),
+            Incident {
+                poc_users: Some(vec![notion_user("john@mystenlabs.com")]),
+                ..tagged(2, "Fullnode RPC")
+            },
+            tagged(3, "Indexer"),
+        ];
+
+        assert_eq!(apply_poc_map(&map, &users, &mut incidents), 1);
+        let emails: Vec<Vec<String>> = incidents
+            .iter()
+            .map(|i| {
+                i.poc_users
+                    .iter()
+                    .flatten()
+                    .map(|u| {
+                        u.notion_user
+                            .as_ref()
+                            .unwrap()
+                            .person
+                            .as_ref()
+                            .unwrap()
+                            .email
+                            .clone()
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            emails,
+            vec![
+                vec!["jane@mystenlabs.com".to_owned()],
+                vec!["john@mystenlabs.com".to_owned()],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_rejects_other_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pocs.json");
+        fs::write(&path, r#"["jane@mystenlabs.com"]"#).unwrap();
+        let err = load_poc_map(&path).unwrap_err();
+        assert!(err.to_string().contains("is not a POC map"));
+    }
+}
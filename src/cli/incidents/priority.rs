@@ -0,0 +1,133 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::HashMap;
+use tracing::warn;
+
+use super::incident::Incident;
+
+/// The priority given to incidents whose severity can't be mapped.
+pub const LOWEST_PRIORITY: &str = "P4";
+
+/// Whether `name` is already on the internal P0–P4 scale.
+fn is_internal(name: &str) -> bool {
+    name.strip_prefix('P')
+        .and_then(|n| n.parse::<u8>().ok())
+        .is_some_and(|n| n <= 4)
+}
+
+/// Parse a `--map-priority` value such as `SEV1=P1`.
+pub fn parse_priority_mapping(value: &str) -> Result<(String, String), String> {
+    let (severity, priority) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected SEVERITY=PRIORITY, got {:?}", value))?;
+    let (severity, priority) = (severity.trim(), priority.trim());
+    if severity.is_empty() {
+        return Err("the severity can't be empty".to_owned());
+    }
+    if !is_internal(priority) {
+        return Err(format!("{:?} is not a priority from P0 to P4", priority));
+    }
+    Ok((severity.to_owned(), priority.to_owned()))
+}
+
+/// Maps the severities sources report, such as `SEV1`, onto the internal P0–P4
+/// priorities, so incidents from every source are filtered alike.
+#[derive(Debug, Default)]
+pub struct PriorityMap {
+    /// Priorities by lowercased severity
+    priorities: HashMap<String, String>,
+}
+
+impl PriorityMap {
+    pub fn new(mappings: &[(String, String)]) -> Self {
+        Self {
+            priorities: mappings
+                .iter()
+                .map(|(severity, priority)| (severity.to_lowercase(), priority.clone()))
+                .collect(),
+        }
+    }
+
+    /// The internal priority for `severity`, which is matched case-insensitively.
+    ///
+    /// Severities already on the internal scale are kept unless mapped; any other
+    /// severity gets the lowest priority, with a warning.
+    pub fn map(&self, severity: &str) -> String {
+        if let Some(priority) = self.priorities.get(&severity.to_lowercase()) {
+            return priority.clone();
+        }
+        if is_internal(severity) {
+            return severity.to_owned();
+        }
+        warn!(
+            "No priority is mapped for severity {:?}, treating it as {}",
+            severity, LOWEST_PRIORITY
+        );
+        LOWEST_PRIORITY.to_owned()
+    }
+
+    /// Replace the severity of each incident with its internal priority.
+    pub fn apply(&self, incidents: &mut [Incident]) {
+        for priority in incidents.iter_mut().filter_map(|i| i.priority.as_mut()) {
+            if !priority.name.is_empty() {
+                priority.name = self.map(&priority.name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::incidents::pd::Priority;
+
+    fn with_priority(number: u64, name: &str) -> Incident {
+        Incident {
+            number,
+            priority: Some(Priority::from_name(name)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sev_mapping() {
+        let map = PriorityMap::new(&[
+            parse_priority_mapping("SEV1=P1").unwrap(),
+            parse_priority_mapping("sev2 = P2").unwrap(),
+        ]);
+        let mut incidents = vec![
+            with_priority(1, "SEV1"),
+            with_priority(2, "Sev2"),
+            with_priority(3, "P0"),
+            Incident::default(),
+        ];
+        map.apply(&mut incidents);
+        let names: Vec<Option<&str>> = incidents
+            .iter()
+            .map(|i| i.priority.as_ref().map(|p| p.name.as_str()))
+            .collect();
+        assert_eq!(names, vec![Some("P1"), Some("P2"), Some("P0"), None]);
+        assert_eq!(incidents[0].priority.as_ref().unwrap().u8(), 1);
+    }
+
+    #[test]
+    fn test_unmapped_severity_gets_lowest_priority() {
+        let map = PriorityMap::new(&[parse_priority_mapping("SEV1=P1").unwrap()]);
+        assert_eq!(map.map("SEV3"), LOWEST_PRIORITY);
+        assert_eq!(PriorityMap::default().map("critical"), LOWEST_PRIORITY);
+        assert_eq!(PriorityMap::default().map("P2"), "P2");
+    }
+
+    #[test]
+    fn test_parse_priority_mapping() {
+        assert_eq!(
+            parse_priority_mapping("SEV1=P1"),
+            Ok(("SEV1".to_owned(), "P1".to_owned()))
+        );
+        assert!(parse_priority_mapping("SEV1").is_err());
+        assert!(parse_priority_mapping("=P1").is_err());
+        assert!(parse_priority_mapping("SEV1=P9").is_err());
+        assert!(parse_priority_mapping("SEV1=high").is_err());
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+use inquire::{Confirm, MultiSelect, Select};
+
+use super::incident::Incident;
+use super::user::User;
+use crate::cli::slack::Channel;
+
+/// What to do with an incident up for review.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ReviewChoice {
+    Keep,
+    Exclude,
+    /// Defer the incident to a later review
+    Snooze,
+}
+
+/// Asks the operator questions during incident review.
+///
+/// The review flow only talks to the terminal through this trait so it can be
+/// driven by canned answers in tests.
+pub(crate) trait Prompter {
+    /// Ask a yes/no question.
+    fn confirm(&self, message: &str, default: bool) -> Result<bool>;
+
+    /// Ask whether to keep, exclude or snooze the incidents just shown.
+    fn choose_review(&self, message: &str) -> Result<ReviewChoice>;
+
+    /// Ask the operator to pick the POCs for an incident from `users`.
+    fn select_pocs(&self, users: Vec<User>) -> Result<Vec<User>>;
+
+    /// Ask the operator which of a group of incidents is the canonical one,
+    /// returning its index.
+    fn select_canonical(&self, incidents: &[Incident]) -> Result<usize>;
+
+    /// Ask the operator which of several candidate channels belongs to the
+    /// incident, returning its index or `None` if none of them does.
+    fn select_channel(&self, incident: &Incident, channels: &[&Channel]) -> Result<Option<usize>>;
+
+    /// Ask the operator which of the named review databases to insert into,
+    /// returning its index.
+    fn select_database(&self, names: &[&str]) -> Result<usize>;
+}
+
+/// Prompts the operator interactively in the terminal.
+pub(crate) struct InquirePrompter;
+
+impl Prompter for InquirePrompter {
+    fn confirm(&self, message: &str, default: bool) -> Result<bool> {
+        Confirm::new(message)
+            .with_default(default)
+            .prompt()
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    fn choose_review(&self, message: &str) -> Result<ReviewChoice> {
+        let choices = [
+            ReviewChoice::Keep,
+            ReviewChoice::Exclude,
+            ReviewChoice::Snooze,
+        ];
+        Select::new(message, vec!["Keep", "Exclude", "Snooze to a later review"])
+            .with_starting_cursor(1)
+            .raw_prompt()
+            .map(|choice| choices[choice.index])
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    fn select_pocs(&self, users: Vec<User>) -> Result<Vec<User>> {
+        MultiSelect::new(
+            "Please select the users who are POCs for this incident",
+            users,
+        )
+        .with_scorer(&|query, user, _, _| {
+            (query.is_empty() || user.matches_search(query)).then_some(0)
+        })
+        .with_default(&[])
+        .prompt()
+        .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    fn select_canonical(&self, incidents: &[Incident]) -> Result<usize> {
+        let options: Vec<String> = incidents
+            .iter()
+            .map(|i| format!("{}: {}", i.number, i.title))
+            .collect();
+        Select::new("Which incident should the others be merged into?", options)
+            .raw_prompt()
+            .map(|choice| choice.index)
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    fn select_channel(&self, incident: &Incident, channels: &[&Channel]) -> Result<Option<usize>> {
+        let mut options: Vec<String> = channels.iter().map(|c| format!("#{}", c.name)).collect();
+        options.push("None of these".to_owned());
+        Select::new(
+            &format!(
+                "Several channels could belong to incident {}: {}. Which one is it?",
+                incident.number, incident.title
+            ),
+            options,
+        )
+        .raw_prompt()
+        .map(|choice| (choice.index < channels.len()).then_some(choice.index))
+        .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    fn select_database(&self, names: &[&str]) -> Result<usize> {
+        Select::new(
+            "Which review database should the incidents go into?",
+            names.to_vec(),
+        )
+        .raw_prompt()
+        .map(|choice| choice.index)
+        .map_err(|e| anyhow::anyhow!(e))
+    }
+}
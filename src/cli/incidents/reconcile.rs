@@ -0,0 +1,140 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+
+use super::incident::Incident;
+use super::selection::IncidentLookup;
+use super::source::IncidentSource;
+
+/// The source status of incidents that are over
+const RESOLVED_STATUS: &str = "resolved";
+
+/// An incident whose review is done in Notion but that is no longer resolved in
+/// its source.
+#[derive(Debug)]
+pub struct Diverged {
+    pub incident: Incident,
+    pub source_status: String,
+}
+
+/// Find the incidents marked as reviewed in Notion that were reopened since.
+///
+/// The reviewed incidents come from Notion rather than the source, which only
+/// lists resolved incidents.
+pub async fn find_reopened(
+    lookup: &impl IncidentLookup,
+    source: &impl IncidentSource,
+) -> Result<Vec<Diverged>> {
+    let mut diverged = vec![];
+    for incident in lookup.reviewed_incidents().await? {
+        match source.fetch_status(&incident).await? {
+            Some(status) if status != RESOLVED_STATUS => diverged.push(Diverged {
+                incident,
+                source_status: status,
+            }),
+            _ => {}
+        }
+    }
+    Ok(diverged)
+}
+
+/// List the diverged incidents, which need to be reviewed again.
+pub fn render_reopened(diverged: &[Diverged]) -> String {
+    if diverged.is_empty() {
+        return "No reviewed incidents have been reopened".to_owned();
+    }
+    let mut lines = vec![format!(
+        "{} incidents were reviewed but have been reopened, and need another review:",
+        diverged.len()
+    )];
+    lines.extend(diverged.iter().map(|d| {
+        format!(
+            "• {} {}: reviewed in Notion, {} in the source",
+            d.incident.number, d.incident.title, d.source_status
+        )
+    }));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Local};
+    use std::collections::HashMap;
+
+    /// Serves the reviewed incidents and the current source statuses by number.
+    #[derive(Default)]
+    struct FakeState {
+        reviewed: Vec<u64>,
+        source_statuses: HashMap<u64, &'static str>,
+    }
+
+    impl IncidentLookup for FakeState {
+        async fn is_scheduled(&self, incident: &Incident) -> Result<bool> {
+            Ok(self.reviewed.contains(&incident.number))
+        }
+
+        async fn reviewed_incidents(&self) -> Result<Vec<Incident>> {
+            Ok(self.reviewed.iter().copied().map(incident).collect())
+        }
+
+        async fn has_postmortem(&self, _incident: &Incident) -> Result<bool> {
+            Ok(false)
+        }
+    }
+
+    impl IncidentSource for FakeState {
+        async fn fetch_recent(
+            &self,
+            _limit: usize,
+            _start_time: DateTime<Local>,
+            _end_time: DateTime<Local>,
+        ) -> Result<Vec<Incident>> {
+            Ok(vec![])
+        }
+
+        async fn fetch_status(&self, incident: &Incident) -> Result<Option<String>> {
+            Ok(self
+                .source_statuses
+                .get(&incident.number)
+                .map(|s| s.to_string()))
+        }
+    }
+
+    fn incident(number: u64) -> Incident {
+        Incident {
+            number,
+            title: format!("Incident {}", number),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_reopened_flags_reviewed_but_reopened() {
+        let state = FakeState {
+            reviewed: vec![1, 2, 3],
+            source_statuses: HashMap::from([(1, "triggered"), (2, "resolved"), (4, "triggered")]),
+        };
+
+        let diverged = find_reopened(&state, &state).await.unwrap();
+
+        // 2 is still resolved, 3 is unknown to the source and 4 wasn't reviewed
+        assert_eq!(diverged.len(), 1);
+        assert_eq!(diverged[0].incident.number, 1);
+        assert_eq!(diverged[0].source_status, "triggered");
+        assert_eq!(
+            render_reopened(&diverged),
+            "1 incidents were reviewed but have been reopened, and need another review:\n\
+             • 1 Incident 1: reviewed in Notion, triggered in the source"
+        );
+    }
+
+    #[test]
+    fn test_render_reopened_none() {
+        assert_eq!(
+            render_reopened(&[]),
+            "No reviewed incidents have been reopened"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+
+use super::incident::Incident;
+
+/// The review channel of each owning team, so large orgs can review incidents
+/// where the teams that own them are.
+#[derive(Debug, Clone, Default)]
+pub struct TeamChannels {
+    /// Channels by lowercased team name
+    channels: HashMap<String, String>,
+}
+
+impl TeamChannels {
+    /// Parse `TEAM=CHANNEL` pairs separated by commas, such as
+    /// `SUIOP_TEAM_CHANNELS=Infra=infra-reviews,Wallet=#wallet-reviews`.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut channels = HashMap::new();
+        for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (team, channel) = pair
+                .split_once('=')
+                .map(|(team, channel)| (team.trim(), channel.trim().trim_start_matches('#')))
+                .filter(|(team, channel)| !team.is_empty() && !channel.is_empty())
+                .ok_or_else(|| anyhow!("expected TEAM=CHANNEL, got {:?}", pair))?;
+            channels.insert(team.to_lowercase(), channel.to_owned());
+        }
+        Ok(Self { channels })
+    }
+
+    /// The team channels from `SUIOP_TEAM_CHANNELS`, if any are configured.
+    pub fn from_env() -> Result<Self> {
+        Self::parse(&std::env::var("SUIOP_TEAM_CHANNELS").unwrap_or_default())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// The channel for incidents owned by `team`, or `default` for incidents
+    /// without a team or whose team has no channel.
+    pub fn channel_for<'a>(&'a self, team: Option<&str>, default: &'a str) -> &'a str {
+        team.and_then(|t| self.channels.get(&t.to_lowercase()))
+            .map_or(default, String::as_str)
+    }
+
+    /// Split the selected and excluded incidents by the channel they are
+    /// reviewed in, ordered by channel name.
+    pub fn route(
+        &self,
+        to_review: &[Incident],
+        excluded: &[Incident],
+        default: &str,
+    ) -> BTreeMap<String, (Vec<Incident>, Vec<Incident>)> {
+        let mut routes: BTreeMap<String, (Vec<Incident>, Vec<Incident>)> = BTreeMap::new();
+        for incident in to_review {
+            let channel = self.channel_for(incident.owning_team.as_deref(), default);
+            routes
+                .entry(channel.to_owned())
+                .or_default()
+                .0
+                .push(incident.clone());
+        }
+        for incident in excluded {
+            let channel = self.channel_for(incident.owning_team.as_deref(), default);
+            routes
+                .entry(channel.to_owned())
+                .or_default()
+                .1
+                .push(incident.clone());
+        }
+        routes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owned_by(number: u64, team: Option<&str>) -> Incident {
+        Incident {
+            number,
+            owning_team: team.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_route_by_team() {
+        let teams = TeamChannels::parse("Infra=infra-reviews, wallet=#wallet-reviews").unwrap();
+        let to_review = vec![
+            owned_by(1, Some("infra")),
+            owned_by(2, Some("Wallet")),
+            owned_by(3, None),
+            owned_by(4, Some("DeFi")),
+        ];
+        let excluded = vec![owned_by(5, Some("Infra"))];
+
+        let routes = teams.route(&to_review, &excluded, "incident-postmortems");
+        let numbers =
+            |incidents: &[Incident]| incidents.iter().map(|i| i.number).collect::<Vec<_>>();
+        let summary: Vec<(&str, Vec<u64>, Vec<u64>)> = routes
+            .iter()
+            .map(|(channel, (kept, dropped))| (channel.as_str(), numbers(kept), numbers(dropped)))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                // no team, or a team without a channel, goes to the default
+                ("incident-postmortems", vec![3, 4], vec![]),
+                ("infra-reviews", vec![1], vec![5]),
+                ("wallet-reviews", vec![2], vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_pairs() {
+        assert!(TeamChannels::parse("").unwrap().is_empty());
+        assert!(TeamChannels::parse("Infra").is_err());
+        assert!(TeamChannels::parse("Infra=#").is_err());
+    }
+}
@@ -1,69 +1,552 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::Result;
-use inquire::{Confirm, MultiSelect};
-use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use strsim::normalized_damerau_levenshtein;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-use crate::cli::incidents::notion::{Notion, INCIDENT_DB_ID, INCIDENT_DB_NAME};
-use crate::cli::incidents::user::User;
+use crate::cli::incidents::notion::Notion;
+use crate::cli::incidents::user::{check_slack_directory, combine_users, User};
+use crate::cli::lib::cache::local_cache_dir;
+use crate::cli::lib::concurrency::{concurrency, try_for_each_bounded};
 use crate::cli::lib::utils::day_of_week;
-use crate::cli::slack::{Channel, Slack};
+use crate::cli::slack::blocks::Blocks;
+use crate::cli::slack::{Channel, PostedMessage, Slack, SlackUser};
 use crate::DEBUG_MODE;
 
+use super::checkpoint::InsertCheckpoint;
+use super::database::{choose_database, configured_databases, ReviewDatabase};
+use super::export::ReviewExport;
+use super::frame::MessageFrame;
 use super::incident::Incident;
+use super::last_run::{filter_incidents_since_last_run, save_last_run};
+use super::markdown::{render_markdown, ReviewFormat};
+use super::metrics::ReviewMetrics;
+use super::number::{NumberFormat, NUMBER_FORMAT};
+use super::output::ReviewOutput;
+use super::overrides::Overrides;
+use super::poc_map::{apply_poc_map, load_poc_map, render_unresolved, unresolved_pocs};
+use super::prompt::{InquirePrompter, Prompter, ReviewChoice};
+use super::routing::TeamChannels;
+use super::snooze::SnoozeList;
+use super::source::IncidentSource;
+use super::target::Target;
 
-fn request_pocs(users: Vec<User>) -> Result<Vec<User>> {
-    MultiSelect::new(
-        "Please select the users who are POCs for this incident",
-        users,
-    )
-    .with_default(&[])
-    .prompt()
-    .map_err(|e| anyhow::anyhow!(e))
+/// Options controlling which incidents are considered during interactive review.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct ReviewOptions {
+    /// skip incidents that already have a slack channel instead of always including them
+    #[arg(long, default_value = "false")]
+    pub require_no_channel: bool,
+    /// only review incidents with this tag (repeat to allow any of several tags)
+    #[arg(long = "tag")]
+    pub tags: Vec<String>,
+    /// don't insert the reviewed incidents into Notion (and don't ask to)
+    #[arg(long, default_value = "false")]
+    pub no_insert: bool,
+    /// don't send the review message to Slack (and don't ask to)
+    #[arg(long, default_value = "false")]
+    pub no_send: bool,
+    /// only print the review outcome, logging progress at debug level instead
+    #[arg(short, long, default_value = "false")]
+    pub quiet: bool,
+    /// how to open the review message
+    #[command(flatten)]
+    pub greeting: GreetingOptions,
+    /// post a root message and each selected incident as a reply in its thread,
+    /// instead of one message listing them all
+    #[arg(long, default_value = "false")]
+    pub threaded: bool,
+    /// post the review message as Block Kit sections, with a button linking to each
+    /// incident, instead of plain text
+    #[arg(long, default_value = "false", conflicts_with = "threaded")]
+    pub blocks: bool,
+    /// offer to first show the review message in its channels to only this Slack
+    /// user (by id), to check it before everyone sees it
+    #[arg(long, value_name = "USER_ID")]
+    pub preview_to: Option<String>,
+    /// react to the review message with ✅ once everything is written, to show the
+    /// selection is final
+    #[arg(long, default_value = "false")]
+    pub react_when_final: bool,
+    /// also send each POC with a Slack user a direct message listing the selected
+    /// incidents they're a POC of
+    #[arg(long, default_value = "false")]
+    pub dm_pocs: bool,
+    /// template for each incident's line in the review message, using `{number}`,
+    /// `{title}`, `{priority}`, `{pocs}`, `{channel}` and `{url}`
+    #[arg(long, value_name = "TEMPLATE")]
+    pub line_template: Option<String>,
+    /// exclude incidents created more than this many days ago
+    #[arg(long, value_name = "DAYS")]
+    pub max_age: Option<u64>,
+    /// exclude incidents the source grouped fewer than this many alerts or events into
+    #[arg(long, value_name = "N")]
+    pub min_events: Option<u64>,
+    /// only review incidents created since the last completed review
+    #[arg(long, default_value = "false")]
+    pub since_last_run: bool,
+    /// match POCs against the Slack users fetched so far when the rest of the
+    /// directory can't be fetched, instead of stopping
+    #[arg(long, default_value = "false")]
+    pub allow_partial_users: bool,
+    /// stop before writing anything if any POC of a selected incident can't be both
+    /// mentioned in Slack and set in Notion
+    #[arg(long, default_value = "false")]
+    pub strict_pocs: bool,
+    /// a JSON file of include and exclude rules, by incident number, priority or
+    /// title regex, deciding incidents before the rest are asked about
+    #[arg(long, value_name = "PATH")]
+    pub overrides: Option<PathBuf>,
+    /// a JSON file of incident tags to POC emails, whose POCs are given to the kept
+    /// incidents no POCs were selected for
+    #[arg(long, value_name = "PATH")]
+    pub poc_map: Option<PathBuf>,
+    /// keep asking for POCs until at least one is selected for each kept incident
+    #[arg(long, default_value = "false")]
+    pub require_poc: bool,
+    /// archive the Slack channels of the reviewed incidents that are resolved
+    #[arg(long, default_value = "false")]
+    pub archive_channels: bool,
+    /// record in the incident source that inserted incidents were scheduled for review
+    #[arg(long, default_value = "false")]
+    pub mark_reviewed: bool,
+    /// the Notion database to insert into [default: debug in debug mode, otherwise prod]
+    #[arg(long = "db", value_enum)]
+    pub db_target: Option<Target>,
+    /// the review database from `SUIOP_REVIEW_DATABASES` to insert into, by name;
+    /// the operator is asked when several are configured and none is named
+    #[arg(long, value_name = "NAME", conflicts_with = "db_target")]
+    pub db_name: Option<String>,
+    /// The configured review database chosen for this run, if any
+    #[arg(skip)]
+    pub database: Option<ReviewDatabase>,
+    /// the Slack channel to send to [default: debug in debug mode, otherwise prod]
+    #[arg(long = "channel", value_enum)]
+    pub channel_target: Option<Target>,
+    /// only report the state of the incidents up for review, without asking anything
+    /// or writing to Notion, Slack or the incident source
+    #[arg(long, default_value = "false", conflicts_with = "mark_reviewed")]
+    pub audit: bool,
+    /// only show how the Notion page of each selected incident would change,
+    /// without sending or inserting anything
+    #[arg(long, default_value = "false", conflicts_with = "mark_reviewed")]
+    pub diff: bool,
+    /// mention this Slack user group (by its handle) at the top of the review message
+    #[arg(long, value_name = "HANDLE")]
+    pub notify_group: Option<String>,
+    /// exclude incidents whose Notion page already links to a postmortem
+    #[arg(long, default_value = "false")]
+    pub skip_with_postmortem: bool,
+    /// how many days a snoozed incident is deferred by
+    #[arg(long, default_value = "7", value_name = "DAYS")]
+    pub snooze_days: u64,
+    /// how to show the reviewed incidents; a Markdown document is printed besides
+    /// the Slack message, which is sent either way
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReviewFormat,
+    /// fetch the Notion database schema again instead of using the cached copy
+    #[arg(long, default_value = "false")]
+    pub refresh: bool,
+    /// write metrics about the review to this file, in the Prometheus text format
+    /// read by node_exporter's textfile collector
+    #[arg(long, value_name = "PATH")]
+    pub metrics_file: Option<PathBuf>,
+    /// also save the reviewed incidents to this directory, for `incidents replay`
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+}
+
+/// Options controlling the greeting that opens the review message.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct GreetingOptions {
+    /// greeting to open the review message with; `{day}` is replaced by the day of the week
+    #[arg(long, conflicts_with = "no_greeting")]
+    pub greeting: Option<String>,
+    /// open the review message without a greeting
+    #[arg(long, default_value = "false")]
+    pub no_greeting: bool,
+}
+
+/// The greeting used when none is configured.
+const DEFAULT_GREETING: &str = "Hello everyone and happy {day}!";
+
+impl ReviewOptions {
+    /// Which incident selection database to use
+    fn db(&self) -> Target {
+        Target::resolve(self.db_target, *DEBUG_MODE)
+    }
+
+    /// The database to insert into: the configured one chosen for this run, or
+    /// the built-in one of the target.
+    fn database(&self) -> ReviewDatabase {
+        self.database
+            .clone()
+            .unwrap_or_else(|| ReviewDatabase::of(self.db()))
+    }
+
+    /// Which Slack channel to send the review message to
+    fn channel(&self) -> Target {
+        Target::resolve(self.channel_target, *DEBUG_MODE)
+    }
+}
+
+impl GreetingOptions {
+    /// The greeting line for the review message, or `None` when it is disabled.
+    pub(super) fn line(&self, day: &str) -> Option<String> {
+        if self.no_greeting {
+            return None;
+        }
+        let template = self.greeting.as_deref().unwrap_or(DEFAULT_GREETING);
+        Some(template.replace("{day}", day))
+    }
+}
+
+/// Keep only the incidents carrying at least one of `tags`.
+///
+/// An empty tag list keeps every incident.
+fn filter_incidents_by_tags(incidents: Vec<Incident>, tags: &[String]) -> Vec<Incident> {
+    if tags.is_empty() {
+        return incidents;
+    }
+    incidents
+        .into_iter()
+        .filter(|i| i.has_any_tag(tags))
+        .collect()
+}
+
+/// Drop the incidents created more than `max_age_days` before `now`, returning the
+/// remaining incidents and how many were aged out.
+///
+/// Incidents without a known creation date are kept.
+fn filter_incidents_by_age(
+    incidents: Vec<Incident>,
+    max_age_days: Option<u64>,
+    now: DateTime<Utc>,
+) -> (Vec<Incident>, usize) {
+    let Some(days) = max_age_days else {
+        return (incidents, 0);
+    };
+    let cutoff = now - Duration::days(days as i64);
+    let before = incidents.len();
+    let kept: Vec<Incident> = incidents
+        .into_iter()
+        .filter(|i| i.created_at_utc().is_none_or(|created| created >= cutoff))
+        .collect();
+    let aged_out = before - kept.len();
+    (kept, aged_out)
+}
+
+/// Note when each incident's Slack channel last had a message, to help judge
+/// whether it is still active. Lookups that fail are only warned about.
+async fn annotate_last_activity(slack: &Slack, incidents: &mut [Incident]) {
+    for incident in incidents.iter_mut() {
+        let Some(channel) = &incident.slack_channel else {
+            continue;
+        };
+        match slack.last_activity(&channel.id).await {
+            Ok(at) => incident.last_activity = at,
+            Err(e) => warn!(
+                "Couldn't look up the last activity in #{}: {:#}",
+                channel.name, e
+            ),
+        }
+    }
+}
+
+/// Drop the incidents with fewer than `min_events` alerts or events, returning the
+/// remaining incidents and how many were dropped.
+///
+/// Incidents without a known count are kept.
+fn filter_incidents_by_min_events(
+    incidents: Vec<Incident>,
+    min_events: Option<u64>,
+) -> (Vec<Incident>, usize) {
+    let Some(min_events) = min_events else {
+        return (incidents, 0);
+    };
+    let before = incidents.len();
+    let kept: Vec<Incident> = incidents
+        .into_iter()
+        .filter(|i| i.count.is_none_or(|count| count >= min_events))
+        .collect();
+    let dropped = before - kept.len();
+    (kept, dropped)
+}
+
+/// Drop the incidents that already link to a postmortem, returning how many were dropped.
+async fn filter_incidents_with_postmortem(
+    lookup: &impl IncidentLookup,
+    incidents: Vec<Incident>,
+) -> Result<(Vec<Incident>, usize)> {
+    let mut kept = Vec::with_capacity(incidents.len());
+    let mut skipped = 0;
+    for incident in incidents {
+        if lookup.has_postmortem(&incident).await? {
+            debug!("Skipping incident {} with a postmortem", incident.number);
+            skipped += 1;
+        } else {
+            kept.push(incident);
+        }
+    }
+    Ok((kept, skipped))
+}
+
+/// The side effects of finishing a review, abstracted so they can be recorded in tests.
+pub(crate) trait ReviewPublisher {
+    /// Post a message, in the thread of `thread_ts` if given.
+    async fn send_message(
+        &self,
+        channel: &str,
+        message: &str,
+        thread_ts: Option<&str>,
+    ) -> Result<PostedMessage>;
+    /// Post a Block Kit message with `text` as its fallback.
+    async fn send_blocks(
+        &self,
+        channel: &str,
+        text: &str,
+        blocks: &serde_json::Value,
+    ) -> Result<PostedMessage>;
+    /// Post a message in the channel that only the given user sees.
+    async fn send_ephemeral(&self, channel: &str, user_id: &str, text: &str) -> Result<String>;
+    /// React to the message posted at `ts` in the channel with the given id.
+    async fn add_reaction(&self, channel: &str, ts: &str, emoji: &str) -> Result<()>;
+    /// Open a direct message with the given Slack user, returning its channel id.
+    async fn open_dm(&self, user_id: &str) -> Result<String>;
+    /// Insert the incident into Notion, returning the url of its page
+    async fn insert_incident(&self, incident: &Incident) -> Result<String>;
+    /// Fetch the Slack users again, bypassing the cache.
+    async fn refresh_slack_users(&self) -> Result<Vec<SlackUser>>;
+    /// Record in the incident's source that it was scheduled for review.
+    async fn mark_reviewed(&self, incident: &Incident) -> Result<()>;
+    /// Archive the Slack channel with the given id.
+    async fn archive_channel(&self, channel: &str) -> Result<()>;
+}
+
+/// Read-only lookups of what has already been recorded about incidents.
+pub(crate) trait IncidentLookup {
+    /// Whether the incident is already in the incident selection database.
+    async fn is_scheduled(&self, incident: &Incident) -> Result<bool>;
+
+    /// The incidents whose review is marked as done.
+    async fn reviewed_incidents(&self) -> Result<Vec<Incident>>;
+
+    /// Whether the incident already links to a postmortem doc.
+    async fn has_postmortem(&self, incident: &Incident) -> Result<bool>;
+}
+
+impl IncidentLookup for Notion {
+    async fn is_scheduled(&self, incident: &Incident) -> Result<bool> {
+        Ok(self.get_incident_page(incident.number).await?.is_some())
+    }
+
+    async fn reviewed_incidents(&self) -> Result<Vec<Incident>> {
+        self.get_reviewed_incidents().await
+    }
+
+    async fn has_postmortem(&self, incident: &Incident) -> Result<bool> {
+        Notion::has_postmortem(self, incident.number).await
+    }
+}
+
+/// Publishes the review results to the real Slack and Notion APIs.
+struct LivePublisher<'a, S> {
+    slack: &'a Slack,
+    notion: &'a Notion,
+    source: &'a S,
+}
+
+impl<S: IncidentSource> ReviewPublisher for LivePublisher<'_, S> {
+    async fn send_message(
+        &self,
+        channel: &str,
+        message: &str,
+        thread_ts: Option<&str>,
+    ) -> Result<PostedMessage> {
+        self.slack.send_message(channel, message, thread_ts).await
+    }
+
+    async fn send_blocks(
+        &self,
+        channel: &str,
+        text: &str,
+        blocks: &serde_json::Value,
+    ) -> Result<PostedMessage> {
+        self.slack.send_blocks(channel, text, blocks).await
+    }
+
+    async fn send_ephemeral(&self, channel: &str, user_id: &str, text: &str) -> Result<String> {
+        self.slack.send_ephemeral(channel, user_id, text).await
+    }
+
+    async fn add_reaction(&self, channel: &str, ts: &str, emoji: &str) -> Result<()> {
+        self.slack.add_reaction(channel, ts, emoji).await
+    }
+
+    async fn open_dm(&self, user_id: &str) -> Result<String> {
+        self.slack.open_dm(user_id).await
+    }
+
+    async fn insert_incident(&self, incident: &Incident) -> Result<String> {
+        let description = incident.description.clone();
+        self.notion
+            .insert_incident(incident.clone(), description.as_deref())
+            .await
+    }
+
+    async fn refresh_slack_users(&self) -> Result<Vec<SlackUser>> {
+        self.slack.refresh_users().await
+    }
+
+    async fn mark_reviewed(&self, incident: &Incident) -> Result<()> {
+        self.source.mark_reviewed(incident).await
+    }
+
+    async fn archive_channel(&self, channel: &str) -> Result<()> {
+        self.slack.archive_channel(channel).await
+    }
+}
+
+/// How many Notion people have no Slack user.
+fn count_unmatched_people(combined_users: &[User]) -> usize {
+    combined_users
+        .iter()
+        .filter(|u| u.notion_user.is_some() && !u.has_slack_user())
+        .count()
+}
+
+/// Warn about the Notion people without a Slack user, who can't be mentioned as POCs.
+fn unmatched_people_warning(combined_users: &[User]) -> Option<String> {
+    let unmatched = count_unmatched_people(combined_users);
+    (unmatched > 0).then(|| {
+        format!(
+            "Warning: {} Notion people couldn't be matched to Slack and won't be mentionable as POCs",
+            unmatched
+        )
+    })
 }
 
 /// Filter incidents based on whether they have <= min_priority priority or any slack
 /// channel associated.
-fn filter_incidents_for_review(incidents: Vec<Incident>, min_priority: &str) -> Vec<Incident> {
+///
+/// When `require_no_channel` is set, incidents with a slack channel are treated as
+/// already handled and excluded, so only priority matters for the rest.
+fn filter_incidents_for_review(
+    incidents: Vec<Incident>,
+    min_priority: &str,
+    require_no_channel: bool,
+) -> Vec<Incident> {
     let min_priority_u = min_priority
         .trim_start_matches("P")
         .parse::<u8>()
         .expect("Parsing priority");
     incidents
         .into_iter()
-        // filter on priority <= min_priority and any slack channel association
         .filter(|i| {
-            i.priority
+            let has_priority = i
+                .priority
                 .clone()
                 .filter(|p| !p.name.is_empty() && p.u8() <= min_priority_u)
-                .is_some()
-                || i.slack_channel.is_some()
+                .is_some();
+            if require_no_channel {
+                // filter on priority <= min_priority and no slack channel association
+                has_priority && i.slack_channel.is_none()
+            } else {
+                // filter on priority <= min_priority or any slack channel association
+                has_priority || i.slack_channel.is_some()
+            }
         })
         .collect()
 }
 
-/// Normalizes an email address for comparison by converting to lowercase and trimming whitespace
-fn normalize_email(email: &str) -> String {
-    email.trim().to_lowercase()
-}
-
-/// Compares two email addresses after normalization
-fn emails_match(email1: &str, email2: &str) -> bool {
-    let normalized1 = normalize_email(email1);
-    let normalized2 = normalize_email(email2);
-    normalized1 == normalized2
+/// The configured review database to insert into, if any.
+///
+/// The configured databases are real ones, so none is chosen while the target is
+/// debug, and naming one with `--db-name` then fails rather than silently
+/// overriding `--db` or `DEBUG_MODE`.
+fn choose_review_database(
+    databases: &[ReviewDatabase],
+    options: &ReviewOptions,
+    prompter: &impl Prompter,
+) -> Result<Option<ReviewDatabase>> {
+    if options.db() == Target::Debug {
+        if let Some(name) = &options.db_name {
+            return Err(anyhow!(
+                "the review database {:?} can't be used while the target is debug, \
+                 pass `--db prod` or unset DEBUG_MODE to insert into it",
+                name
+            ));
+        }
+        if !databases.is_empty() {
+            info!("Debug target, ignoring the databases in SUIOP_REVIEW_DATABASES");
+        }
+        return Ok(None);
+    }
+    choose_database(databases, options.db_name.as_deref(), prompter)
 }
 
-pub async fn review_recent_incidents(incidents: Vec<Incident>) -> Result<()> {
-    let slack = Slack::new().await;
-    let notion = Notion::new();
+pub async fn review_recent_incidents(
+    incidents: Vec<Incident>,
+    source: &impl IncidentSource,
+    options: &ReviewOptions,
+) -> Result<()> {
+    let started_at = Utc::now();
+    // ask which database is meant up front, before the review itself
+    let chosen;
+    let options = match choose_review_database(&configured_databases()?, options, &InquirePrompter)?
+    {
+        Some(database) => {
+            chosen = ReviewOptions {
+                database: Some(database),
+                ..options.clone()
+            };
+            &chosen
+        }
+        None => options,
+    };
+    // fail before anything is fetched if there is no earlier review to start from
+    let (incidents, before_last_run) = if options.since_last_run {
+        filter_incidents_since_last_run(&local_cache_dir(), incidents)?
+    } else {
+        (incidents, 0)
+    };
+    let slack = Slack::new().await?;
+    let notion = Notion::new(options.db()).with_database(&options.database());
+    if options.refresh {
+        notion.forget_schema()?;
+    }
+    // check the group exists before the review rather than after it
+    let group_mention = match &options.notify_group {
+        Some(handle) => Some(slack.find_usergroup(handle).await?.mention()),
+        None => None,
+    };
 
     if *DEBUG_MODE {
         info!("Retrieved {} users from Slack", slack.users.len());
     }
+    check_slack_directory(
+        slack.users_incomplete.as_deref(),
+        slack.users.len(),
+        options.allow_partial_users,
+    )?;
+
+    if options.audit {
+        if let Err(e) = notion.check_schema().await {
+            warn!(
+                "The Notion database doesn't match what inserts need: {:#}",
+                e
+            );
+        }
+    } else if !options.no_insert {
+        // fail before the review rather than after it if we can't insert the results
+        notion.check_schema().await?;
+    }
 
     let notion_people = notion.get_all_people().await?;
 
@@ -71,57 +554,17 @@ pub async fn review_recent_incidents(incidents: Vec<Incident>) -> Result<()> {
         info!("Retrieved {} people from Notion", notion_people.len());
     }
 
-    let combined_users = notion_people
-        .into_iter()
-        .map(|nu| {
-            let notion_email = nu.person.as_ref().map(|p| &p.email);
-            let slack_user = if let Some(email) = notion_email {
-                slack.users.iter().find(|su| {
-                    if let Some(profile) = &su.profile {
-                        if let Some(slack_email) = &profile.email {
-                            if *DEBUG_MODE {
-                                debug!(
-                                    "Comparing emails - Notion: '{}', Slack: '{}'",
-                                    email, slack_email
-                                );
-                                let matches = emails_match(email, slack_email);
-                                if matches {
-                                    debug!("Email match found!");
-                                }
-                                matches
-                            } else {
-                                emails_match(email, slack_email)
-                            }
-                        } else {
-                            if *DEBUG_MODE {
-                                debug!("Slack user {} has no email", su.name);
-                            }
-                            false
-                        }
-                    } else {
-                        if *DEBUG_MODE {
-                            debug!("Slack user {} has no profile", su.name);
-                        }
-                        false
-                    }
-                })
-            } else {
-                if *DEBUG_MODE {
-                    debug!("Notion user {} has no email", nu.name);
-                }
-                None
-            };
-
-            let user = User::new(slack_user.cloned(), Some(nu))
-                .expect("Failed to convert user from Notion");
-
-            if *DEBUG_MODE {
-                debug!("Created user: {} [{}]", user, user.system_presence());
-            }
-
-            user
-        })
-        .collect::<Vec<_>>();
+    let combined_users = combine_users(notion_people, &slack.users);
+    let output = ReviewOutput::stdout(options.quiet);
+    if options.since_last_run {
+        output.progress(format!(
+            "Excluded {} incidents created before the last review",
+            before_last_run
+        ));
+    }
+    if let Some(warning) = unmatched_people_warning(&combined_users) {
+        output.progress(warning);
+    }
 
     if *DEBUG_MODE {
         info!("Found {} combined users", combined_users.len());
@@ -171,74 +614,354 @@ pub async fn review_recent_incidents(incidents: Vec<Incident>) -> Result<()> {
         }
     }
 
-    let filtered_incidents = filter_incidents_for_review(incidents, "P2");
-    println!("Reviewing {} recent incidents", filtered_incidents.len());
-    let mut group_map = group_by_similar_title(filtered_incidents, 0.9);
-    let mut to_review = vec![];
-    let mut excluded = vec![];
-    for (title, incident_group) in group_map.iter_mut() {
-        let treat_as_one = if incident_group.len() > 1 {
-            println!(
-                "There are {} incidents with a title similar to this: {}",
-                &incident_group.len(),
-                title
-            );
-            println!("All incidents with a similar title:");
-            for i in incident_group.iter() {
-                i.print(false)?;
-            }
-            Confirm::new("Treat them as one?")
-                .with_default(true)
-                .prompt()
-                .expect("Unexpected response")
-        } else {
-            false
-        };
-        if treat_as_one {
-            let ans = Confirm::new("Keep these incidents for review?")
-                .with_default(false)
-                .prompt()
-                .expect("Unexpected response");
-            if ans {
-                let poc_users = request_pocs(combined_users.clone())?;
-                incident_group
-                    .iter_mut()
-                    .for_each(|i| i.poc_users = Some(poc_users.clone()));
-                to_review.extend(incident_group.clone());
-            } else {
-                excluded.extend(incident_group.clone());
-            }
-        } else {
-            for incident in incident_group.iter_mut() {
-                incident.print(false)?;
-                let ans = Confirm::new("Keep this incident for review?")
-                    .with_default(false)
-                    .prompt()
-                    .expect("Unexpected response");
-                if ans {
-                    let poc_users = request_pocs(combined_users.clone())?;
-                    incident.poc_users = Some(poc_users.clone());
-                    to_review.push(incident.clone());
-                } else {
-                    excluded.push(incident.clone());
-                }
-            }
+    let filtered_incidents = filter_incidents_by_tags(
+        filter_incidents_for_review(incidents, "P2", options.require_no_channel),
+        &options.tags,
+    );
+    let (filtered_incidents, aged_out) =
+        filter_incidents_by_age(filtered_incidents, options.max_age, Utc::now());
+    if let Some(days) = options.max_age {
+        output.progress(format!(
+            "Excluded {} incidents created more than {} days ago",
+            aged_out, days
+        ));
+    }
+    let (filtered_incidents, quiet_incidents) =
+        filter_incidents_by_min_events(filtered_incidents, options.min_events);
+    if let Some(min_events) = options.min_events {
+        output.progress(format!(
+            "Excluded {} incidents with fewer than {} alerts",
+            quiet_incidents, min_events
+        ));
+    }
+    let filtered_incidents = if options.skip_with_postmortem {
+        let (kept, skipped) = filter_incidents_with_postmortem(&notion, filtered_incidents).await?;
+        output.progress(format!(
+            "Excluded {} incidents that already have a postmortem",
+            skipped
+        ));
+        kept
+    } else {
+        filtered_incidents
+    };
+    let mut snoozes = SnoozeList::load()?;
+    let (mut filtered_incidents, snoozed) = snoozes.apply(filtered_incidents, Utc::now());
+    if snoozed.still_snoozed > 0 || snoozed.resurfaced > 0 {
+        output.progress(format!(
+            "Left out {} snoozed incidents, brought back {} whose snooze is over",
+            snoozed.still_snoozed, snoozed.resurfaced
+        ));
+    }
+    annotate_last_activity(&slack, &mut filtered_incidents).await;
+    output.progress(format!(
+        "Reviewing {} recent incidents",
+        filtered_incidents.len()
+    ));
+    let mut group_map =
+        merge_groups_sharing_channel(group_by_similar_title(filtered_incidents, 0.9));
+    if options.audit {
+        // nothing is asked or saved, including the snooze list
+        output.outcome(audit_report(&notion, &group_map, options).await?);
+        return Ok(());
+    }
+    let overrides = match &options.overrides {
+        Some(path) => Some(Overrides::load(path)?),
+        None => None,
+    };
+    let poc_map = match &options.poc_map {
+        Some(path) => Some(load_poc_map(path)?),
+        None => None,
+    };
+    if let Some(map) = &poc_map {
+        let unresolved = unresolved_pocs(map, &combined_users);
+        if !unresolved.is_empty() {
+            output.progress(render_unresolved(map, &unresolved));
         }
     }
-    println!(
+    let decided = overrides
+        .as_ref()
+        .map(|o| o.apply(&mut group_map))
+        .unwrap_or_default();
+    if overrides.is_some() {
+        output.outcome(decided.render());
+    }
+    let prompter = InquirePrompter;
+    let (mut to_review, mut excluded, newly_snoozed) = select_incidents_for_review(
+        group_map,
+        &combined_users,
+        options.require_poc,
+        &prompter,
+        &output,
+    )?;
+    for kept in decided.kept {
+        let mut incident = kept.incident;
+        output.progress(format!(
+            "Kept by {}: {}",
+            kept.rule,
+            incident.summary(false)?
+        ));
+        incident.poc_users = Some(select_pocs(
+            &combined_users,
+            options.require_poc,
+            &prompter,
+            &output,
+        )?);
+        to_review.push(incident);
+    }
+    excluded.extend(decided.excluded.into_iter().map(|o| o.incident));
+    if let Some(map) = &poc_map {
+        let applied = apply_poc_map(map, &combined_users, &mut to_review);
+        output.progress(format!(
+            "Set the POCs of {} incidents from the POC map",
+            applied
+        ));
+    }
+    let publisher = LivePublisher {
+        slack: &slack,
+        notion: &notion,
+        source,
+    };
+    // before the message is built, so it mentions POCs found by the refresh
+    refresh_unmatched_pocs(&publisher, &prompter, &mut to_review).await?;
+    let until = Utc::now() + Duration::days(options.snooze_days as i64);
+    for incident in newly_snoozed.iter() {
+        snoozes.snooze(incident, until);
+    }
+    if !newly_snoozed.is_empty() {
+        output.outcome(format!(
+            "Snoozed until {}: {}",
+            until.format("%Y-%m-%d"),
+            newly_snoozed
+                .iter()
+                .map(|i| i.number.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    output.outcome(format!(
         "Incidents marked for review: {}",
         to_review
             .iter()
             .map(|i| i.number.to_string())
             .collect::<Vec<_>>()
             .join(", ")
+    ));
+
+    let frame = MessageFrame::from_env();
+    let today = Local::now().date_naive();
+    let message = build_routed_review_message(
+        options,
+        &TeamChannels::from_env()?,
+        group_mention.as_deref(),
+        &frame,
+        today,
+        &to_review,
+        &excluded,
     );
+    if let Some(dir) = &options.output_dir {
+        let path = ReviewExport::new(&to_review, &excluded).save(dir)?;
+        output.progress(format!("Saved the review to {}", path.display()));
+    }
+    if let Some(path) = &options.metrics_file {
+        ReviewMetrics {
+            finished_at: Utc::now(),
+            to_review: &to_review,
+            excluded: &excluded,
+            unmatched_people: count_unmatched_people(&combined_users),
+        }
+        .write(path)?;
+        output.progress(format!("Wrote the review metrics to {}", path.display()));
+    }
+    if options.format == ReviewFormat::Markdown {
+        output.progress("Here are the reviewed incidents as Markdown:");
+        output.outcome(render_markdown(today, &to_review, &excluded));
+    }
+    output.progress("Here is the message to send in the channel:");
+    output.outcome(message.preview());
+    if options.diff {
+        // merged incidents aren't inserted, so their pages wouldn't change
+        for incident in to_review.iter().filter(|i| i.merged_into.is_none()) {
+            output.outcome(notion.diff_incident(incident).await?);
+        }
+        return Ok(());
+    }
+    let checkpoint = InsertCheckpoint::load(&local_cache_dir(), &options.database().id)?;
+    finish_review(
+        &publisher,
+        &prompter,
+        options,
+        &message,
+        &to_review,
+        &checkpoint,
+        &snoozes,
+        &local_cache_dir(),
+        started_at,
+    )
+    .await
+}
 
-    let message = format!(
-        "
-Hello everyone and happy {}!
+/// Publish the review, and only once it is published save the snooze list and
+/// record the run that started at `started_at` in `cache_dir`. A declined run
+/// then neither hides its incidents from the next `--since-last-run` nor drops
+/// the snoozes that came due.
+#[allow(clippy::too_many_arguments)]
+async fn finish_review(
+    publisher: &impl ReviewPublisher,
+    prompter: &impl Prompter,
+    options: &ReviewOptions,
+    message: &ReviewMessage,
+    to_review: &[Incident],
+    checkpoint: &InsertCheckpoint,
+    snoozes: &SnoozeList,
+    cache_dir: &Path,
+    started_at: DateTime<Utc>,
+) -> Result<()> {
+    if publish_review(publisher, prompter, options, message, to_review, checkpoint).await? {
+        snoozes.save_to(cache_dir)?;
+        save_last_run(cache_dir, started_at)?;
+    }
+    Ok(())
+}
+
+/// The review message for the incidents, split by the channel of each owning
+/// team when team channels are configured.
+///
+/// Team channels are real channels, so they are only posted to when the review
+/// channel is the production one. Otherwise everything goes to the review channel.
+fn build_routed_review_message(
+    options: &ReviewOptions,
+    team_channels: &TeamChannels,
+    group_mention: Option<&str>,
+    frame: &MessageFrame,
+    today: NaiveDate,
+    to_review: &[Incident],
+    excluded: &[Incident],
+) -> ReviewMessage {
+    if team_channels.is_empty() || options.channel() != Target::Prod {
+        return build_review_message(options, group_mention, frame, today, to_review, excluded);
+    }
+    ReviewMessage::Routed(
+        team_channels
+            .route(to_review, excluded, options.channel().review_channel())
+            .into_iter()
+            .map(|(channel, (to_review, excluded))| {
+                let message = build_review_message(
+                    options,
+                    group_mention,
+                    frame,
+                    today,
+                    &to_review,
+                    &excluded,
+                );
+                (channel, message)
+            })
+            .collect(),
+    )
+}
+
+/// The review message for the incidents, framed and with the group mentioned.
+fn build_review_message(
+    options: &ReviewOptions,
+    group_mention: Option<&str>,
+    frame: &MessageFrame,
+    today: NaiveDate,
+    to_review: &[Incident],
+    excluded: &[Incident],
+) -> ReviewMessage {
+    if options.threaded {
+        ReviewMessage::Threaded {
+            root: prepend_mention(
+                group_mention,
+                frame.apply(
+                    format!("Incident review for {}", today.format("%Y-%m-%d")),
+                    today,
+                    to_review.len(),
+                ),
+            ),
+            replies: to_review
+                .iter()
+                .map(|i| i.render_line(options.line_template.as_deref()))
+                .collect(),
+        }
+    } else {
+        let greeting = options.greeting.line(&day_of_week());
+        let text = prepend_mention(
+            group_mention,
+            frame.apply(
+                render_review_message(
+                    greeting.as_deref(),
+                    options.line_template.as_deref(),
+                    to_review,
+                    excluded,
+                ),
+                today,
+                to_review.len(),
+            ),
+        );
+        if options.blocks {
+            let (header, footer) = frame.filled(today, to_review.len());
+            ReviewMessage::Blocks {
+                text,
+                blocks: render_review_blocks(
+                    group_mention,
+                    greeting.as_deref(),
+                    (header.as_deref(), footer.as_deref()),
+                    options.line_template.as_deref(),
+                    to_review,
+                    excluded,
+                ),
+            }
+        } else {
+            ReviewMessage::Single(text)
+        }
+    }
+}
+
+/// How many incidents have each priority, and how many have none.
+pub(super) fn priority_counts(incidents: &[Incident]) -> (BTreeMap<u8, usize>, usize) {
+    let mut counts: BTreeMap<u8, usize> = BTreeMap::new();
+    let mut unknown = 0;
+    for incident in incidents {
+        match incident.priority.as_ref().filter(|p| !p.name.is_empty()) {
+            Some(priority) => *counts.entry(priority.u8()).or_default() += 1,
+            None => unknown += 1,
+        }
+    }
+    (counts, unknown)
+}
+
+/// Summarize the priorities of a batch of incidents, e.g. "This batch: 2×P1, 5×P2".
+///
+/// Incidents without a priority are counted as "unknown", after the known priorities.
+pub(super) fn priority_histogram(incidents: &[Incident]) -> String {
+    let (counts, unknown) = priority_counts(incidents);
+    let mut parts: Vec<String> = counts
+        .into_iter()
+        .map(|(priority, count)| format!("{}×P{}", count, priority))
+        .collect();
+    if unknown > 0 {
+        parts.push(format!("{}×unknown", unknown));
+    }
+    format!("This batch: {}", parts.join(", "))
+}
 
-We have selected the following incidents for review:
+/// Render the Slack message announcing the incidents selected for review, with
+/// each incident's line rendered from `line_template` if there is one.
+pub(super) fn render_review_message(
+    greeting: Option<&str>,
+    line_template: Option<&str>,
+    to_review: &[Incident],
+    excluded: &[Incident],
+) -> String {
+    let histogram = if to_review.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n\n", priority_histogram(to_review))
+    };
+    let greeting = greeting.map(|g| format!("{}\n\n", g)).unwrap_or_default();
+    format!(
+        "
+{}{}We have selected the following incidents for review:
 {}
     
 and the following incidents have been excluded from review:
@@ -246,114 +969,1282 @@ and the following incidents have been excluded from review:
 
 These are only *newly scheduled* incidents. All incidents scheduled for review can be found in Notion <https://www.notion.so/mystenlabs/Incident-Review-Selection-c96bb9ba36c24a59af230162042d3dd4?pvs=4|here>.
 Please comment in the thread to request an adjustment to the list.",
-        day_of_week(),
+        histogram,
+        greeting,
         to_review
             .iter()
-            .map(Incident::short_fmt)
+            .map(|i| i.render_line(line_template))
             .collect::<Vec<_>>()
             .join("\n"),
         excluded
             .iter()
-            .map(Incident::short_fmt)
+            .map(|i| i.render_line(line_template))
             .collect::<Vec<_>>()
             .join("\n")
-    );
-    println!(
-        "Here is the message to send in the channel: 
-    {}
-    ",
-        message
-    );
-    let slack_channel = if *DEBUG_MODE {
-        "test-notifications"
-    } else {
-        "incident-postmortems"
-    };
-    let send_message = Confirm::new(&format!(
-        "Send this message to the #{} channel?",
-        slack_channel
-    ))
-    .with_default(false)
-    .prompt()
-    .expect("Unexpected response");
-    if send_message {
-        slack.send_message(slack_channel, &message).await?;
-        debug!("Message sent to #{}", slack_channel);
-    }
-    #[allow(clippy::unnecessary_to_owned)]
-    let insert_into_db = Confirm::new(&format!(
-        "Insert {} incidents into {:?} Notion database ({:?}) for review?",
-        to_review.len(),
-        INCIDENT_DB_NAME.to_string(),
-        INCIDENT_DB_ID.to_string()
-    ))
-    .with_default(false)
-    .prompt()
-    .expect("Unexpected response");
-    if insert_into_db {
-        for incident in to_review.iter() {
-            debug!("Inserting incident into Notion: {}", incident.number);
-            notion.insert_incident(incident.clone()).await?;
-        }
-    }
-    Ok(())
+    )
 }
 
-fn group_by_similar_title(
-    incidents: Vec<Incident>,
-    threshold: f64,
-) -> HashMap<String, Vec<Incident>> {
-    if !(0.0..=1.0).contains(&threshold) {
-        panic!("Threshold must be between 0.0 and 1.0");
+/// Render the review message as Block Kit blocks: each incident up for review is
+/// its own section, with a button opening it when it has a url.
+pub(super) fn render_review_blocks(
+    mention: Option<&str>,
+    greeting: Option<&str>,
+    (header, footer): (Option<&str>, Option<&str>),
+    line_template: Option<&str>,
+    to_review: &[Incident],
+    excluded: &[Incident],
+) -> serde_json::Value {
+    let mut blocks = Blocks::new();
+    let opening: Vec<&str> = [mention, header, greeting].into_iter().flatten().collect();
+    if !opening.is_empty() {
+        blocks = blocks.section(&opening.join("\n\n"));
     }
+    blocks = blocks.section("We have selected the following incidents for review:");
+    if !to_review.is_empty() {
+        blocks = blocks.context(&priority_histogram(to_review));
+    }
+    for incident in to_review {
+        let line = incident.render_line(line_template);
+        blocks = if incident.html_url.is_empty() {
+            blocks.section(&line)
+        } else {
+            blocks.section_with_link(&line, "Open", &incident.html_url)
+        };
+    }
+    blocks = blocks.divider();
+    if !excluded.is_empty() {
+        let lines: Vec<String> = excluded
+            .iter()
+            .map(|i| i.render_line(line_template))
+            .collect();
+        blocks = blocks.section(&format!(
+            "and the following incidents have been excluded from review:\n{}",
+            lines.join("\n")
+        ));
+    }
+    blocks = blocks.context("These are only *newly scheduled* incidents. All incidents scheduled for review can be found in Notion <https://www.notion.so/mystenlabs/Incident-Review-Selection-c96bb9ba36c24a59af230162042d3dd4?pvs=4|here>. Please comment in the thread to request an adjustment to the list.");
+    if let Some(footer) = footer {
+        blocks = blocks.section(footer);
+    }
+    blocks.build()
+}
 
-    let mut groups: HashMap<String, Vec<Incident>> = HashMap::new();
-
-    for incident in incidents {
-        // Try to find an existing title that is similar enough
-        let mut found = false;
-        for (existing_title, group) in groups.iter_mut() {
-            if normalized_damerau_levenshtein(
-                &incident.title.chars().take(20).collect::<String>(),
-                &existing_title.chars().take(20).collect::<String>(),
-            ) >= threshold
-            {
-                // If similar, add it to this group
-                group.push(incident.clone());
-                found = true;
-                break;
-            }
+/// Describe the incidents up for review as they stand: how they group, their
+/// channels and whether they are already scheduled in Notion.
+///
+/// Only reads through `lookup`, so an audit can't change anything.
+async fn audit_report(
+    lookup: &impl IncidentLookup,
+    group_map: &BTreeMap<String, Vec<Incident>>,
+    options: &ReviewOptions,
+) -> Result<String> {
+    // list the groups by their first incident rather than by title
+    let mut groups: Vec<&Vec<Incident>> = group_map.values().collect();
+    groups.sort_by_key(|group| group.iter().map(|i| i.number).min());
+    let total: usize = groups.iter().map(|group| group.len()).sum();
+    let mut lines = vec![format!(
+        "Audit of {} incidents up for review (Notion {:?} database, #{} channel):",
+        total,
+        options.database().name,
+        options.channel().review_channel()
+    )];
+    let mut scheduled = 0;
+    let mut without_channel = 0;
+    for group in groups {
+        if group.len() > 1 {
+            lines.push(format!(
+                "Similar titles, reviewed together if the operator agrees: {}",
+                group
+                    .iter()
+                    .map(|i| i.number.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
         }
-
-        // If no similar title found, add a new group
-        if !found {
-            groups
-                .entry(incident.title.clone())
-                .or_default()
-                .push(incident);
+        for incident in group {
+            let in_notion = lookup.is_scheduled(incident).await?;
+            scheduled += in_notion as usize;
+            without_channel += incident.slack_channel.is_none() as usize;
+            lines.push(format!(
+                "• {} [{}] {}, {}, {}",
+                incident.number,
+                incident
+                    .priority
+                    .as_ref()
+                    .map_or("no priority", |p| p.name.as_str()),
+                incident.title,
+                incident
+                    .slack_channel
+                    .as_ref()
+                    .map_or("no channel".to_owned(), |c| format!("#{}", c.name)),
+                if in_notion {
+                    "already in Notion"
+                } else {
+                    "not in Notion"
+                }
+            ));
         }
     }
-
-    debug!(
-        "map: {:#?}",
-        groups.iter().map(|(k, v)| (k, v.len())).collect::<Vec<_>>()
-    );
-    groups
+    lines.push(format!(
+        "{} of {} already in Notion, {} without a channel",
+        scheduled, total, without_channel
+    ));
+    Ok(lines.join("\n"))
 }
 
-pub fn get_channel_for<'a>(incident: &Incident, slack: &'a Slack) -> Option<&'a Channel> {
-    slack
-        .channels
-        .iter()
-        .find(|c| c.name.contains(&incident.number.to_string()))
+/// Put `mention` at the very top of the review message, if there is one.
+fn prepend_mention(mention: Option<&str>, message: String) -> String {
+    match mention {
+        Some(mention) => format!("{}\n{}", mention, message),
+        None => message,
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_group_by_similar_title() {
+/// Ask the operator which incidents to keep for review, returning the incidents to
+/// review along with the excluded and the snoozed ones.
+fn select_incidents_for_review(
+    mut group_map: BTreeMap<String, Vec<Incident>>,
+    combined_users: &[User],
+    require_poc: bool,
+    prompter: &impl Prompter,
+    output: &ReviewOutput<impl Write, impl Write>,
+) -> Result<(Vec<Incident>, Vec<Incident>, Vec<Incident>)> {
+    let mut to_review = vec![];
+    let mut excluded = vec![];
+    let mut snoozed = vec![];
+    for (title, incident_group) in group_map.iter_mut() {
+        let treat_as_one = if incident_group.len() > 1 {
+            output.progress(format!(
+                "There are {} incidents with a title similar to this: {}",
+                &incident_group.len(),
+                title
+            ));
+            output.progress("All incidents with a similar title:");
+            for i in incident_group.iter() {
+                output.progress(i.summary(false)?);
+            }
+            prompter.confirm("Treat them as one?", true)?
+        } else {
+            false
+        };
+        if treat_as_one {
+            match prompter.choose_review("Keep these incidents for review?")? {
+                ReviewChoice::Keep => {
+                    let poc_users = select_pocs(combined_users, require_poc, prompter, output)?;
+                    let canonical =
+                        incident_group[prompter.select_canonical(incident_group)?].number;
+                    incident_group.iter_mut().for_each(|i| {
+                        i.poc_users = Some(poc_users.clone());
+                        i.merged_into = (i.number != canonical).then_some(canonical);
+                    });
+                    to_review.extend(incident_group.clone());
+                }
+                ReviewChoice::Exclude => excluded.extend(incident_group.clone()),
+                ReviewChoice::Snooze => snoozed.extend(incident_group.clone()),
+            }
+        } else {
+            for incident in incident_group.iter_mut() {
+                output.progress(incident.summary(false)?);
+                match prompter.choose_review("Keep this incident for review?")? {
+                    ReviewChoice::Keep => {
+                        let poc_users = select_pocs(combined_users, require_poc, prompter, output)?;
+                        incident.poc_users = Some(poc_users.clone());
+                        to_review.push(incident.clone());
+                    }
+                    ReviewChoice::Exclude => excluded.push(incident.clone()),
+                    ReviewChoice::Snooze => snoozed.push(incident.clone()),
+                }
+            }
+        }
+    }
+    Ok((to_review, excluded, snoozed))
+}
+
+/// Ask for the POCs of a kept incident, warning and asking again when none are selected.
+///
+/// Without `require_poc` the operator may confirm that the incident has no POCs.
+fn select_pocs(
+    combined_users: &[User],
+    require_poc: bool,
+    prompter: &impl Prompter,
+    output: &ReviewOutput<impl Write, impl Write>,
+) -> Result<Vec<User>> {
+    loop {
+        let poc_users = prompter.select_pocs(combined_users.to_vec())?;
+        if !poc_users.is_empty() {
+            return Ok(poc_users);
+        }
+        output.progress("Warning: no POCs were selected for this incident");
+        if !require_poc && prompter.confirm("Keep it without any POCs?", false)? {
+            return Ok(poc_users);
+        }
+    }
+}
+
+/// What a review posts to Slack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum ReviewMessage {
+    /// One message listing every incident
+    Single(String),
+    /// A root message with each selected incident as a reply in its thread
+    Threaded { root: String, replies: Vec<String> },
+    /// A message for each owning team's channel, with the incidents it owns
+    Routed(Vec<(String, ReviewMessage)>),
+    /// A Block Kit message, with `text` shown in notifications
+    Blocks {
+        text: String,
+        blocks: serde_json::Value,
+    },
+}
+
+impl ReviewMessage {
+    /// The message as it reads in the channel, replies after the root.
+    fn preview(&self) -> String {
+        match self {
+            ReviewMessage::Single(text) | ReviewMessage::Blocks { text, .. } => text.clone(),
+            ReviewMessage::Threaded { root, replies } => {
+                let mut lines = vec![root.clone()];
+                lines.extend(replies.iter().map(|r| format!("  ↳ {}", r)));
+                lines.join("\n")
+            }
+            ReviewMessage::Routed(routes) => routes
+                .iter()
+                .map(|(channel, message)| format!("#{}:\n{}", channel, message.preview()))
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        }
+    }
+
+    /// The channels the message is posted to and what each gets, `default_channel`
+    /// unless it's routed to the owning teams.
+    fn destinations<'a>(&'a self, default_channel: &'a str) -> Vec<(&'a str, &'a ReviewMessage)> {
+        match self {
+            ReviewMessage::Routed(routes) => routes
+                .iter()
+                .map(|(channel, message)| (channel.as_str(), message))
+                .collect(),
+            _ => vec![(default_channel, self)],
+        }
+    }
+}
+
+/// Post the review message to each of its channels, returning the message posted
+/// in each, the root if it's threaded.
+async fn send_review_message(
+    publisher: &impl ReviewPublisher,
+    slack_channel: &str,
+    message: &ReviewMessage,
+) -> Result<Vec<PostedMessage>> {
+    let mut posted = vec![];
+    for (channel, message) in message.destinations(slack_channel) {
+        posted.push(send_to_channel(publisher, channel, message).await?);
+    }
+    Ok(posted)
+}
+
+/// Show the review message in each of its channels to only the given user.
+///
+/// Ephemeral messages can't have replies, so a threaded message is previewed as
+/// it reads, replies after the root.
+async fn preview_review_message(
+    publisher: &impl ReviewPublisher,
+    slack_channel: &str,
+    message: &ReviewMessage,
+    user_id: &str,
+) -> Result<()> {
+    for (channel, message) in message.destinations(slack_channel) {
+        publisher
+            .send_ephemeral(channel, user_id, &message.preview())
+            .await?;
+        info!(
+            "Previewed the review message to {} in #{}",
+            user_id, channel
+        );
+    }
+    Ok(())
+}
+
+/// Post a message to one Slack channel, as one message or as a thread, returning
+/// the message or the thread's root.
+async fn send_to_channel(
+    publisher: &impl ReviewPublisher,
+    slack_channel: &str,
+    message: &ReviewMessage,
+) -> Result<PostedMessage> {
+    match message {
+        ReviewMessage::Routed(_) => Err(anyhow!("a routed review message can't be nested")),
+        ReviewMessage::Single(text) => {
+            let posted = publisher.send_message(slack_channel, text, None).await?;
+            debug!("Message sent to #{}", slack_channel);
+            Ok(posted)
+        }
+        ReviewMessage::Blocks { text, blocks } => {
+            let posted = publisher.send_blocks(slack_channel, text, blocks).await?;
+            debug!("Block message sent to #{}", slack_channel);
+            Ok(posted)
+        }
+        ReviewMessage::Threaded { root, replies } => {
+            let root = publisher.send_message(slack_channel, root, None).await?;
+            let mut reply_ts = Vec::with_capacity(replies.len());
+            for reply in replies {
+                reply_ts.push(
+                    publisher
+                        .send_message(&root.channel, reply, Some(&root.ts))
+                        .await?
+                        .ts,
+                );
+            }
+            info!(
+                "Posted the review thread {} in #{} with replies {}",
+                root.ts,
+                slack_channel,
+                reply_ts.join(", ")
+            );
+            Ok(root)
+        }
+    }
+}
+
+/// The direct message for each POC of the selected incidents, by Slack user id,
+/// listing the incidents they're a POC of. POCs without a Slack user are skipped
+/// with a warning.
+fn pocs_to_dm(to_review: &[Incident], line_template: Option<&str>) -> BTreeMap<String, String> {
+    let mut owned: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for incident in to_review {
+        for user in incident.poc_users.iter().flatten() {
+            match user.slack_user.as_ref().filter(|u| !u.id.is_empty()) {
+                Some(slack_user) => owned
+                    .entry(slack_user.id.clone())
+                    .or_default()
+                    .push(incident.render_line(line_template)),
+                None => warn!(
+                    "Not sending {} their incidents, they have no Slack user",
+                    user.mention()
+                ),
+            }
+        }
+    }
+    owned
+        .into_iter()
+        .map(|(user_id, lines)| {
+            let text = format!(
+                "You're a POC of these incidents selected for review:\n{}",
+                lines.join("\n")
+            );
+            (user_id, text)
+        })
+        .collect()
+}
+
+/// Fail with every POC of the selected incidents that lacks a Slack or Notion user,
+/// or a Notion user without an id, so none of them is silently left out.
+fn check_pocs_linked(to_review: &[Incident]) -> Result<()> {
+    let offenders: Vec<String> = to_review
+        .iter()
+        .flat_map(|incident| {
+            incident.poc_users.iter().flatten().filter_map(move |user| {
+                let missing = user.missing_links();
+                (!missing.is_empty()).then(|| {
+                    format!(
+                        "• {}: {} has no {} user",
+                        incident.number,
+                        user,
+                        missing.join(" or ")
+                    )
+                })
+            })
+        })
+        .collect();
+    if offenders.is_empty() {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "{} POCs can't be both mentioned in Slack and set in Notion (--strict-pocs), nothing was written:\n{}",
+        offenders.len(),
+        offenders.join("\n")
+    ))
+}
+
+/// The channels of the reviewed incidents that are resolved, once each, in the
+/// order of the incidents. Unresolved incidents keep their channels.
+fn channels_to_archive(to_review: &[Incident]) -> Vec<&Channel> {
+    let mut channels: Vec<&Channel> = vec![];
+    for channel in to_review
+        .iter()
+        .filter(|i| i.is_resolved())
+        .filter_map(|i| i.slack_channel.as_ref())
+    {
+        if !channels.iter().any(|c| c.id == channel.id) {
+            channels.push(channel);
+        }
+    }
+    channels
+}
+
+/// Archive each channel, reporting which were archived and which failed rather
+/// than stopping at the first failure.
+/// Send each POC the direct message listing their incidents.
+///
+/// The review message is already posted by now, so a POC who can't be reached is
+/// skipped with a warning rather than stopping the rest of the publish.
+async fn dm_pocs(publisher: &impl ReviewPublisher, to_dm: &BTreeMap<String, String>) {
+    let mut failed = 0;
+    for (user_id, text) in to_dm {
+        let sent = match publisher.open_dm(user_id).await {
+            Ok(dm) => publisher.send_message(&dm, text, None).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        match sent {
+            Ok(()) => debug!("Sent POC {} their incidents", user_id),
+            Err(e) => {
+                warn!("Couldn't send POC {} their incidents: {:#}", user_id, e);
+                failed += 1;
+            }
+        }
+    }
+    if !to_dm.is_empty() {
+        info!(
+            "Sent {} of {} POCs their incidents",
+            to_dm.len() - failed,
+            to_dm.len()
+        );
+    }
+}
+
+async fn archive_channels(publisher: &impl ReviewPublisher, channels: &[&Channel]) -> Result<()> {
+    let mut failed = vec![];
+    for channel in channels {
+        match publisher.archive_channel(&channel.id).await {
+            Ok(()) => info!("Archived #{}", channel.name),
+            Err(e) => {
+                warn!("Couldn't archive #{}: {:#}", channel.name, e);
+                failed.push(format!("#{}", channel.name));
+            }
+        }
+    }
+    info!(
+        "Archived {} of {} channels",
+        channels.len() - failed.len(),
+        channels.len()
+    );
+    if !failed.is_empty() {
+        return Err(anyhow!("couldn't archive {}", failed.join(", ")));
+    }
+    Ok(())
+}
+
+/// The confirmation listing every write a publish is about to make.
+fn pending_writes_summary(
+    options: &ReviewOptions,
+    channels: &[&str],
+    to_dm: usize,
+    to_insert: usize,
+    to_mark: usize,
+    to_archive: usize,
+) -> String {
+    let mut lines = vec!["About to:".to_owned()];
+    if !channels.is_empty() {
+        lines.push(format!(
+            "• post the review message to {}",
+            channels
+                .iter()
+                .map(|c| format!("#{}", c))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if to_dm > 0 {
+        lines.push(format!(
+            "• send {} POCs a direct message listing their incidents",
+            to_dm
+        ));
+    }
+    if to_insert > 0 {
+        lines.push(format!(
+            "• insert {} incidents into the {:?} Notion database ({})",
+            to_insert,
+            options.database().name,
+            options.database().id
+        ));
+    }
+    if to_mark > 0 {
+        lines.push(format!(
+            "• mark {} incidents as reviewed in the incident source",
+            to_mark
+        ));
+    }
+    if to_archive > 0 {
+        lines.push(format!(
+            "• archive {} Slack channels of resolved incidents",
+            to_archive
+        ));
+    }
+    lines.push("Proceed?".to_owned());
+    lines.join("\n")
+}
+
+/// Send the review message to Slack and insert the reviewed incidents into Notion,
+/// after one confirmation listing everything that will be written. Whatever the
+/// options suppress is left out.
+///
+/// Returns whether the review was published, which it isn't when there is nothing
+/// to write or the operator declines.
+async fn publish_review(
+    publisher: &impl ReviewPublisher,
+    prompter: &impl Prompter,
+    options: &ReviewOptions,
+    message: &ReviewMessage,
+    to_review: &[Incident],
+    checkpoint: &InsertCheckpoint,
+) -> Result<bool> {
+    if options.strict_pocs {
+        check_pocs_linked(to_review)?;
+    }
+    let slack_channel = options.channel().review_channel();
+    if options.no_send {
+        debug!("Not sending message to #{} (--no-send)", slack_channel);
+    }
+    let to_insert: Vec<&Incident> = if options.no_insert {
+        debug!("Not inserting incidents into Notion (--no-insert)");
+        vec![]
+    } else {
+        // merged incidents are reviewed as part of their canonical incident
+        let (merged, to_insert): (Vec<&Incident>, Vec<&Incident>) =
+            to_review.iter().partition(|i| i.merged_into.is_some());
+        for incident in merged {
+            debug!(
+                "Not inserting incident {}, it is merged into {}",
+                incident.number,
+                incident.merged_into.unwrap_or_default()
+            );
+        }
+        // resume an insert run that died partway without inserting anything twice
+        let (inserted, to_insert): (Vec<&Incident>, Vec<&Incident>) = to_insert
+            .into_iter()
+            .partition(|i| checkpoint.contains(i.number));
+        if !inserted.is_empty() {
+            info!(
+                "Skipping {} incidents inserted by an earlier run: {}",
+                inserted.len(),
+                inserted
+                    .iter()
+                    .map(|i| i.number.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        to_insert
+    };
+    let to_mark = if options.mark_reviewed && !options.no_insert {
+        to_review
+    } else {
+        &[]
+    };
+    let to_archive = if options.archive_channels {
+        channels_to_archive(to_review)
+    } else {
+        vec![]
+    };
+    let send = !options.no_send;
+    let channels: Vec<&str> = if send {
+        message
+            .destinations(slack_channel)
+            .into_iter()
+            .map(|(channel, _)| channel)
+            .collect()
+    } else {
+        vec![]
+    };
+    let to_dm = if send && options.dm_pocs {
+        pocs_to_dm(to_review, options.line_template.as_deref())
+    } else {
+        BTreeMap::new()
+    };
+    if !send && to_insert.is_empty() && to_mark.is_empty() && to_archive.is_empty() {
+        return Ok(false);
+    }
+    if let Some(user_id) = options.preview_to.as_deref().filter(|_| send) {
+        if prompter.confirm(
+            "Preview the review message in Slack, visible only to you, first?",
+            true,
+        )? {
+            preview_review_message(publisher, slack_channel, message, user_id).await?;
+        }
+    }
+    if !prompter.confirm(
+        &pending_writes_summary(
+            options,
+            &channels,
+            to_dm.len(),
+            to_insert.len(),
+            to_mark.len(),
+            to_archive.len(),
+        ),
+        false,
+    )? {
+        debug!("Nothing was written");
+        return Ok(false);
+    }
+
+    let posted = if send {
+        send_review_message(publisher, slack_channel, message).await?
+    } else {
+        vec![]
+    };
+    dm_pocs(publisher, &to_dm).await;
+    if !to_insert.is_empty() {
+        // one failed insert doesn't stop the rest, but nothing is marked reviewed
+        // until every incident is in Notion
+        let failed = Mutex::new(vec![]);
+        let pages = Mutex::new(vec![]);
+        try_for_each_bounded(to_insert.iter(), concurrency(), |incident| {
+            let (failed, pages) = (&failed, &pages);
+            async move {
+                debug!("Inserting incident into Notion: {}", incident.number);
+                match publisher.insert_incident(incident).await {
+                    Ok(url) => {
+                        pages.lock().unwrap().push((incident.number, url));
+                        checkpoint.record(incident.number)
+                    }
+                    Err(e) => {
+                        warn!("Couldn't insert incident {}: {:#}", incident.number, e);
+                        failed.lock().unwrap().push(incident.number);
+                        Ok(())
+                    }
+                }
+            }
+        })
+        .await?;
+        let mut pages = pages.into_inner().unwrap();
+        if !pages.is_empty() {
+            pages.sort();
+            info!("{}", render_inserted_pages(&pages));
+        }
+        let mut failed = failed.into_inner().unwrap();
+        if !failed.is_empty() {
+            failed.sort();
+            return Err(anyhow!(
+                "{} of {} incidents couldn't be inserted into Notion: {}",
+                failed.len(),
+                to_insert.len(),
+                failed
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+    if !options.no_insert {
+        // the batch is complete, so a later run shouldn't skip any of it
+        checkpoint.clear()?;
+    }
+    try_for_each_bounded(to_mark, concurrency(), |incident| {
+        publisher.mark_reviewed(incident)
+    })
+    .await?;
+    archive_channels(publisher, &to_archive).await?;
+    if options.react_when_final {
+        react_final(publisher, &posted).await;
+    }
+    Ok(true)
+}
+
+/// List each inserted incident with a link to its Notion page.
+fn render_inserted_pages(pages: &[(u64, String)]) -> String {
+    let mut lines = vec![format!("Inserted {} incidents into Notion:", pages.len())];
+    lines.extend(
+        pages
+            .iter()
+            .map(|(number, url)| format!("• {}: {}", NUMBER_FORMAT.format(*number), url)),
+    );
+    lines.join("\n")
+}
+
+/// The reaction added to the review message once everything is written.
+const FINAL_REACTION: &str = "white_check_mark";
+
+/// React to each posted review message to show the selection is final. Everything
+/// is written by then, so a reaction that fails is only a warning.
+async fn react_final(publisher: &impl ReviewPublisher, posted: &[PostedMessage]) {
+    for message in posted {
+        if let Err(e) = publisher
+            .add_reaction(&message.channel, &message.ts, FINAL_REACTION)
+            .await
+        {
+            warn!(
+                "Couldn't mark the review message in {} as final: {:#}",
+                message.channel, e
+            );
+        }
+    }
+}
+
+/// Offer to fetch the Slack users again when POCs of the incidents have no Slack
+/// user, such as someone who joined since the users were cached, so the review
+/// message can mention them.
+async fn refresh_unmatched_pocs(
+    publisher: &impl ReviewPublisher,
+    prompter: &impl Prompter,
+    incidents: &mut [Incident],
+) -> Result<()> {
+    let unmatched = count_unmatched_pocs(incidents, &[]);
+    if unmatched > 0
+        && prompter.confirm(
+            &format!(
+                "{} POCs couldn't be matched to a Slack user. Refresh the Slack users and retry?",
+                unmatched
+            ),
+            true,
+        )?
+    {
+        let slack_users = publisher.refresh_slack_users().await?;
+        let unmatched = count_unmatched_pocs(incidents, &slack_users);
+        if unmatched > 0 {
+            info!(
+                "{} POCs still couldn't be matched to a Slack user",
+                unmatched
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Try to match the POCs of `incidents` that have no Slack user against
+/// `slack_users`, returning how many remain unmatched.
+fn count_unmatched_pocs(incidents: &mut [Incident], slack_users: &[SlackUser]) -> usize {
+    incidents
+        .iter_mut()
+        .flat_map(|i| i.poc_users.iter_mut().flatten())
+        .map(|user| user.match_slack_user(slack_users))
+        .filter(|matched| !matched)
+        .count()
+}
+
+/// Group the incidents whose titles start alike, keyed by the title of the first
+/// incident of each group. The groups are ordered by that title, so every run
+/// over the same incidents asks about them in the same order.
+fn group_by_similar_title(
+    incidents: Vec<Incident>,
+    threshold: f64,
+) -> BTreeMap<String, Vec<Incident>> {
+    if !(0.0..=1.0).contains(&threshold) {
+        panic!("Threshold must be between 0.0 and 1.0");
+    }
+
+    let mut groups: BTreeMap<String, Vec<Incident>> = BTreeMap::new();
+
+    for incident in incidents {
+        // Try to find an existing title that is similar enough
+        let mut found = false;
+        for (existing_title, group) in groups.iter_mut() {
+            if normalized_damerau_levenshtein(
+                &incident.title.chars().take(20).collect::<String>(),
+                &existing_title.chars().take(20).collect::<String>(),
+            ) >= threshold
+            {
+                // If similar, add it to this group
+                group.push(incident.clone());
+                found = true;
+                break;
+            }
+        }
+
+        // If no similar title found, add a new group
+        if !found {
+            groups
+                .entry(incident.title.clone())
+                .or_default()
+                .push(incident);
+        }
+    }
+
+    debug!(
+        "map: {:#?}",
+        groups.iter().map(|(k, v)| (k, v.len())).collect::<Vec<_>>()
+    );
+    groups
+}
+
+/// Merge the groups whose incidents share a Slack channel, since those are almost
+/// certainly the same event whatever their titles say.
+///
+/// A merged group keeps the alphabetically first of its titles.
+fn merge_groups_sharing_channel(
+    groups: BTreeMap<String, Vec<Incident>>,
+) -> BTreeMap<String, Vec<Incident>> {
+    let channel_ids = |group: &[Incident]| -> Vec<String> {
+        group
+            .iter()
+            .filter_map(|i| i.slack_channel.as_ref().map(|c| c.id.clone()))
+            .collect()
+    };
+    let mut merged: Vec<(String, Vec<Incident>, Vec<String>)> = vec![];
+    // in title order, so the first title of a merged group is the one kept
+    for (title, group) in groups {
+        let channels = channel_ids(&group);
+        let mut entry = (title, group, channels);
+        // a group can share channels with several merged groups, joining them all
+        while let Some(index) = merged
+            .iter()
+            .position(|(_, _, ids)| ids.iter().any(|id| entry.2.contains(id)))
+        {
+            let (title, group, ids) = merged.remove(index);
+            debug!(
+                "Merging incidents titled {:?} and {:?}, they share a channel",
+                title, entry.0
+            );
+            entry.0 = entry.0.min(title);
+            entry.1.splice(0..0, group);
+            entry.2.extend(ids);
+        }
+        merged.push(entry);
+    }
+    merged
+        .into_iter()
+        .map(|(title, group, _)| (title, group))
+        .collect()
+}
+
+/// How well a channel name matches an incident number; higher is better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ChannelScore {
+    /// The number appears on its own somewhere in the name, e.g. `db-outage-42`
+    Mentioned,
+    /// An incident channel renamed to describe it, e.g. `incident-42-db-outage`
+    Renamed,
+    /// The channel created for the incident, `incident-42`
+    Exact,
+}
+
+/// Score `name` as a channel for incident `number`, or `None` if it isn't one.
+///
+/// The number has to appear on its own, so incident 42 doesn't match
+/// `incident-420` or `release-4242`. With a prefixed `format` the channel may
+/// also be named after the formatted number, e.g. `inc-0042`.
+fn channel_score(format: &NumberFormat, number: u64, name: &str) -> Option<ChannelScore> {
+    let bare = number.to_string();
+    let padded = format.digits(number);
+    let formatted = format.format(number).to_lowercase();
+    let mut exact = vec![
+        format!("incident-{}", bare),
+        format!("incident-{}", formatted),
+    ];
+    if !format.prefix.is_empty() {
+        exact.push(formatted);
+    }
+    if exact.iter().any(|e| name == e) {
+        Some(ChannelScore::Exact)
+    } else if exact.iter().any(|e| name.starts_with(&format!("{}-", e))) {
+        Some(ChannelScore::Renamed)
+    } else if name
+        .split(|c: char| !c.is_ascii_digit())
+        .any(|part| part == bare || part == padded)
+    {
+        Some(ChannelScore::Mentioned)
+    } else {
+        None
+    }
+}
+
+/// The channels that could belong to an incident.
+#[derive(Debug)]
+pub enum ChannelMatch<'a> {
+    None,
+    Unique(&'a Channel),
+    /// Several channels match equally well, sorted by name
+    Ambiguous(Vec<&'a Channel>),
+}
+
+/// Find the channel for an incident: the best scoring match wins, and a tie
+/// for the best score is ambiguous.
+pub fn get_channel_for<'a>(incident: &Incident, slack: &'a Slack) -> ChannelMatch<'a> {
+    let scored: Vec<(ChannelScore, &Channel)> = slack
+        .channels
+        .iter()
+        .filter_map(|c| {
+            channel_score(&NUMBER_FORMAT, incident.number, &c.name).map(|score| (score, c))
+        })
+        .collect();
+    let Some(best) = scored.iter().map(|(score, _)| *score).max() else {
+        return ChannelMatch::None;
+    };
+    let mut candidates: Vec<&Channel> = scored
+        .into_iter()
+        .filter(|(score, _)| *score == best)
+        .map(|(_, c)| c)
+        .collect();
+    if candidates.len() == 1 {
+        ChannelMatch::Unique(candidates[0])
+    } else {
+        candidates.sort_by(|a, b| a.name.cmp(&b.name));
+        ChannelMatch::Ambiguous(candidates)
+    }
+}
+
+/// Pick the channel for an incident, asking the operator to choose when the
+/// match is ambiguous. Without a prompter an ambiguous match is left unset.
+pub fn resolve_channel_for(
+    incident: &Incident,
+    slack: &Slack,
+    prompter: Option<&impl Prompter>,
+) -> Result<Option<Channel>> {
+    Ok(match get_channel_for(incident, slack) {
+        ChannelMatch::None => None,
+        ChannelMatch::Unique(channel) => Some(channel.clone()),
+        ChannelMatch::Ambiguous(candidates) => match prompter {
+            Some(prompter) => prompter
+                .select_channel(incident, &candidates)?
+                .map(|index| candidates[index].clone()),
+            None => {
+                warn!(
+                    "Incident {} matches several channels, leaving it without one: {}",
+                    incident.number,
+                    candidates
+                        .iter()
+                        .map(|c| format!("#{}", c.name))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                None
+            }
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::incidents::notion::{NotionPerson, NotionPersonDetails};
+    use crate::cli::incidents::pd::Priority;
+    use crate::cli::slack::Profile;
+    use std::cell::RefCell;
+
+    /// Answers every confirmation with `answer` (keeping or excluding incidents
+    /// likewise unless `snooze` is set), selects the queued `pocs` in turn
+    /// (none once they run out) and picks the incident at index `canonical` of a
+    /// group as the canonical one.
+    struct FixedPrompter {
+        answer: bool,
+        /// Snooze every incident instead of keeping or excluding it
+        snooze: bool,
+        canonical: usize,
+        pocs: RefCell<Vec<Vec<User>>>,
+        questions: RefCell<Vec<String>>,
+    }
+
+    impl FixedPrompter {
+        fn new(answer: bool) -> Self {
+            Self {
+                answer,
+                snooze: false,
+                canonical: 0,
+                pocs: RefCell::new(vec![]),
+                questions: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl Prompter for FixedPrompter {
+        fn confirm(&self, message: &str, _default: bool) -> Result<bool> {
+            self.questions.borrow_mut().push(message.to_owned());
+            Ok(self.answer)
+        }
+
+        fn choose_review(&self, message: &str) -> Result<ReviewChoice> {
+            self.questions.borrow_mut().push(message.to_owned());
+            Ok(if self.snooze {
+                ReviewChoice::Snooze
+            } else if self.answer {
+                ReviewChoice::Keep
+            } else {
+                ReviewChoice::Exclude
+            })
+        }
+
+        fn select_pocs(&self, _users: Vec<User>) -> Result<Vec<User>> {
+            self.questions.borrow_mut().push("select pocs".to_owned());
+            let mut pocs = self.pocs.borrow_mut();
+            Ok(if pocs.is_empty() {
+                vec![]
+            } else {
+                pocs.remove(0)
+            })
+        }
+
+        fn select_canonical(&self, _incidents: &[Incident]) -> Result<usize> {
+            Ok(self.canonical)
+        }
+
+        fn select_channel(
+            &self,
+            _incident: &Incident,
+            _channels: &[&Channel],
+        ) -> Result<Option<usize>> {
+            self.questions
+                .borrow_mut()
+                .push("select channel".to_owned());
+            Ok(Some(self.canonical))
+        }
+
+        fn select_database(&self, _names: &[&str]) -> Result<usize> {
+            self.questions
+                .borrow_mut()
+                .push("select database".to_owned());
+            Ok(self.canonical)
+        }
+    }
+
+    /// Records the side effects instead of performing them.
+    #[derive(Default)]
+    struct RecordingPublisher {
+        sent: RefCell<Vec<(String, String)>>,
+        /// Thread replies, by the timestamp of the message replied to
+        replies: RefCell<Vec<(String, String)>>,
+        /// The blocks of each Block Kit message sent
+        blocks: RefCell<Vec<serde_json::Value>>,
+        /// Ephemeral previews, as (channel, user id, text)
+        previews: RefCell<Vec<(String, String, String)>>,
+        /// Reactions, as (channel, ts, emoji)
+        reactions: RefCell<Vec<(String, String, String)>>,
+        inserted: RefCell<Vec<Incident>>,
+        /// What a refresh of the Slack users returns
+        slack_users: Vec<SlackUser>,
+        refreshes: RefCell<usize>,
+        marked: RefCell<Vec<u64>>,
+        archived: RefCell<Vec<String>>,
+        /// The incidents a lookup reports as already in Notion
+        scheduled: Vec<u64>,
+        /// The incidents a lookup reports as having a postmortem
+        postmortems: Vec<u64>,
+        /// Fail every insert after this many have succeeded
+        fail_after: Option<usize>,
+        /// Fail the inserts of these incidents
+        rejected: Vec<u64>,
+        /// Fail opening a direct message with these Slack users
+        unreachable: Vec<String>,
+    }
+
+    /// The id the recording publisher reports the channel with the given name by
+    fn channel_id(name: &str) -> String {
+        format!("C-{}", name)
+    }
+
+    impl RecordingPublisher {
+        fn inserted_numbers(&self) -> Vec<u64> {
+            numbers(&self.inserted.borrow())
+        }
+    }
+
+    impl IncidentLookup for RecordingPublisher {
+        async fn is_scheduled(&self, incident: &Incident) -> Result<bool> {
+            Ok(self.scheduled.contains(&incident.number))
+        }
+
+        async fn reviewed_incidents(&self) -> Result<Vec<Incident>> {
+            Ok(vec![])
+        }
+
+        async fn has_postmortem(&self, incident: &Incident) -> Result<bool> {
+            Ok(self.postmortems.contains(&incident.number))
+        }
+    }
+
+    impl ReviewPublisher for RecordingPublisher {
+        async fn send_message(
+            &self,
+            channel: &str,
+            message: &str,
+            thread_ts: Option<&str>,
+        ) -> Result<PostedMessage> {
+            let ts = format!(
+                "ts{}",
+                self.sent.borrow().len() + self.replies.borrow().len()
+            );
+            match thread_ts {
+                Some(thread_ts) => self
+                    .replies
+                    .borrow_mut()
+                    .push((thread_ts.to_owned(), message.to_owned())),
+                None => self
+                    .sent
+                    .borrow_mut()
+                    .push((channel.to_owned(), message.to_owned())),
+            }
+            Ok(PostedMessage {
+                channel: channel_id(channel),
+                ts,
+            })
+        }
+
+        async fn send_blocks(
+            &self,
+            channel: &str,
+            text: &str,
+            blocks: &serde_json::Value,
+        ) -> Result<PostedMessage> {
+            self.blocks.borrow_mut().push(blocks.clone());
+            self.send_message(channel, text, None).await
+        }
+
+        async fn send_ephemeral(&self, channel: &str, user_id: &str, text: &str) -> Result<String> {
+            self.previews.borrow_mut().push((
+                channel.to_owned(),
+                user_id.to_owned(),
+                text.to_owned(),
+            ));
+            Ok(format!("ephemeral{}", self.previews.borrow().len()))
+        }
+
+        async fn open_dm(&self, user_id: &str) -> Result<String> {
+            if self.unreachable.iter().any(|u| u == user_id) {
+                return Err(anyhow!("cannot_dm_bot"));
+            }
+            Ok(format!("D-{}", user_id))
+        }
+
+        async fn add_reaction(&self, channel: &str, ts: &str, emoji: &str) -> Result<()> {
+            self.reactions
+                .borrow_mut()
+                .push((channel.to_owned(), ts.to_owned(), emoji.to_owned()));
+            Ok(())
+        }
+
+        async fn insert_incident(&self, incident: &Incident) -> Result<String> {
+            if self.rejected.contains(&incident.number)
+                || self
+                    .fail_after
+                    .is_some_and(|k| self.inserted.borrow().len() >= k)
+            {
+                return Err(anyhow::anyhow!("insert of {} failed", incident.number));
+            }
+            self.inserted.borrow_mut().push(incident.clone());
+            Ok(format!("https://www.notion.so/{}", incident.number))
+        }
+
+        async fn refresh_slack_users(&self) -> Result<Vec<SlackUser>> {
+            *self.refreshes.borrow_mut() += 1;
+            Ok(self.slack_users.clone())
+        }
+
+        async fn mark_reviewed(&self, incident: &Incident) -> Result<()> {
+            self.marked.borrow_mut().push(incident.number);
+            Ok(())
+        }
+
+        async fn archive_channel(&self, channel: &str) -> Result<()> {
+            self.archived.borrow_mut().push(channel.to_owned());
+            Ok(())
+        }
+    }
+
+    fn slack_with_channels(names: &[&str]) -> Slack {
+        let mut slack = Slack::default();
+        slack.channels = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Channel {
+                id: format!("C{}", i),
+                name: name.to_string(),
+            })
+            .collect();
+        slack
+    }
+
+    fn channel_names(matched: ChannelMatch) -> Vec<String> {
+        match matched {
+            ChannelMatch::None => vec![],
+            ChannelMatch::Unique(c) => vec![c.name.clone()],
+            ChannelMatch::Ambiguous(cs) => cs.iter().map(|c| c.name.clone()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_get_channel_for_renamed_channel() {
+        let incident = incident_with(42, Some("P1"), false);
+        let slack = slack_with_channels(&["incident-420", "release-4242", "incident-42-db-outage"]);
+        assert!(matches!(
+            get_channel_for(&incident, &slack),
+            ChannelMatch::Unique(c) if c.name == "incident-42-db-outage"
+        ));
+
+        // the channel created for the incident beats anything else mentioning it
+        let slack = slack_with_channels(&["incident-42-db-outage", "incident-42", "db-42"]);
+        assert!(matches!(
+            get_channel_for(&incident, &slack),
+            ChannelMatch::Unique(c) if c.name == "incident-42"
+        ));
+
+        let slack = slack_with_channels(&["incident-420", "incident-1420"]);
+        assert!(matches!(
+            get_channel_for(&incident, &slack),
+            ChannelMatch::None
+        ));
+    }
+
+    #[test]
+    fn test_channel_score_prefixed_numbers() {
+        let format = NumberFormat {
+            prefix: "INC-".to_owned(),
+            digits: 4,
+        };
+        assert_eq!(
+            channel_score(&format, 42, "inc-0042"),
+            Some(ChannelScore::Exact)
+        );
+        assert_eq!(
+            channel_score(&format, 42, "incident-inc-0042"),
+            Some(ChannelScore::Exact)
+        );
+        assert_eq!(
+            channel_score(&format, 42, "incident-42"),
+            Some(ChannelScore::Exact)
+        );
+        assert_eq!(
+            channel_score(&format, 42, "inc-0042-db-outage"),
+            Some(ChannelScore::Renamed)
+        );
+        assert_eq!(
+            channel_score(&format, 42, "db-0042"),
+            Some(ChannelScore::Mentioned)
+        );
+        assert_eq!(channel_score(&format, 42, "inc-00420"), None);
+        assert_eq!(channel_score(&format, 42, "inc-1042"), None);
+        // padding is only accepted when configured
+        assert_eq!(
+            channel_score(&NumberFormat::default(), 42, "inc-0042"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_get_channel_for_ambiguous() {
+        let incident = incident_with(42, Some("P1"), false);
+        let slack =
+            slack_with_channels(&["incident-42-db-outage", "incident-42-rpc", "followup-42"]);
+        assert_eq!(
+            channel_names(get_channel_for(&incident, &slack)),
+            vec!["incident-42-db-outage", "incident-42-rpc"]
+        );
+
+        // without a prompter the incident is left without a channel
+        assert!(
+            resolve_channel_for(&incident, &slack, None::<&FixedPrompter>)
+                .unwrap()
+                .is_none()
+        );
+
+        let prompter = FixedPrompter {
+            canonical: 1,
+            ..FixedPrompter::new(true)
+        };
+        let channel = resolve_channel_for(&incident, &slack, Some(&prompter)).unwrap();
+        assert_eq!(channel.unwrap().name, "incident-42-rpc");
+        assert_eq!(*prompter.questions.borrow(), vec!["select channel"]);
+    }
+
+    #[test]
+    fn test_group_by_similar_title() {
         let incidents = vec![
             Incident {
                 title: "Incident 1".to_string(),
@@ -372,72 +2263,1539 @@ mod tests {
                 ..Default::default()
             },
             Incident {
-                title: "A third thing that doesn't look the same".to_string(),
-                ..Default::default()
+                title: "A third thing that doesn't look the same".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let groups = group_by_similar_title(incidents, 0.8);
+        println!("{:#?}", groups);
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups.get("Incident 1").unwrap().len(), 2);
+        assert!(!groups.contains_key("Incident 2"));
+        assert_eq!(groups.get("Another thing entirely").unwrap().len(), 2);
+        assert_eq!(
+            groups
+                .get("A third thing that doesn't look the same")
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_group_by_similar_title_with_similar_titles() {
+        let incidents = vec![
+            Incident {
+                title: "Incident 1".to_string(),
+                ..Default::default()
+            },
+            Incident {
+                title: "Incident 1".to_string(),
+                ..Default::default()
+            },
+            Incident {
+                title: "Incident 2".to_string(),
+                ..Default::default()
+            },
+            Incident {
+                title: "Incident 2".to_string(),
+                ..Default::default()
+            },
+            Incident {
+                title: "Incident 3".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let groups = group_by_similar_title(incidents, 0.8);
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups.get("Incident 1").unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_merge_groups_sharing_channel() {
+        let on_channel = |number: u64, title: &str, channel: Option<&str>| Incident {
+            number,
+            title: title.to_owned(),
+            slack_channel: channel.map(|id| Channel {
+                id: id.to_owned(),
+                name: format!("incident-{}", id),
+            }),
+            ..Default::default()
+        };
+        let incidents = vec![
+            on_channel(1, "Fullnode RPC latency spike", Some("C1")),
+            on_channel(2, "Checkpoint execution halted", Some("C1")),
+            on_channel(3, "Validator disk full", Some("C3")),
+            on_channel(4, "Indexer lagging behind", None),
+        ];
+        let groups = merge_groups_sharing_channel(group_by_similar_title(incidents, 0.9));
+
+        assert_eq!(groups.len(), 3);
+        let mut merged: Vec<u64> = groups["Checkpoint execution halted"]
+            .iter()
+            .map(|i| i.number)
+            .collect();
+        merged.sort();
+        assert_eq!(merged, vec![1, 2]);
+        assert_eq!(groups["Validator disk full"].len(), 1);
+        assert_eq!(groups["Indexer lagging behind"].len(), 1);
+    }
+
+    fn incident_with(number: u64, priority: Option<&str>, channel: bool) -> Incident {
+        Incident {
+            number,
+            title: format!("Incident {}", number),
+            priority: priority.map(Priority::from_name),
+            slack_channel: channel.then(|| Channel {
+                id: format!("C{}", number),
+                name: format!("incident-{}", number),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn numbers(incidents: &[Incident]) -> Vec<u64> {
+        incidents.iter().map(|i| i.number).collect()
+    }
+
+    #[test]
+    fn test_filter_incidents_for_review_includes_channels_by_default() {
+        let incidents = vec![
+            incident_with(1, Some("P1"), false),
+            incident_with(2, Some("P1"), true),
+            incident_with(3, Some("P3"), true),
+            incident_with(4, Some("P3"), false),
+            incident_with(5, None, false),
+        ];
+
+        let filtered = filter_incidents_for_review(incidents, "P2", false);
+
+        assert_eq!(numbers(&filtered), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_filter_incidents_for_review_require_no_channel() {
+        let incidents = vec![
+            incident_with(1, Some("P1"), false),
+            incident_with(2, Some("P1"), true),
+            incident_with(3, Some("P3"), true),
+            incident_with(4, Some("P3"), false),
+            incident_with(5, None, false),
+        ];
+
+        let filtered = filter_incidents_for_review(incidents, "P2", true);
+
+        assert_eq!(numbers(&filtered), vec![1]);
+    }
+
+    fn tagged(number: u64, tags: &[&str]) -> Incident {
+        Incident {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..incident_with(number, Some("P1"), false)
+        }
+    }
+
+    #[test]
+    fn test_filter_incidents_by_single_tag() {
+        let incidents = vec![
+            tagged(1, &["validator"]),
+            tagged(2, &["indexer", "data"]),
+            tagged(3, &[]),
+        ];
+
+        let filtered = filter_incidents_by_tags(incidents, &["Indexer".to_string()]);
+
+        assert_eq!(numbers(&filtered), vec![2]);
+    }
+
+    #[test]
+    fn test_filter_incidents_by_multiple_tags_ors() {
+        let incidents = vec![
+            tagged(1, &["validator"]),
+            tagged(2, &["indexer", "data"]),
+            tagged(3, &["rpc"]),
+            tagged(4, &[]),
+        ];
+
+        let filtered = filter_incidents_by_tags(
+            incidents.clone(),
+            &["validator".to_string(), "data".to_string()],
+        );
+        assert_eq!(numbers(&filtered), vec![1, 2]);
+
+        let unfiltered = filter_incidents_by_tags(incidents, &[]);
+        assert_eq!(numbers(&unfiltered), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Threshold must be between 0.0 and 1.0")]
+    fn test_group_by_similar_title_with_invalid_threshold() {
+        let incidents = vec![
+            Incident {
+                title: "Incident 1".to_string(),
+                ..Default::default()
+            },
+            Incident {
+                title: "Incident 2".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        group_by_similar_title(incidents, -0.5);
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_sends_and_inserts_by_default() {
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(true);
+        let incidents = vec![incident_with(1, Some("P1"), false)];
+
+        publish_review(
+            &publisher,
+            &prompter,
+            &ReviewOptions::default(),
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(publisher.sent.borrow().len(), 1);
+        assert_eq!(publisher.inserted_numbers(), vec![1]);
+        // one summary confirms every write
+        let options = ReviewOptions::default();
+        assert_eq!(
+            *prompter.questions.borrow(),
+            vec![format!(
+                "About to:\n• post the review message to #{}\n• insert 1 incidents into the {:?} Notion database ({})\nProceed?",
+                options.channel().review_channel(),
+                options.database().name,
+                options.database().id
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_archives_only_resolved_channels() {
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(true);
+        let resolved = |incident: Incident| Incident {
+            status: Some("resolved".to_owned()),
+            resolved_at: Some("2024-03-04T18:02:00Z".to_owned()),
+            ..incident
+        };
+        let incidents = vec![
+            resolved(incident_with(1, Some("P1"), true)),
+            // still open, so its channel is kept
+            incident_with(2, Some("P1"), true),
+            resolved(incident_with(3, Some("P1"), false)),
+        ];
+        let options = ReviewOptions {
+            no_send: true,
+            no_insert: true,
+            archive_channels: true,
+            ..Default::default()
+        };
+
+        publish_review(
+            &publisher,
+            &prompter,
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(*publisher.archived.borrow(), vec!["C1"]);
+        assert!(prompter.questions.borrow()[0]
+            .contains("• archive 1 Slack channels of resolved incidents"));
+    }
+
+    #[test]
+    fn test_configured_database_is_not_chosen_for_the_debug_target() {
+        let databases = vec![ReviewDatabase {
+            name: "Wallet".to_owned(),
+            ..ReviewDatabase::of(Target::Prod)
+        }];
+        let prompter = FixedPrompter::new(true);
+        let debug = ReviewOptions {
+            db_target: Some(Target::Debug),
+            ..Default::default()
+        };
+        assert!(choose_review_database(&databases, &debug, &prompter)
+            .unwrap()
+            .is_none());
+
+        let named = ReviewOptions {
+            db_name: Some("Wallet".to_owned()),
+            ..debug
+        };
+        let err = choose_review_database(&databases, &named, &prompter).unwrap_err();
+        assert!(err.to_string().contains("\"Wallet\""), "{}", err);
+
+        let prod = ReviewOptions {
+            db_target: Some(Target::Prod),
+            ..Default::default()
+        };
+        let chosen = choose_review_database(&databases, &prod, &prompter).unwrap();
+        assert_eq!(chosen.unwrap().name, "Wallet");
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_dms_pocs_their_incidents() {
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(true);
+        let mut jane = poc("jane");
+        jane.slack_user = Some(SlackUser {
+            id: "U1".to_owned(),
+            name: "jane".to_owned(),
+            profile: None,
+        });
+        let incidents = vec![
+            Incident {
+                poc_users: Some(vec![jane.clone(), poc("abc")]),
+                ..incident_with(1, Some("P1"), false)
+            },
+            Incident {
+                poc_users: Some(vec![jane]),
+                ..incident_with(2, Some("P1"), false)
+            },
+        ];
+        let options = ReviewOptions {
+            no_insert: true,
+            dm_pocs: true,
+            line_template: Some("{number} {title}".to_owned()),
+            ..Default::default()
+        };
+
+        publish_review(
+            &publisher,
+            &prompter,
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        // the POC without a Slack user is skipped
+        assert!(prompter.questions.borrow()[0]
+            .contains("• send 1 POCs a direct message listing their incidents"));
+        let sent = publisher.sent.borrow();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(
+            sent[1],
+            (
+                "D-U1".to_owned(),
+                "You're a POC of these incidents selected for review:\n1 Incident 1\n2 Incident 2"
+                    .to_owned()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_poc_does_not_stop_the_publish() {
+        let publisher = RecordingPublisher {
+            unreachable: vec!["U1".to_owned()],
+            ..Default::default()
+        };
+        let prompter = FixedPrompter::new(true);
+        let slack_poc = |name: &str, id: &str| {
+            let mut user = poc(name);
+            user.slack_user = Some(SlackUser {
+                id: id.to_owned(),
+                name: name.to_owned(),
+                profile: None,
+            });
+            user
+        };
+        let incidents = vec![
+            Incident {
+                poc_users: Some(vec![slack_poc("jane", "U1")]),
+                ..incident_with(1, Some("P1"), false)
+            },
+            Incident {
+                poc_users: Some(vec![slack_poc("john", "U2")]),
+                ..incident_with(2, Some("P1"), false)
+            },
+        ];
+        let options = ReviewOptions {
+            dm_pocs: true,
+            ..Default::default()
+        };
+
+        let published = publish_review(
+            &publisher,
+            &prompter,
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(published);
+        let dms: Vec<String> = publisher
+            .sent
+            .borrow()
+            .iter()
+            .map(|(channel, _)| channel.clone())
+            .filter(|channel| channel.starts_with("D-"))
+            .collect();
+        assert_eq!(dms, vec!["D-U2"]);
+        let mut inserted: Vec<u64> = publisher
+            .inserted
+            .borrow()
+            .iter()
+            .map(|i| i.number)
+            .collect();
+        inserted.sort();
+        assert_eq!(inserted, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_strict_pocs_aborts_with_offenders() {
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(true);
+        let mut linked = poc("jane");
+        linked.slack_user = Some(SlackUser {
+            id: "U1".to_owned(),
+            name: "jane".to_owned(),
+            profile: None,
+        });
+        let incidents = vec![
+            Incident {
+                poc_users: Some(vec![linked.clone(), poc("abc")]),
+                ..incident_with(1, Some("P1"), false)
+            },
+            Incident {
+                poc_users: Some(vec![linked]),
+                ..incident_with(2, Some("P1"), false)
+            },
+        ];
+        let options = ReviewOptions {
+            strict_pocs: true,
+            ..Default::default()
+        };
+
+        let err = publish_review(
+            &publisher,
+            &prompter,
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "1 POCs can't be both mentioned in Slack and set in Notion (--strict-pocs), nothing was written:\n• 1: abc [Notion] has no Slack user"
+        );
+        assert!(publisher.sent.borrow().is_empty());
+        assert!(publisher.inserted_numbers().is_empty());
+        assert!(prompter.questions.borrow().is_empty());
+
+        // the same incidents go through without strict mode
+        publish_review(
+            &publisher,
+            &prompter,
+            &ReviewOptions::default(),
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(publisher.inserted_numbers(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_declined_summary_writes_nothing() {
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(false);
+        let incidents = vec![incident_with(1, Some("P1"), false)];
+        let options = ReviewOptions {
+            mark_reviewed: true,
+            ..Default::default()
+        };
+
+        publish_review(
+            &publisher,
+            &prompter,
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(publisher.sent.borrow().is_empty());
+        assert!(publisher.inserted_numbers().is_empty());
+        let questions = prompter.questions.borrow();
+        assert_eq!(questions.len(), 1);
+        assert!(questions[0].contains("• mark 1 incidents as reviewed in the incident source"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_no_send_only_inserts() {
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(true);
+        let incidents = vec![incident_with(1, Some("P1"), false)];
+        let options = ReviewOptions {
+            no_send: true,
+            ..Default::default()
+        };
+
+        publish_review(
+            &publisher,
+            &prompter,
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(publisher.sent.borrow().is_empty());
+        assert_eq!(publisher.inserted_numbers(), vec![1]);
+        let questions = prompter.questions.borrow();
+        assert_eq!(questions.len(), 1);
+        assert!(questions[0].contains("• insert 1 incidents"));
+        assert!(!questions[0].contains("• post"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_inserts_the_rest_after_a_failed_insert() {
+        let incidents: Vec<Incident> = (1..=4)
+            .map(|n| incident_with(n, Some("P1"), false))
+            .collect();
+        let options = ReviewOptions {
+            no_send: true,
+            ..Default::default()
+        };
+        let publisher = RecordingPublisher {
+            rejected: vec![2],
+            ..Default::default()
+        };
+        let err = publish_review(
+            &publisher,
+            &FixedPrompter::new(true),
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "1 of 4 incidents couldn't be inserted into Notion: 2"
+        );
+        let mut inserted = publisher.inserted_numbers();
+        inserted.sort();
+        assert_eq!(inserted, vec![1, 3, 4]);
+        assert!(publisher.marked.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_render_inserted_pages() {
+        assert_eq!(
+            render_inserted_pages(&[
+                (1, "https://www.notion.so/1".to_owned()),
+                (3, "https://www.notion.so/3".to_owned()),
+            ]),
+            "Inserted 2 incidents into Notion:\n• 1: https://www.notion.so/1\n• 3: https://www.notion.so/3"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_declined_review_does_not_record_the_run() {
+        use crate::cli::incidents::last_run::load_last_run;
+
+        let dir = tempfile::tempdir().unwrap();
+        let incidents = vec![incident_with(1, Some("P1"), false)];
+        let options = ReviewOptions::default();
+        let started_at = Utc::now();
+        let mut snoozes = SnoozeList::default();
+        snoozes.snooze(&incident_with(2, Some("P1"), false), started_at);
+        let finish = |prompter: FixedPrompter| {
+            let (options, incidents, snoozes, dir) = (&options, &incidents, &snoozes, dir.path());
+            async move {
+                finish_review(
+                    &RecordingPublisher::default(),
+                    &prompter,
+                    options,
+                    &ReviewMessage::Single("msg".to_owned()),
+                    incidents,
+                    &InsertCheckpoint::default(),
+                    snoozes,
+                    dir,
+                    started_at,
+                )
+                .await
+            }
+        };
+
+        finish(FixedPrompter::new(false)).await.unwrap();
+        assert!(load_last_run(dir.path()).unwrap().is_none());
+        assert!(SnoozeList::load_from(dir.path())
+            .unwrap()
+            .snoozed
+            .is_empty());
+
+        finish(FixedPrompter::new(true)).await.unwrap();
+        assert_eq!(load_last_run(dir.path()).unwrap(), Some(started_at));
+        assert_eq!(SnoozeList::load_from(dir.path()).unwrap().snoozed.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_resumes_from_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let incidents: Vec<Incident> = (1..=5)
+            .map(|n| incident_with(n, Some("P1"), false))
+            .collect();
+        let options = ReviewOptions {
+            no_send: true,
+            ..Default::default()
+        };
+
+        // the first run dies after two inserts
+        let crashing = RecordingPublisher {
+            fail_after: Some(2),
+            ..Default::default()
+        };
+        let checkpoint = InsertCheckpoint::load(dir.path(), &options.database().id).unwrap();
+        let result = publish_review(
+            &crashing,
+            &FixedPrompter::new(true),
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &checkpoint,
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(crashing.inserted_numbers(), vec![1, 2]);
+
+        // the rerun only inserts the rest
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(true);
+        let checkpoint = InsertCheckpoint::load(dir.path(), &options.database().id).unwrap();
+        publish_review(
+            &publisher,
+            &prompter,
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &checkpoint,
+        )
+        .await
+        .unwrap();
+        assert_eq!(publisher.inserted_numbers(), vec![3, 4, 5]);
+        assert!(prompter.questions.borrow()[0].contains("• insert 3 incidents"));
+
+        // the batch completed, so running again inserts everything, updating the pages
+        let publisher = RecordingPublisher::default();
+        let checkpoint = InsertCheckpoint::load(dir.path(), &options.database().id).unwrap();
+        publish_review(
+            &publisher,
+            &FixedPrompter::new(true),
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &checkpoint,
+        )
+        .await
+        .unwrap();
+        assert_eq!(publisher.inserted_numbers(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_no_insert_only_sends() {
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(true);
+        let incidents = vec![incident_with(1, Some("P1"), false)];
+        let options = ReviewOptions {
+            no_insert: true,
+            ..Default::default()
+        };
+
+        publish_review(
+            &publisher,
+            &prompter,
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(publisher.sent.borrow().len(), 1);
+        assert!(publisher.inserted_numbers().is_empty());
+        let questions = prompter.questions.borrow();
+        assert_eq!(questions.len(), 1);
+        assert!(questions[0].contains("• post the review message"));
+        assert!(!questions[0].contains("• insert"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_threaded_replies_under_one_root() {
+        let publisher = RecordingPublisher::default();
+        let options = ReviewOptions {
+            no_insert: true,
+            threaded: true,
+            ..Default::default()
+        };
+        let message = ReviewMessage::Threaded {
+            root: "Incident review for 2024-03-04".to_owned(),
+            replies: vec!["• 1".to_owned(), "• 2".to_owned(), "• 3".to_owned()],
+        };
+
+        publish_review(
+            &publisher,
+            &FixedPrompter::new(true),
+            &options,
+            &message,
+            &[],
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        let sent = publisher.sent.borrow();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].1, "Incident review for 2024-03-04");
+        // the root is the first message posted, so it has ts0
+        assert_eq!(
+            *publisher.replies.borrow(),
+            vec![
+                ("ts0".to_owned(), "• 1".to_owned()),
+                ("ts0".to_owned(), "• 2".to_owned()),
+                ("ts0".to_owned(), "• 3".to_owned()),
+            ]
+        );
+        // without --react-when-final nothing is reacted to
+        assert!(publisher.reactions.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_reacts_to_the_root_when_final() {
+        let publisher = RecordingPublisher::default();
+        let options = ReviewOptions {
+            no_insert: true,
+            react_when_final: true,
+            ..Default::default()
+        };
+        let message = ReviewMessage::Threaded {
+            root: "Incident review".to_owned(),
+            replies: vec!["• 1".to_owned(), "• 2".to_owned()],
+        };
+
+        publish_review(
+            &publisher,
+            &FixedPrompter::new(true),
+            &options,
+            &message,
+            &[],
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        // reactions.add only takes a channel id, not the name the message was posted to
+        assert_eq!(
+            *publisher.reactions.borrow(),
+            vec![(
+                channel_id(options.channel().review_channel()),
+                "ts0".to_owned(),
+                FINAL_REACTION.to_owned()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_sends_blocks() {
+        let publisher = RecordingPublisher::default();
+        let options = ReviewOptions {
+            no_insert: true,
+            blocks: true,
+            ..Default::default()
+        };
+        let to_review = vec![
+            Incident {
+                html_url: "https://pagerduty.com/incidents/1".to_owned(),
+                ..incident_with(1, Some("P1"), false)
             },
+            incident_with(2, Some("P2"), false),
         ];
+        let message = build_review_message(
+            &options,
+            Some("<!subteam^S1>"),
+            &MessageFrame::default(),
+            NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+            &to_review,
+            &[],
+        );
+        let ReviewMessage::Blocks { text, blocks } = &message else {
+            panic!("expected a Block Kit message, got {:?}", message);
+        };
+        assert!(text.starts_with("<!subteam^S1>\n"));
+        let types: Vec<&str> = blocks
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|b| b["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            vec!["section", "section", "context", "section", "section", "divider", "context"]
+        );
+        assert!(blocks[0]["text"]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("<!subteam^S1>\n\n"));
+        assert_eq!(
+            blocks[3]["accessory"]["url"],
+            "https://pagerduty.com/incidents/1"
+        );
+        // an incident without a url gets no button
+        assert!(blocks[4].get("accessory").is_none());
 
-        let groups = group_by_similar_title(incidents, 0.8);
-        println!("{:#?}", groups);
+        publish_review(
+            &publisher,
+            &FixedPrompter::new(true),
+            &options,
+            &message,
+            &[],
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(publisher.sent.borrow()[0].1, *text);
+        assert_eq!(*publisher.blocks.borrow(), vec![blocks.clone()]);
+    }
 
-        assert_eq!(groups.len(), 3);
-        assert_eq!(groups.get("Incident 1").unwrap().len(), 2);
-        assert!(!groups.contains_key("Incident 2"));
-        assert_eq!(groups.get("Another thing entirely").unwrap().len(), 2);
+    #[tokio::test]
+    async fn test_publish_review_previews_before_sending() {
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(true);
+        let options = ReviewOptions {
+            no_insert: true,
+            preview_to: Some("U123".to_owned()),
+            ..Default::default()
+        };
+        let message = ReviewMessage::Threaded {
+            root: "Incident review".to_owned(),
+            replies: vec!["• 1".to_owned()],
+        };
+
+        publish_review(
+            &publisher,
+            &prompter,
+            &options,
+            &message,
+            &[],
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        let channel = options.channel().review_channel().to_owned();
         assert_eq!(
-            groups
-                .get("A third thing that doesn't look the same")
-                .unwrap()
-                .len(),
-            1
+            *publisher.previews.borrow(),
+            vec![(
+                channel,
+                "U123".to_owned(),
+                "Incident review\n  ↳ • 1".to_owned()
+            )]
+        );
+        {
+            let questions = prompter.questions.borrow();
+            assert_eq!(questions.len(), 2);
+            assert!(questions[0].starts_with("Preview the review message"));
+        }
+        assert_eq!(publisher.sent.borrow().len(), 1);
+
+        // answering no skips both the preview and the post
+        let publisher = RecordingPublisher::default();
+        publish_review(
+            &publisher,
+            &FixedPrompter::new(false),
+            &options,
+            &message,
+            &[],
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+        assert!(publisher.previews.borrow().is_empty());
+        assert!(publisher.sent.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_routes_to_team_channels() {
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(true);
+        let options = ReviewOptions {
+            no_insert: true,
+            channel_target: Some(Target::Prod),
+            ..Default::default()
+        };
+        let owned_by = |number, team: Option<&str>| Incident {
+            owning_team: team.map(str::to_owned),
+            ..incident_with(number, Some("P1"), false)
+        };
+        let to_review = vec![
+            owned_by(1, Some("Infra")),
+            owned_by(2, None),
+            owned_by(3, Some("Wallet")),
+            owned_by(4, Some("infra")),
+        ];
+        let teams = TeamChannels::parse("Infra=infra-reviews,Wallet=wallet-reviews").unwrap();
+        let message = build_routed_review_message(
+            &options,
+            &teams,
+            None,
+            &MessageFrame::default(),
+            NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+            &to_review,
+            &[],
         );
+
+        publish_review(
+            &publisher,
+            &prompter,
+            &options,
+            &message,
+            &to_review,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        let sent = publisher.sent.borrow();
+        let default_channel = options.channel().review_channel();
+        assert_eq!(sent.len(), 3);
+        let mentions = |channel: &str| {
+            let (_, text) = sent.iter().find(|(c, _)| c == channel).unwrap();
+            [1, 2, 3, 4]
+                .into_iter()
+                .filter(|n| text.contains(&format!("Incident {}", n)))
+                .collect::<Vec<u64>>()
+        };
+        // unteamed incidents go to the default channel
+        assert_eq!(mentions(default_channel), vec![2]);
+        assert_eq!(mentions("infra-reviews"), vec![1, 4]);
+        assert_eq!(mentions("wallet-reviews"), vec![3]);
+        // one confirmation lists every channel
+        let question = &prompter.questions.borrow()[0];
+        for channel in [default_channel, "infra-reviews", "wallet-reviews"] {
+            assert!(question.contains(&format!("#{}", channel)));
+        }
     }
 
     #[test]
-    fn test_group_by_similar_title_with_similar_titles() {
+    fn test_debug_review_is_not_routed_to_team_channels() {
+        let options = ReviewOptions {
+            channel_target: Some(Target::Debug),
+            ..Default::default()
+        };
+        let to_review = vec![Incident {
+            owning_team: Some("Infra".to_owned()),
+            ..incident_with(1, Some("P1"), false)
+        }];
+        let teams = TeamChannels::parse("Infra=infra-reviews").unwrap();
+        let message = build_routed_review_message(
+            &options,
+            &teams,
+            None,
+            &MessageFrame::default(),
+            NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+            &to_review,
+            &[],
+        );
+        let channels: Vec<&str> = message
+            .destinations(options.channel().review_channel())
+            .into_iter()
+            .map(|(channel, _)| channel)
+            .collect();
+        assert_eq!(channels, vec![Target::Debug.review_channel()]);
+    }
+
+    #[tokio::test]
+    async fn test_filter_incidents_with_postmortem() {
+        let lookup = RecordingPublisher {
+            postmortems: vec![2],
+            ..Default::default()
+        };
         let incidents = vec![
+            incident_with(1, Some("P1"), false),
+            incident_with(2, Some("P1"), false),
+            incident_with(3, Some("P2"), false),
+        ];
+        let (kept, skipped) = filter_incidents_with_postmortem(&lookup, incidents)
+            .await
+            .unwrap();
+        assert_eq!(numbers(&kept), vec![1, 3]);
+        assert_eq!(skipped, 1);
+    }
+
+    #[tokio::test]
+    async fn test_audit_report_only_reads() {
+        let incidents = vec![
+            incident_with(1, Some("P1"), true),
             Incident {
-                title: "Incident 1".to_string(),
-                ..Default::default()
-            },
-            Incident {
-                title: "Incident 1".to_string(),
-                ..Default::default()
-            },
-            Incident {
-                title: "Incident 2".to_string(),
-                ..Default::default()
-            },
-            Incident {
-                title: "Incident 2".to_string(),
-                ..Default::default()
+                title: "Validator crash at epoch change".to_owned(),
+                ..incident_with(2, None, false)
             },
             Incident {
-                title: "Incident 3".to_string(),
-                ..Default::default()
+                title: "Validator crash at epoch change, again".to_owned(),
+                ..incident_with(3, Some("P2"), false)
             },
         ];
+        let group_map = group_by_similar_title(incidents, 0.9);
+        let publisher = RecordingPublisher {
+            scheduled: vec![1],
+            ..Default::default()
+        };
+        let options = ReviewOptions {
+            audit: true,
+            db_target: Some(Target::Debug),
+            channel_target: Some(Target::Debug),
+            ..Default::default()
+        };
 
-        let groups = group_by_similar_title(incidents, 0.8);
+        let report = audit_report(&publisher, &group_map, &options)
+            .await
+            .unwrap();
 
-        assert_eq!(groups.len(), 1);
-        assert_eq!(groups.get("Incident 1").unwrap().len(), 5);
+        assert!(publisher.sent.borrow().is_empty());
+        assert!(publisher.inserted.borrow().is_empty());
+        assert!(publisher.marked.borrow().is_empty());
+        assert_eq!(*publisher.refreshes.borrow(), 0);
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines[0].starts_with("Audit of 3 incidents up for review"));
+        assert!(lines[0].contains(&format!("#{}", Target::Debug.review_channel())));
+        assert_eq!(
+            lines[1..],
+            [
+                "• 1 [P1] Incident 1, #incident-1, already in Notion",
+                "Similar titles, reviewed together if the operator agrees: 2, 3",
+                "• 2 [no priority] Validator crash at epoch change, no channel, not in Notion",
+                "• 3 [P2] Validator crash at epoch change, again, no channel, not in Notion",
+                "1 of 3 already in Notion, 2 without a channel",
+            ]
+        );
     }
 
     #[test]
-    #[should_panic(expected = "Threshold must be between 0.0 and 1.0")]
-    fn test_group_by_similar_title_with_invalid_threshold() {
+    fn test_select_incidents_for_review_snooze() {
         let incidents = vec![
+            incident_with(1, Some("P1"), false),
+            incident_with(2, Some("P1"), false),
+        ];
+        let group_map = group_by_similar_title(incidents, 0.9);
+        let prompter = FixedPrompter {
+            snooze: true,
+            ..FixedPrompter::new(false)
+        };
+
+        let output = ReviewOutput::new(true, vec![], vec![]);
+        let (to_review, excluded, snoozed) =
+            select_incidents_for_review(group_map, &[], false, &prompter, &output).unwrap();
+        assert!(to_review.is_empty());
+        assert!(excluded.is_empty());
+        let mut snoozed = numbers(&snoozed);
+        snoozed.sort();
+        assert_eq!(snoozed, vec![1, 2]);
+        // snoozed incidents aren't asked for POCs
+        assert!(!prompter
+            .questions
+            .borrow()
+            .contains(&"select pocs".to_owned()));
+    }
+
+    #[test]
+    fn test_select_incidents_for_review_asks_in_a_stable_order() {
+        let titles = [
+            "Wallet sync stalled",
+            "RPC latency high",
+            "Checkpoint builder stuck",
+            "Indexer lagging behind",
+            "Faucet out of funds",
+            "Explorer returns 502s",
+        ];
+        let incidents: Vec<Incident> = titles
+            .iter()
+            .enumerate()
+            .map(|(n, title)| Incident {
+                title: title.to_string(),
+                ..incident_with(n as u64 + 1, Some("P1"), false)
+            })
+            .collect();
+        let run = || {
+            let output = ReviewOutput::new(false, vec![], vec![]);
+            let (to_review, _, _) = select_incidents_for_review(
+                group_by_similar_title(incidents.clone(), 0.9),
+                &[],
+                false,
+                &FixedPrompter::new(true),
+                &output,
+            )
+            .unwrap();
+            (numbers(&to_review), output.into_inner().1)
+        };
+
+        let (first, first_progress) = run();
+        let (second, second_progress) = run();
+        // the groups are visited by title
+        assert_eq!(first, vec![3, 6, 5, 4, 2, 1]);
+        assert_eq!(first, second);
+        assert_eq!(first_progress, second_progress);
+    }
+
+    #[test]
+    fn test_select_incidents_for_review_quiet_has_no_progress_output() {
+        let incidents = vec![
+            incident_with(1, Some("P1"), false),
+            incident_with(2, Some("P1"), false),
+        ];
+        let group_map = group_by_similar_title(incidents, 0.9);
+
+        let output = ReviewOutput::new(true, vec![], vec![]);
+        let (to_review, excluded, _) = select_incidents_for_review(
+            group_map.clone(),
+            &[],
+            false,
+            &FixedPrompter::new(true),
+            &output,
+        )
+        .unwrap();
+        assert_eq!(numbers(&to_review), vec![1, 2]);
+        assert!(excluded.is_empty());
+        assert!(output.into_inner().1.is_empty());
+
+        let output = ReviewOutput::new(false, vec![], vec![]);
+        select_incidents_for_review(group_map, &[], false, &FixedPrompter::new(true), &output)
+            .unwrap();
+        let printed = String::from_utf8(output.into_inner().1).unwrap();
+        assert!(printed.contains("There are 2 incidents with a title similar to this"));
+    }
+
+    #[test]
+    fn test_greeting_line() {
+        let options = GreetingOptions::default();
+        assert_eq!(
+            options.line("Monday").as_deref(),
+            Some("Hello everyone and happy Monday!")
+        );
+
+        let options = GreetingOptions {
+            greeting: Some("Good {day}, all.".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(options.line("Friday").as_deref(), Some("Good Friday, all."));
+
+        let options = GreetingOptions {
+            no_greeting: true,
+            ..Default::default()
+        };
+        assert_eq!(options.line("Monday"), None);
+    }
+
+    #[test]
+    fn test_prepend_mention() {
+        let message = render_review_message(None, None, &[incident_with(1, Some("P1"), true)], &[]);
+        assert_eq!(prepend_mention(None, message.clone()), message);
+        let mentioned = prepend_mention(Some("<!subteam^S0614TZR7>"), message.clone());
+        assert!(mentioned.starts_with("<!subteam^S0614TZR7>\n\nThis batch: 1×P1"));
+        assert!(mentioned.ends_with(&message));
+    }
+
+    #[test]
+    fn test_render_review_message_without_greeting() {
+        let to_review = vec![incident_with(1, Some("P1"), true)];
+        let excluded = vec![incident_with(2, Some("P2"), false)];
+
+        let message = render_review_message(None, None, &to_review, &[]);
+        assert!(message.starts_with("\nThis batch: 1×P1\n\nWe have selected"));
+        assert!(!message.contains("Hello everyone"));
+
+        let message = render_review_message(None, None, &[], &excluded);
+        assert!(message.starts_with("\nWe have selected the following incidents for review:\n"));
+        assert!(!message.contains("This batch"));
+
+        let message = render_review_message(None, None, &to_review, &excluded);
+        assert!(!message.contains("Hello everyone"));
+        assert!(message.contains(&to_review[0].short_fmt()));
+        assert!(message.contains(&excluded[0].short_fmt()));
+
+        let message = render_review_message(Some("Hi!"), None, &to_review, &excluded);
+        assert!(message.starts_with("\nThis batch: 1×P1\n\nHi!\n\nWe have selected"));
+    }
+
+    #[test]
+    fn test_priority_histogram_mixed_batch() {
+        let incidents = vec![
+            incident_with(1, Some("P2"), false),
+            incident_with(2, Some("P1"), false),
+            incident_with(3, None, false),
+            incident_with(4, Some("P2"), false),
+            incident_with(5, Some("P1"), false),
+            incident_with(6, Some("P2"), false),
+            incident_with(7, Some(""), false),
+        ];
+        assert_eq!(
+            priority_histogram(&incidents),
+            "This batch: 2×P1, 3×P2, 2×unknown"
+        );
+    }
+
+    #[test]
+    fn test_filter_incidents_by_age() {
+        let now = Utc::now();
+        let created = |number: u64, age: Duration| Incident {
+            created_at: Some((now - age).format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+            ..incident_with(number, Some("P1"), false)
+        };
+        let incidents = vec![
+            created(1, Duration::days(7) - Duration::minutes(1)),
+            created(2, Duration::days(7) + Duration::minutes(1)),
+            incident_with(3, Some("P1"), false),
+        ];
+
+        let (kept, aged_out) = filter_incidents_by_age(incidents.clone(), Some(7), now);
+        assert_eq!(numbers(&kept), vec![1, 3]);
+        assert_eq!(aged_out, 1);
+
+        let (kept, aged_out) = filter_incidents_by_age(incidents, None, now);
+        assert_eq!(numbers(&kept), vec![1, 2, 3]);
+        assert_eq!(aged_out, 0);
+    }
+
+    #[test]
+    fn test_filter_incidents_by_min_events() {
+        let counted = |number: u64, count: Option<u64>| Incident {
+            count,
+            ..incident_with(number, Some("P1"), false)
+        };
+        let incidents = vec![
+            counted(1, Some(5)),
+            counted(2, Some(2)),
+            counted(3, Some(1)),
+            counted(4, None),
+        ];
+
+        let (kept, dropped) = filter_incidents_by_min_events(incidents.clone(), Some(2));
+        assert_eq!(numbers(&kept), vec![1, 2, 4]);
+        assert_eq!(dropped, 1);
+
+        let (kept, dropped) = filter_incidents_by_min_events(incidents, None);
+        assert_eq!(numbers(&kept), vec![1, 2, 3, 4]);
+        assert_eq!(dropped, 0);
+    }
+
+    #[test]
+    fn test_treat_as_one_records_canonical_incident() {
+        let group_map = BTreeMap::from([(
+            "Incident".to_owned(),
+            vec![
+                incident_with(1, Some("P1"), false),
+                incident_with(2, Some("P1"), false),
+                incident_with(3, Some("P1"), false),
+            ],
+        )]);
+        let prompter = FixedPrompter {
+            canonical: 1,
+            ..FixedPrompter::new(true)
+        };
+
+        let (to_review, _, _) = select_incidents_for_review(
+            group_map,
+            &[],
+            false,
+            &prompter,
+            &ReviewOutput::new(true, vec![], vec![]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_review.iter().map(|i| i.merged_into).collect::<Vec<_>>(),
+            vec![Some(2), None, Some(2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_skips_merged_incidents() {
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(true);
+        let incidents = vec![
+            incident_with(1, Some("P1"), false),
             Incident {
-                title: "Incident 1".to_string(),
-                ..Default::default()
-            },
-            Incident {
-                title: "Incident 2".to_string(),
-                ..Default::default()
+                merged_into: Some(1),
+                ..incident_with(2, Some("P1"), false)
             },
         ];
+        let options = ReviewOptions {
+            no_send: true,
+            ..Default::default()
+        };
 
-        group_by_similar_title(incidents, -0.5);
+        publish_review(
+            &publisher,
+            &prompter,
+            &options,
+            &ReviewMessage::Single("msg".to_owned()),
+            &incidents,
+            &InsertCheckpoint::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(publisher.inserted_numbers(), vec![1]);
+        assert!(prompter.questions.borrow()[0].contains("• insert 1 incidents"));
+    }
+
+    fn poc(id: &str) -> User {
+        User::new(
+            None,
+            Some(NotionPerson {
+                object: "user".to_owned(),
+                id: id.to_owned(),
+                name: id.to_owned(),
+                avatar_url: None,
+                person: None,
+            }),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_select_pocs_reprompts_on_empty_selection() {
+        let prompter = FixedPrompter::new(false);
+        prompter
+            .pocs
+            .borrow_mut()
+            .extend([vec![], vec![poc("abc")]]);
+        let output = ReviewOutput::new(false, vec![], vec![]);
+
+        let pocs = select_pocs(&[], false, &prompter, &output).unwrap();
+
+        assert_eq!(pocs.len(), 1);
+        assert_eq!(
+            *prompter.questions.borrow(),
+            vec!["select pocs", "Keep it without any POCs?", "select pocs"]
+        );
+        let printed = String::from_utf8(output.into_inner().1).unwrap();
+        assert!(printed.contains("no POCs were selected"));
+    }
+
+    #[test]
+    fn test_select_pocs_allows_confirmed_empty_selection() {
+        let prompter = FixedPrompter::new(true);
+
+        let pocs = select_pocs(
+            &[],
+            false,
+            &prompter,
+            &ReviewOutput::new(true, vec![], vec![]),
+        )
+        .unwrap();
+
+        assert!(pocs.is_empty());
+        assert_eq!(prompter.questions.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_select_pocs_required_does_not_offer_empty_selection() {
+        let prompter = FixedPrompter::new(true);
+        prompter
+            .pocs
+            .borrow_mut()
+            .extend([vec![], vec![], vec![poc("abc")]]);
+
+        let pocs = select_pocs(
+            &[],
+            true,
+            &prompter,
+            &ReviewOutput::new(true, vec![], vec![]),
+        )
+        .unwrap();
+
+        assert_eq!(pocs.len(), 1);
+        assert_eq!(
+            *prompter.questions.borrow(),
+            vec!["select pocs", "select pocs", "select pocs"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_targets_db_and_channel_independently() {
+        let incidents = vec![incident_with(1, Some("P1"), false)];
+        for db in [Target::Debug, Target::Prod] {
+            for channel in [Target::Debug, Target::Prod] {
+                let publisher = RecordingPublisher::default();
+                let prompter = FixedPrompter::new(true);
+                let options = ReviewOptions {
+                    db_target: Some(db),
+                    channel_target: Some(channel),
+                    ..Default::default()
+                };
+
+                publish_review(
+                    &publisher,
+                    &prompter,
+                    &options,
+                    &ReviewMessage::Single("msg".to_owned()),
+                    &incidents,
+                    &InsertCheckpoint::default(),
+                )
+                .await
+                .unwrap();
+
+                assert_eq!(publisher.sent.borrow()[0].0, channel.review_channel());
+                let questions = prompter.questions.borrow();
+                assert!(questions[0].contains(&format!("#{}", channel.review_channel())));
+                assert!(questions[0].contains(&format!("{:?}", db.incident_db_name())));
+                assert!(questions[0].contains(&db.incident_db_id().to_string()));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_matches_new_slack_user_before_the_message() {
+        let new_hire = User::new(
+            None,
+            Some(NotionPerson {
+                person: Some(NotionPersonDetails {
+                    email: "new.hire@mystenlabs.com".to_owned(),
+                }),
+                ..poc("abc").notion_user.unwrap()
+            }),
+        )
+        .unwrap();
+        let mut incidents = vec![Incident {
+            poc_users: Some(vec![new_hire]),
+            ..incident_with(1, Some("P1"), false)
+        }];
+        let publisher = RecordingPublisher {
+            slack_users: vec![SlackUser {
+                id: "U1".to_owned(),
+                name: "newhire".to_owned(),
+                profile: Some(Profile {
+                    email: Some("new.hire@mystenlabs.com".to_owned()),
+                }),
+            }],
+            ..Default::default()
+        };
+        let prompter = FixedPrompter::new(true);
+        let message = |incidents: &[Incident]| {
+            build_review_message(
+                &ReviewOptions::default(),
+                None,
+                &MessageFrame::default(),
+                NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+                incidents,
+                &[],
+            )
+            .preview()
+        };
+        assert!(!message(&incidents).contains("<@U1>"));
+
+        refresh_unmatched_pocs(&publisher, &prompter, &mut incidents)
+            .await
+            .unwrap();
+
+        assert_eq!(*publisher.refreshes.borrow(), 1);
+        assert!(prompter.questions.borrow()[0].starts_with("1 POCs couldn't be matched"));
+        // the message built afterwards mentions the POC found by the refresh
+        assert!(message(&incidents).contains("<@U1>"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_skips_matched_pocs() {
+        let mut matched = poc("abc");
+        matched.slack_user = Some(SlackUser {
+            id: "U1".to_owned(),
+            name: "jane".to_owned(),
+            profile: None,
+        });
+        let mut incidents = vec![Incident {
+            poc_users: Some(vec![matched]),
+            ..incident_with(1, Some("P1"), false)
+        }];
+        let publisher = RecordingPublisher::default();
+        let prompter = FixedPrompter::new(true);
+
+        refresh_unmatched_pocs(&publisher, &prompter, &mut incidents)
+            .await
+            .unwrap();
+
+        assert_eq!(*publisher.refreshes.borrow(), 0);
+        assert!(prompter.questions.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_unmatched_people_warning_counts_notion_only_users() {
+        let mut matched = poc("abc");
+        matched.slack_user = Some(SlackUser {
+            id: "U1".to_owned(),
+            name: "jane".to_owned(),
+            profile: None,
+        });
+        let users = vec![matched.clone(), poc("def"), poc("ghi")];
+
+        assert_eq!(
+            unmatched_people_warning(&users).as_deref(),
+            Some(
+                "Warning: 2 Notion people couldn't be matched to Slack and won't be mentionable as POCs"
+            )
+        );
+        assert!(unmatched_people_warning(&[matched]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_publish_review_marks_reviewed_only_when_asked() {
+        let incidents = vec![
+            incident_with(1, Some("P1"), false),
+            incident_with(2, Some("P1"), false),
+        ];
+        for mark_reviewed in [false, true] {
+            let publisher = RecordingPublisher::default();
+            let options = ReviewOptions {
+                no_send: true,
+                mark_reviewed,
+                ..Default::default()
+            };
+
+            publish_review(
+                &publisher,
+                &FixedPrompter::new(true),
+                &options,
+                &ReviewMessage::Single("msg".to_owned()),
+                &incidents,
+                &InsertCheckpoint::default(),
+            )
+            .await
+            .unwrap();
+
+            let expected: Vec<u64> = if mark_reviewed { vec![1, 2] } else { vec![] };
+            assert_eq!(*publisher.marked.borrow(), expected);
+        }
     }
 }
@@ -0,0 +1,188 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::create_dir_all;
+use std::path::Path;
+
+use crate::cli::lib::cache::local_cache_dir;
+use crate::cli::lib::cache::{cache_raw, get_cached};
+
+use super::incident::Incident;
+
+/// The cache key the snooze list is stored under.
+const SNOOZE_CACHE_KEY: &str = "incident_snoozes";
+
+/// An incident deferred to the first review on or after `until`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Snoozed {
+    pub incident: Incident,
+    pub until: DateTime<Utc>,
+}
+
+/// The incidents snoozed in earlier reviews.
+///
+/// The whole incident is kept so it comes back even once it has fallen out of
+/// the window of recent incidents.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SnoozeList {
+    pub snoozed: Vec<Snoozed>,
+}
+
+/// What applying the snooze list changed about the incidents up for review.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SnoozeOutcome {
+    /// Incidents left out because they are snoozed until later
+    pub still_snoozed: usize,
+    /// Snoozed incidents that are due and back up for review
+    pub resurfaced: usize,
+}
+
+impl SnoozeList {
+    pub fn load() -> Result<Self> {
+        Self::load_from(&local_cache_dir())
+    }
+
+    /// Load the snooze list from `cache_dir`, which has none until something is snoozed.
+    pub fn load_from(cache_dir: &Path) -> Result<Self> {
+        // the list doesn't expire like other cached values, so the age is ignored
+        Ok(get_cached(SNOOZE_CACHE_KEY, cache_dir)?
+            .map(|cached| cached.value)
+            .unwrap_or_default())
+    }
+
+    pub fn save_to(&self, cache_dir: &Path) -> Result<()> {
+        create_dir_all(cache_dir)?;
+        cache_raw(SNOOZE_CACHE_KEY, serde_json::to_string(self)?, cache_dir)?;
+        Ok(())
+    }
+
+    /// Defer `incident` to the first review on or after `until`.
+    pub fn snooze(&mut self, incident: &Incident, until: DateTime<Utc>) {
+        self.snoozed
+            .retain(|s| s.incident.number != incident.number);
+        self.snoozed.push(Snoozed {
+            incident: incident.clone(),
+            until,
+        });
+    }
+
+    /// Leave out the incidents snoozed until after `now`, and bring back the ones
+    /// that are due, dropping them from the list.
+    pub fn apply(
+        &mut self,
+        incidents: Vec<Incident>,
+        now: DateTime<Utc>,
+    ) -> (Vec<Incident>, SnoozeOutcome) {
+        let (due, waiting): (Vec<Snoozed>, Vec<Snoozed>) = std::mem::take(&mut self.snoozed)
+            .into_iter()
+            .partition(|s| s.until <= now);
+        self.snoozed = waiting;
+
+        let mut outcome = SnoozeOutcome::default();
+        let mut incidents: Vec<Incident> = incidents
+            .into_iter()
+            .filter(|i| {
+                let snoozed = self.snoozed.iter().any(|s| s.incident.number == i.number);
+                outcome.still_snoozed += snoozed as usize;
+                !snoozed
+            })
+            .collect();
+        for snoozed in due {
+            outcome.resurfaced += 1;
+            // prefer the freshly fetched copy if the incident is still recent
+            if !incidents
+                .iter()
+                .any(|i| i.number == snoozed.incident.number)
+            {
+                incidents.push(snoozed.incident);
+            }
+        }
+        (incidents, outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn incident(number: u64) -> Incident {
+        Incident {
+            number,
+            title: format!("Incident {}", number),
+            ..Default::default()
+        }
+    }
+
+    fn numbers(incidents: &[Incident]) -> Vec<u64> {
+        incidents.iter().map(|i| i.number).collect()
+    }
+
+    #[test]
+    fn test_snoozed_incident_is_excluded_until_due() {
+        let now = Utc::now();
+        let mut list = SnoozeList::default();
+        list.snooze(&incident(1), now + Duration::days(7));
+
+        let (incidents, outcome) = list.apply(vec![incident(1), incident(2)], now);
+        assert_eq!(numbers(&incidents), vec![2]);
+        assert_eq!(
+            outcome,
+            SnoozeOutcome {
+                still_snoozed: 1,
+                resurfaced: 0
+            }
+        );
+        assert_eq!(list.snoozed.len(), 1);
+    }
+
+    #[test]
+    fn test_snoozed_incident_resurfaces_after_its_date() {
+        let now = Utc::now();
+        let mut list = SnoozeList::default();
+        list.snooze(&incident(1), now + Duration::days(7));
+
+        // the incident is no longer among the recent ones, but still comes back
+        let (incidents, outcome) = list.apply(vec![incident(2)], now + Duration::days(8));
+        assert_eq!(numbers(&incidents), vec![2, 1]);
+        assert_eq!(outcome.resurfaced, 1);
+        assert!(list.snoozed.is_empty());
+
+        // and isn't duplicated when it is
+        list.snooze(&incident(1), now);
+        let (incidents, _) = list.apply(vec![incident(1)], now);
+        assert_eq!(numbers(&incidents), vec![1]);
+    }
+
+    #[test]
+    fn test_snooze_replaces_earlier_snooze() {
+        let now = Utc::now();
+        let mut list = SnoozeList::default();
+        list.snooze(&incident(1), now);
+        list.snooze(&incident(1), now + Duration::days(7));
+        assert_eq!(list.snoozed.len(), 1);
+        assert_eq!(list.snoozed[0].until, now + Duration::days(7));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(SnoozeList::load_from(dir.path())
+            .unwrap()
+            .snoozed
+            .is_empty());
+
+        let until = Utc::now() + Duration::days(7);
+        let mut list = SnoozeList::default();
+        list.snooze(&incident(1), until);
+        list.save_to(dir.path()).unwrap();
+
+        let loaded = SnoozeList::load_from(dir.path()).unwrap();
+        assert_eq!(loaded.snoozed.len(), 1);
+        assert_eq!(loaded.snoozed[0].incident.number, 1);
+        assert_eq!(loaded.snoozed[0].until, until);
+    }
+}
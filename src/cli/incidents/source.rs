@@ -0,0 +1,32 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+
+use super::incident::Incident;
+
+/// A system incidents are fetched from.
+pub(crate) trait IncidentSource {
+    /// Fetch up to `limit` incidents resolved between `start_time` and `end_time`.
+    async fn fetch_recent(
+        &self,
+        limit: usize,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+    ) -> Result<Vec<Incident>>;
+
+    /// Look up the incident's current status in the source.
+    ///
+    /// Sources that can't look incidents up again report the status it was fetched with.
+    async fn fetch_status(&self, incident: &Incident) -> Result<Option<String>> {
+        Ok(incident.status.clone())
+    }
+
+    /// Record in the source that the incident has been scheduled for review.
+    ///
+    /// Sources with nowhere to record this do nothing.
+    async fn mark_reviewed(&self, _incident: &Incident) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::str::FromStr;
+
+use crate::cli::notion::ids::DatabaseId;
+
+/// Which set of destinations the review results are published to.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// the test Notion database and Slack channel
+    Debug,
+    /// the production Notion database and Slack channel
+    Prod,
+}
+
+impl Target {
+    /// The explicitly chosen target, falling back to debug when in debug mode.
+    pub fn resolve(flag: Option<Target>, debug_mode: bool) -> Target {
+        flag.unwrap_or(if debug_mode {
+            Target::Debug
+        } else {
+            Target::Prod
+        })
+    }
+
+    /// The id of the incident selection database
+    pub fn incident_db_id(self) -> DatabaseId {
+        match self {
+            Target::Debug => DatabaseId::from_str("10e6d9dcb4e980f8ae73c4aa2da176cd"),
+            Target::Prod => DatabaseId::from_str("a8da55dadb524e7db202b4dfd799d9ce"),
+        }
+        .expect("Invalid Database ID")
+    }
+
+    /// The name of the incident selection database
+    pub fn incident_db_name(self) -> &'static str {
+        match self {
+            Target::Debug => "Incident Selection (Debug)",
+            Target::Prod => "Incident Selection",
+        }
+    }
+
+    /// The slack channel the review message is sent to
+    pub fn review_channel(self) -> &'static str {
+        match self {
+            Target::Debug => "test-notifications",
+            Target::Prod => "incident-postmortems",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_defaults_to_debug_mode() {
+        assert_eq!(Target::resolve(None, true), Target::Debug);
+        assert_eq!(Target::resolve(None, false), Target::Prod);
+    }
+
+    #[test]
+    fn test_resolve_flag_overrides_debug_mode() {
+        for debug_mode in [true, false] {
+            assert_eq!(
+                Target::resolve(Some(Target::Debug), debug_mode),
+                Target::Debug
+            );
+            assert_eq!(
+                Target::resolve(Some(Target::Prod), debug_mode),
+                Target::Prod
+            );
+        }
+    }
+
+    #[test]
+    fn test_targets_have_distinct_destinations() {
+        assert_ne!(
+            Target::Debug.incident_db_id(),
+            Target::Prod.incident_db_id()
+        );
+        assert_eq!(Target::Debug.review_channel(), "test-notifications");
+        assert_eq!(Target::Prod.review_channel(), "incident-postmortems");
+    }
+}
@@ -3,9 +3,13 @@
 
 use std::fmt::{Display, Formatter};
 
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
 
+use crate::cli::lib::email::emails_match;
 use crate::cli::slack::SlackUser;
+use crate::DEBUG_MODE;
 
 use super::notion::NotionPerson;
 
@@ -38,6 +42,90 @@ impl User {
         }
         presence.join(" & ")
     }
+
+    /// Whether the user could be matched to a Slack user.
+    pub fn has_slack_user(&self) -> bool {
+        self.slack_user.is_some()
+    }
+
+    /// How the user is named in a Slack message: a mention that notifies them if
+    /// they have a Slack user, otherwise their Notion name.
+    pub fn mention(&self) -> String {
+        match (&self.slack_user, &self.notion_user) {
+            (Some(slack_user), _) if !slack_user.id.is_empty() => format!("<@{}>", slack_user.id),
+            (_, Some(notion_user)) => notion_user.name.clone(),
+            (Some(slack_user), None) => slack_user.name.clone(),
+            (None, None) => String::new(),
+        }
+    }
+
+    /// The systems the user is missing from that a POC needs: Slack to be mentioned
+    /// and Notion, with an id, to be set on the incident's page.
+    pub fn missing_links(&self) -> Vec<&'static str> {
+        let mut missing = vec![];
+        if !self.has_slack_user() {
+            missing.push("Slack");
+        }
+        if self
+            .notion_user
+            .as_ref()
+            .is_none_or(|u| u.id.trim().is_empty())
+        {
+            missing.push("Notion");
+        }
+        missing
+    }
+
+    /// Fill in the Slack user sharing this user's Notion email, if it's missing and
+    /// one exists in `slack_users`. Returns whether the user now has a Slack user.
+    pub fn match_slack_user(&mut self, slack_users: &[SlackUser]) -> bool {
+        if self.slack_user.is_none() {
+            let email = self
+                .notion_user
+                .as_ref()
+                .and_then(|u| u.person.as_ref())
+                .map(|p| p.email.as_str());
+            if let Some(email) = email {
+                self.slack_user = slack_users
+                    .iter()
+                    .find(|su| {
+                        su.profile
+                            .as_ref()
+                            .and_then(|p| p.email.as_deref())
+                            .is_some_and(|slack_email| emails_match(email, slack_email))
+                    })
+                    .cloned();
+            }
+        }
+        self.has_slack_user()
+    }
+
+    /// The text the POC picker filters on: the Notion name, the Slack handle and
+    /// both emails, lowercased.
+    pub fn search_key(&self) -> String {
+        let slack = self.slack_user.as_ref();
+        let notion = self.notion_user.as_ref();
+        [
+            notion.map(|u| u.name.as_str()),
+            slack.map(|u| u.name.as_str()),
+            slack
+                .and_then(|u| u.profile.as_ref())
+                .and_then(|p| p.email.as_deref()),
+            notion
+                .and_then(|u| u.person.as_ref())
+                .map(|p| p.email.as_str()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+    }
+
+    /// Whether a free-text query typed into the picker matches this user.
+    pub fn matches_search(&self, query: &str) -> bool {
+        self.search_key().contains(&query.trim().to_lowercase())
+    }
 }
 
 impl Display for User {
@@ -71,3 +159,288 @@ impl Display for User {
         }
     }
 }
+
+/// How a Notion person was matched to a Slack user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum MatchMethod {
+    /// Their emails are the same
+    EmailExact,
+    /// Their names are similar
+    NameFuzzy,
+    None,
+}
+
+/// How similar names have to be to match, from 0 to 1.
+const NAME_MATCH_THRESHOLD: f64 = 0.85;
+
+/// A name lowercased with everything but letters and digits dropped, so
+/// "Jane Doe" and "jane.doe" compare equal.
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// How similar a Notion person's name is to a Slack user, comparing it with both
+/// their handle and the local part of their email.
+fn name_similarity(notion_name: &str, slack_user: &SlackUser) -> f64 {
+    let notion_name = normalize_name(notion_name);
+    if notion_name.is_empty() {
+        return 0.0;
+    }
+    let email_local = slack_user
+        .profile
+        .as_ref()
+        .and_then(|p| p.email.as_deref())
+        .and_then(|e| e.split('@').next());
+    [Some(slack_user.name.as_str()), email_local]
+        .into_iter()
+        .flatten()
+        .map(|name| strsim::jaro_winkler(&notion_name, &normalize_name(name)))
+        .fold(0.0, f64::max)
+}
+
+/// Find the Slack user for a Notion person, by email or failing that by name,
+/// along with how they were matched and the confidence in the match from 0 to 1.
+pub(crate) fn match_person<'a>(
+    person: &NotionPerson,
+    slack_users: &'a [SlackUser],
+) -> (Option<&'a SlackUser>, MatchMethod, f64) {
+    if let Some(email) = person.person.as_ref().map(|p| p.email.as_str()) {
+        let by_email = slack_users.iter().find(|su| {
+            su.profile
+                .as_ref()
+                .and_then(|p| p.email.as_deref())
+                .is_some_and(|slack_email| emails_match(email, slack_email))
+        });
+        if let Some(slack_user) = by_email {
+            return (Some(slack_user), MatchMethod::EmailExact, 1.0);
+        }
+    }
+    slack_users
+        .iter()
+        .map(|su| (su, name_similarity(&person.name, su)))
+        .filter(|(_, score)| *score >= NAME_MATCH_THRESHOLD)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or((None, MatchMethod::None, 0.0), |(su, score)| {
+            (Some(su), MatchMethod::NameFuzzy, score)
+        })
+}
+
+/// Check the Slack user directory is whole before POCs are matched against it.
+///
+/// A directory that is missing pages would leave people unmatched for no visible
+/// reason, so it is an error unless `allow_partial` is set, when it only warns.
+pub(crate) fn check_slack_directory(
+    incomplete: Option<&str>,
+    fetched: usize,
+    allow_partial: bool,
+) -> Result<()> {
+    let Some(reason) = incomplete else {
+        return Ok(());
+    };
+    if !allow_partial {
+        return Err(anyhow!(
+            "only {} Slack users could be fetched ({}); rerun, or pass --allow-partial-users to match POCs against them anyway",
+            fetched,
+            reason
+        ));
+    }
+    warn!(
+        "Matching POCs against only {} Slack users, the rest couldn't be fetched: {}",
+        fetched, reason
+    );
+    Ok(())
+}
+
+/// Pair each Notion person with the Slack user sharing their email, if any.
+///
+/// A Notion person without an id can't be set as a POC, so it only becomes a user
+/// through its Slack match; without one it is skipped with a warning.
+pub(crate) fn combine_users(
+    notion_people: Vec<NotionPerson>,
+    slack_users: &[SlackUser],
+) -> Vec<User> {
+    notion_people
+        .into_iter()
+        .filter_map(|nu| {
+            let notion_email = nu.person.as_ref().map(|p| &p.email);
+            let slack_user = if let Some(email) = notion_email {
+                slack_users.iter().find(|su| {
+                    if let Some(profile) = &su.profile {
+                        if let Some(slack_email) = &profile.email {
+                            if *DEBUG_MODE {
+                                debug!(
+                                    "Comparing emails - Notion: '{}', Slack: '{}'",
+                                    email, slack_email
+                                );
+                                let matches = emails_match(email, slack_email);
+                                if matches {
+                                    debug!("Email match found!");
+                                }
+                                matches
+                            } else {
+                                emails_match(email, slack_email)
+                            }
+                        } else {
+                            if *DEBUG_MODE {
+                                debug!("Slack user {} has no email", su.name);
+                            }
+                            false
+                        }
+                    } else {
+                        if *DEBUG_MODE {
+                            debug!("Slack user {} has no profile", su.name);
+                        }
+                        false
+                    }
+                })
+            } else {
+                if *DEBUG_MODE {
+                    debug!("Notion user {} has no email", nu.name);
+                }
+                None
+            };
+
+            let (id, name) = (nu.id.clone(), nu.name.clone());
+            let notion_user = Some(nu).filter(|nu| !nu.id.trim().is_empty());
+            let Some(user) = User::new(slack_user.cloned(), notion_user) else {
+                warn!(
+                    "Skipping Notion person {:?} (id {:?}): it has no id and no matching Slack user",
+                    name, id
+                );
+                return None;
+            };
+
+            if *DEBUG_MODE {
+                debug!("Created user: {} [{}]", user, user.system_presence());
+            }
+
+            Some(user)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::incidents::notion::NotionPersonDetails;
+    use crate::cli::slack::Profile;
+
+    fn user(handle: &str, slack_email: &str, name: &str, notion_email: &str) -> User {
+        User::new(
+            Some(SlackUser {
+                id: format!("U-{}", handle),
+                name: handle.to_owned(),
+                profile: Some(Profile {
+                    email: Some(slack_email.to_owned()),
+                }),
+            }),
+            Some(NotionPerson {
+                object: "user".to_owned(),
+                id: format!("N-{}", handle),
+                name: name.to_owned(),
+                avatar_url: None,
+                person: Some(NotionPersonDetails {
+                    email: notion_email.to_owned(),
+                }),
+            }),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_partial_slack_directory() {
+        assert!(check_slack_directory(None, 10, false).is_ok());
+        let err = check_slack_directory(Some("connection reset"), 10, false).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("only 10 Slack users could be fetched (connection reset)"));
+        // allowed through with only a warning
+        assert!(check_slack_directory(Some("connection reset"), 10, true).is_ok());
+    }
+
+    #[test]
+    fn test_combine_users_skips_person_without_id() {
+        let jane = user(
+            "jdoe",
+            "jane@mystenlabs.com",
+            "Jane Doe",
+            "jane@mystenlabs.com",
+        );
+        let ghost = NotionPerson {
+            object: "user".to_owned(),
+            id: "".to_owned(),
+            name: "Ghost".to_owned(),
+            avatar_url: None,
+            person: None,
+        };
+        let combined = combine_users(
+            vec![jane.notion_user.clone().unwrap(), ghost],
+            &[jane.slack_user.clone().unwrap()],
+        );
+        assert_eq!(combined.len(), 1);
+        assert_eq!(combined[0].notion_user.as_ref().unwrap().id, "N-jdoe");
+        assert!(combined[0].has_slack_user());
+    }
+
+    #[test]
+    fn test_partial_email_matches_user() {
+        let users = [
+            user(
+                "jdoe",
+                "jane@mystenlabs.com",
+                "Jane Doe",
+                "jane.doe@mystenlabs.com",
+            ),
+            user(
+                "jsmith",
+                "john@mystenlabs.com",
+                "John Smith",
+                "jsmith@mystenlabs.com",
+            ),
+        ];
+
+        let matches = |query: &str| {
+            users
+                .iter()
+                .filter(|u| u.matches_search(query))
+                .map(|u| u.slack_user.as_ref().unwrap().name.clone())
+                .collect::<Vec<_>>()
+        };
+        // only in jane's notion email
+        assert_eq!(matches("jane.do"), vec!["jdoe"]);
+        // only in john's slack email
+        assert_eq!(matches("John@Mysten"), vec!["jsmith"]);
+        assert_eq!(matches("smith"), vec!["jsmith"]);
+        assert_eq!(matches("mystenlabs"), vec!["jdoe", "jsmith"]);
+        assert!(matches("nobody").is_empty());
+    }
+
+    #[test]
+    fn test_poc_mentions_fall_back_to_names() {
+        use crate::cli::incidents::incident::Incident;
+
+        let mut notion_only = user("jsmith", "", "John Smith", "john@mystenlabs.com");
+        notion_only.slack_user = None;
+        let incident = Incident {
+            number: 1,
+            poc_users: Some(vec![
+                user(
+                    "jdoe",
+                    "jane@mystenlabs.com",
+                    "Jane Doe",
+                    "jane@mystenlabs.com",
+                ),
+                notion_only,
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            incident.render_line(Some("{pocs}")),
+            "<@U-jdoe>, John Smith"
+        );
+    }
+}
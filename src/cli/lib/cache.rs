@@ -3,16 +3,77 @@
 
 use std::{
     fs::{create_dir_all, Metadata},
-    path::{Path, PathBuf},
+    io::{Read, Write},
+    marker::PhantomData,
+    path::{Component, Path, PathBuf},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use crate::LOCAL_CACHE_DIR;
 
+/// How long a cached value stays fresh, in seconds.
+pub const CACHE_TTL_SECS: u64 = 86400;
+
+/// How long a cached "not found" stays fresh, in seconds.
+pub const TOMBSTONE_TTL_SECS: u64 = 3600;
+
+/// What is written to a cache file to remember that its value doesn't exist upstream.
+const TOMBSTONE: &str = r#"{"suiop_cache_tombstone":true}"#;
+
+/// Appended to a cache file's name while it's being written.
+const TEMPORARY_SUFFIX: &str = ".tmp";
+
+/// The namespace Slack data is cached under.
+pub const SLACK_NAMESPACE: &str = "slack";
+
+/// The namespace Notion data is cached under.
+pub const NOTION_NAMESPACE: &str = "notion";
+
+/// Files kept in the local cache directory by other commands, which aren't cached
+/// values: the Okta token and the pulumi setup marker.
+const NOT_CACHED: &[&str] = &["okta_token.json", "pulumi_setup"];
+
+/// The local cache directory, `~/.suiop`, shared by every data source so it's the
+/// same wherever suiop is run from.
+pub fn local_cache_dir() -> PathBuf {
+    dirs::home_dir()
+        .expect("HOME env var not set")
+        .join(LOCAL_CACHE_DIR)
+}
+
+/// The local cache directory of a data source, so its keys can't collide with
+/// another source's.
+///
+/// Fails unless `namespace` is a single directory name, so it can't point outside
+/// the cache.
+pub fn namespace_dir(namespace: &str) -> Result<PathBuf> {
+    let mut components = Path::new(namespace).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(local_cache_dir().join(namespace)),
+        _ => Err(anyhow!("{:?} isn't a cache namespace", namespace)),
+    }
+}
+
+/// Returned by the `get_cached*` functions when the key was cached as not found,
+/// so callers can tell it apart from a miss and skip fetching it again.
+#[derive(Debug, thiserror::Error)]
+#[error("{key} is cached as not found")]
+pub struct Tombstone {
+    pub key: String,
+}
+
+/// Whether a `get_cached*` error is an unexpired tombstone rather than a miss.
+pub fn is_tombstone(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<Tombstone>().is_some()
+}
+
 /// A generic cache for values that take time to compute.
+#[derive(Debug)]
 pub struct CacheResult<T> {
     pub value: T,
     pub metadata: Metadata,
@@ -28,14 +89,233 @@ impl<T> CacheResult<T> {
         }
     }
 
+    /// Whether the value is older than [`CACHE_TTL_SECS`].
+    ///
+    /// A value modified in the future, as after the clock was set back, is fresh,
+    /// and one whose modified time is unknown is expired.
     pub fn is_expired(&self) -> bool {
-        self.metadata
-            .modified()
-            .unwrap()
-            .elapsed()
-            .unwrap()
-            .as_secs()
-            > 86400
+        match self.metadata.modified() {
+            Ok(modified) => modified
+                .elapsed()
+                .is_ok_and(|age| age.as_secs() > CACHE_TTL_SECS),
+            Err(_) => true,
+        }
+    }
+}
+
+/// Details about a single entry in a cache directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheEntryInfo {
+    pub key: String,
+    /// Size of the cached value in bytes
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+    /// Seconds since the entry was last written
+    pub age_secs: u64,
+    pub expired: bool,
+}
+
+/// List the entries in a cache directory, including those of its namespaces,
+/// which are keyed like `slack/users`.
+///
+/// A missing cache directory has no entries.
+pub fn list_cached(cache_dir: &Path) -> Result<Vec<CacheEntryInfo>> {
+    let mut entries = vec![];
+    list_cached_into(cache_dir, "", &mut entries)?;
+    Ok(entries)
+}
+
+fn list_cached_into(dir: &Path, prefix: &str, entries: &mut Vec<CacheEntryInfo>) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("reading cache dir {}", dir.display()))?
+    {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let key = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if metadata.is_dir() {
+            list_cached_into(&entry.path(), &format!("{}/", key), entries)?;
+            continue;
+        }
+        // skip the leftovers of writes that were interrupted
+        if !metadata.is_file() || key.ends_with(TEMPORARY_SUFFIX) || is_not_cached(&entry.path()) {
+            continue;
+        }
+        let modified = metadata.modified()?;
+        let age_secs = modified.elapsed().map_or(0, |age| age.as_secs());
+        entries.push(CacheEntryInfo {
+            key,
+            size: metadata.len(),
+            modified: modified.into(),
+            age_secs,
+            expired: age_secs > CACHE_TTL_SECS,
+        });
+    }
+    Ok(())
+}
+
+pub fn list_cached_local() -> Result<Vec<CacheEntryInfo>> {
+    list_cached(&local_cache_dir())
+}
+
+/// The file `key` is cached in, failing unless the key is a relative path that
+/// stays inside `cache_dir`.
+fn cache_file(key: &str, cache_dir: &Path) -> Result<PathBuf> {
+    let path = Path::new(key);
+    let inside = path.components().next().is_some()
+        && path.components().all(|c| matches!(c, Component::Normal(_)));
+    if !inside {
+        return Err(anyhow!("{:?} isn't a cache key", key));
+    }
+    Ok(cache_dir.join(path))
+}
+
+/// Drop the cached value of `key`, so it's fetched again. A key that isn't cached
+/// is left alone.
+pub fn invalidate(key: &str, cache_dir: &Path) -> Result<()> {
+    let cache_file = cache_file(key, cache_dir)?;
+    match std::fs::remove_file(&cache_file) {
+        Ok(()) => {
+            debug!("Invalidated cached value for key: {}", key);
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("removing {}", cache_file.display())),
+    }
+}
+
+pub fn invalidate_local(key: &str) -> Result<()> {
+    invalidate(key, &local_cache_dir())
+}
+
+/// Whether `path` is one of the [`NOT_CACHED`] files rather than a cached value.
+fn is_not_cached(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| NOT_CACHED.contains(&name))
+}
+
+/// Remove every file in a cache directory and its namespaces, returning how many
+/// were removed.
+///
+/// A missing cache directory has nothing to remove.
+pub fn clear_cache(cache_dir: &Path) -> Result<usize> {
+    if !cache_dir.exists() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for entry in std::fs::read_dir(cache_dir)
+        .with_context(|| format!("reading cache dir {}", cache_dir.display()))?
+    {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            removed += clear_cache(&entry.path())?;
+            continue;
+        }
+        if !metadata.is_file() || is_not_cached(&entry.path()) {
+            continue;
+        }
+        std::fs::remove_file(entry.path())
+            .with_context(|| format!("removing {}", entry.path().display()))?;
+        removed += 1;
+    }
+    debug!("Removed {} cached values", removed);
+    Ok(removed)
+}
+
+/// Clear the local cache, or only one namespace of it.
+pub fn clear_local_cache(namespace: Option<&str>) -> Result<usize> {
+    match namespace {
+        Some(namespace) => clear_cache(&namespace_dir(namespace)?),
+        None => clear_cache(&local_cache_dir()),
+    }
+}
+
+/// Where a cache file is written before it's moved into place.
+fn temporary_path(cache_file: &Path) -> PathBuf {
+    let mut temporary = cache_file.as_os_str().to_owned();
+    temporary.push(TEMPORARY_SUFFIX);
+    PathBuf::from(temporary)
+}
+
+/// Write `contents` to a temporary file next to `cache_file` and rename it into
+/// place, so an interrupted run leaves the previous value rather than a truncated one.
+fn write_atomically(cache_file: &Path, contents: &[u8]) -> Result<()> {
+    let temporary = temporary_path(cache_file);
+    if let Err(e) = std::fs::write(&temporary, contents) {
+        let _ = std::fs::remove_file(&temporary);
+        return Err(e).with_context(|| format!("writing {}", temporary.display()));
+    }
+    std::fs::rename(&temporary, cache_file)
+        .with_context(|| format!("moving {} into place", temporary.display()))
+}
+
+/// How values are written to their cache files and read back.
+pub trait CacheCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>>;
+    fn decode<T: for<'a> Deserialize<'a>>(bytes: &[u8]) -> Result<T>;
+}
+
+/// Caches values as JSON, which every `cache*` function without a codec uses.
+pub struct JsonCodec;
+
+impl CacheCodec for JsonCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn decode<T: for<'a> Deserialize<'a>>(bytes: &[u8]) -> Result<T> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// Caches values as bincode, which is smaller and faster to read than JSON for
+/// large values like the Slack users.
+///
+/// bincode isn't self-describing, so it only suits types that always serialize
+/// every field, without `skip_serializing_if`, `flatten` or untagged enums.
+pub struct BincodeCodec;
+
+impl CacheCodec for BincodeCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode<T: for<'a> Deserialize<'a>>(bytes: &[u8]) -> Result<T> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// Written in front of a compressed cache file, so it can be told apart from a
+/// plain one.
+const COMPRESSED_MAGIC: &[u8] = b"SUIOPGZ1";
+
+/// Gzip compresses what the codec `C` encodes, for large values like the Slack
+/// users and Notion people that are slow to read as plain JSON.
+///
+/// A file without the [`COMPRESSED_MAGIC`] header is decoded as plain `C`, so the
+/// cache files written before compression was turned on stay readable.
+pub struct Compressed<C>(PhantomData<C>);
+
+impl<C: CacheCodec> CacheCodec for Compressed<C> {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(COMPRESSED_MAGIC.to_vec(), Compression::default());
+        encoder.write_all(&C::encode(value)?)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn decode<T: for<'a> Deserialize<'a>>(bytes: &[u8]) -> Result<T> {
+        let Some(compressed) = bytes.strip_prefix(COMPRESSED_MAGIC) else {
+            return C::decode(bytes);
+        };
+        let mut decoded = vec![];
+        GzDecoder::new(compressed)
+            .read_to_end(&mut decoded)
+            .context("decompressing a cached value")?;
+        C::decode(&decoded)
     }
 }
 
@@ -44,60 +324,507 @@ pub fn cache<T: Serialize + for<'a> Deserialize<'a>>(
     value: T,
     cache_dir: &Path,
 ) -> Result<T> {
-    let cache_file = cache_dir.join(key);
-    std::fs::write(cache_file, serde_json::to_string(&value)?)?;
+    cache_with::<JsonCodec, T>(key, value, cache_dir)
+}
+
+/// Cache a value encoded with the codec `C`.
+pub fn cache_with<C: CacheCodec, T: Serialize + for<'a> Deserialize<'a>>(
+    key: &str,
+    value: T,
+    cache_dir: &Path,
+) -> Result<T> {
+    let cache_file = cache_file(key, cache_dir)?;
+    write_atomically(&cache_file, &C::encode(&value)?)?;
     debug!("Cached value for key: {}", key);
     Ok(value)
 }
 
 pub fn cache_raw<T: AsRef<[u8]>>(key: &str, value: T, cache_dir: &Path) -> Result<T> {
-    let cache_file = cache_dir.join(key);
-    std::fs::write(cache_file, value.as_ref())?;
+    let cache_file = cache_file(key, cache_dir)?;
+    write_atomically(&cache_file, value.as_ref())?;
     debug!("Cached value for key: {}", key);
     Ok(value)
 }
 
+/// Remember that `key` doesn't exist upstream, for [`TOMBSTONE_TTL_SECS`].
+pub fn cache_tombstone(key: &str, cache_dir: &Path) -> Result<()> {
+    cache_raw(key, TOMBSTONE, cache_dir)?;
+    Ok(())
+}
+
+pub fn cache_local_tombstone(key: &str) -> Result<()> {
+    create_dir_all(local_cache_dir())?;
+    cache_tombstone(key, &local_cache_dir())
+}
+
+/// Whether `value` is a cached value rather than an expired tombstone, failing
+/// with a [`Tombstone`] if it's a fresh one.
+fn check_tombstone(key: &str, value: &[u8], metadata: &Metadata) -> Result<bool> {
+    if value != TOMBSTONE.as_bytes() {
+        return Ok(true);
+    }
+    let age_secs = metadata
+        .modified()?
+        .elapsed()
+        .map_or(0, |age| age.as_secs());
+    if age_secs > TOMBSTONE_TTL_SECS {
+        debug!("Tombstone for key {} has expired", key);
+        Ok(false)
+    } else {
+        Err(Tombstone {
+            key: key.to_owned(),
+        }
+        .into())
+    }
+}
+
+/// Read the cache file of `key`, or `None` if there is none, it can't be read
+/// or it holds an expired tombstone.
+fn read_cache_file(key: &str, cache_dir: &Path) -> Result<Option<CacheResult<Vec<u8>>>> {
+    let cache_file = cache_file(key, cache_dir)?;
+    debug!("cache_file: {}", cache_file.display());
+    let read =
+        std::fs::read(&cache_file).and_then(|value| Ok((value, std::fs::metadata(&cache_file)?)));
+    let (value, metadata) = match read {
+        Ok(read) => read,
+        Err(e) => {
+            debug!("No cached value for key {}: {}", key, e);
+            return Ok(None);
+        }
+    };
+    if !check_tombstone(key, &value, &metadata)? {
+        return Ok(None);
+    }
+    debug!("Retrieved cached value for key: {}", key);
+    Ok(Some(CacheResult::new(value, metadata, cache_file)))
+}
+
 pub fn cache_local<T: Serialize + for<'a> Deserialize<'a>>(key: &str, value: T) -> Result<T> {
-    create_dir_all(Path::new(LOCAL_CACHE_DIR))?;
-    cache(key, value, Path::new(LOCAL_CACHE_DIR))
+    cache_local_with::<JsonCodec, T>(key, value)
+}
+
+pub fn cache_local_with<C: CacheCodec, T: Serialize + for<'a> Deserialize<'a>>(
+    key: &str,
+    value: T,
+) -> Result<T> {
+    create_dir_all(local_cache_dir())?;
+    cache_with::<C, T>(key, value, &local_cache_dir())
+}
+
+/// Cache a value as gzip compressed JSON, for [`get_cached_local_compressed`].
+pub fn cache_local_compressed<T: Serialize + for<'a> Deserialize<'a>>(
+    key: &str,
+    value: T,
+) -> Result<T> {
+    cache_local_with::<Compressed<JsonCodec>, T>(key, value)
+}
+
+/// Cache a value under a namespace of the local cache, creating it if needed.
+pub fn cache_local_namespaced<T: Serialize + for<'a> Deserialize<'a>>(
+    namespace: &str,
+    key: &str,
+    value: T,
+) -> Result<T> {
+    let cache_dir = namespace_dir(namespace)?;
+    create_dir_all(&cache_dir)?;
+    cache(key, value, &cache_dir)
 }
 
 pub fn cache_local_raw<T: AsRef<[u8]>>(key: &str, value: T) -> Result<T> {
-    create_dir_all(Path::new(LOCAL_CACHE_DIR))?;
-    cache_raw(key, value, Path::new(LOCAL_CACHE_DIR))
+    create_dir_all(local_cache_dir())?;
+    cache_raw(key, value, &local_cache_dir())
 }
 
+/// The cached value of `key`, or `None` on a miss.
+///
+/// A value that can't be deserialized, say because the cached type has changed
+/// since it was written, is deleted and treated as a miss so it's fetched again.
+/// Fails with a [`Tombstone`] if the key is cached as not found.
 pub fn get_cached<T: for<'a> Deserialize<'a>>(
     key: &str,
     cache_dir: &Path,
-) -> Result<CacheResult<T>> {
-    let cache_file = cache_dir.join(key);
-    debug!("cache_file: {}", cache_file.display());
-    let value = std::fs::read_to_string(&cache_file)?;
-    debug!("Retrieved cached value for key: {}", key);
-    Ok(CacheResult::new(
-        serde_json::from_str(&value)?,
-        std::fs::metadata(&cache_file)?,
-        cache_file,
-    ))
+) -> Result<Option<CacheResult<T>>> {
+    get_cached_with::<JsonCodec, T>(key, cache_dir)
 }
 
-pub fn get_cached_raw(key: &str, cache_dir: &Path) -> Result<CacheResult<String>> {
-    let cache_file = cache_dir.join(key);
-    debug!("cache_file: {}", cache_file.display());
-    let value = std::fs::read_to_string(&cache_file)?;
-    debug!("Retrieved cached value for key: {}", key);
-    Ok(CacheResult::new(
-        value,
-        std::fs::metadata(&cache_file)?,
-        cache_file,
-    ))
+/// The cached value of `key` decoded with the codec `C`, like [`get_cached`].
+pub fn get_cached_with<C: CacheCodec, T: for<'a> Deserialize<'a>>(
+    key: &str,
+    cache_dir: &Path,
+) -> Result<Option<CacheResult<T>>> {
+    let Some(cached) = read_cache_file(key, cache_dir)? else {
+        return Ok(None);
+    };
+    match C::decode(&cached.value) {
+        Ok(value) => Ok(Some(CacheResult::new(value, cached.metadata, cached.path))),
+        Err(e) => {
+            debug!("Deleting the corrupt cached value for key {}: {}", key, e);
+            if let Err(e) = std::fs::remove_file(&cached.path) {
+                debug!("Couldn't delete {}: {}", cached.path.display(), e);
+            }
+            Ok(None)
+        }
+    }
 }
 
-pub fn get_cached_local<T: for<'a> Deserialize<'a>>(key: &str) -> Result<CacheResult<T>> {
-    get_cached(key, Path::new(LOCAL_CACHE_DIR))
+/// The cached contents of `key` as they were written, or `None` on a miss.
+/// Contents that aren't UTF-8 are a miss.
+pub fn get_cached_raw(key: &str, cache_dir: &Path) -> Result<Option<CacheResult<String>>> {
+    let Some(cached) = read_cache_file(key, cache_dir)? else {
+        return Ok(None);
+    };
+    match String::from_utf8(cached.value) {
+        Ok(value) => Ok(Some(CacheResult::new(value, cached.metadata, cached.path))),
+        Err(e) => {
+            debug!("The cached value for key {} isn't text: {}", key, e);
+            Ok(None)
+        }
+    }
+}
+
+pub fn get_cached_local<T: for<'a> Deserialize<'a>>(key: &str) -> Result<Option<CacheResult<T>>> {
+    get_cached(key, &local_cache_dir())
+}
+
+pub fn get_cached_local_with<C: CacheCodec, T: for<'a> Deserialize<'a>>(
+    key: &str,
+) -> Result<Option<CacheResult<T>>> {
+    get_cached_with::<C, T>(key, &local_cache_dir())
 }
 
-pub fn get_cached_local_raw(key: &str) -> Result<CacheResult<String>> {
-    get_cached_raw(key, Path::new(LOCAL_CACHE_DIR))
+/// The cached value of `key`, whether it was written compressed or as plain JSON.
+pub fn get_cached_local_compressed<T: for<'a> Deserialize<'a>>(
+    key: &str,
+) -> Result<Option<CacheResult<T>>> {
+    get_cached_local_with::<Compressed<JsonCodec>, T>(key)
+}
+
+pub fn get_cached_local_namespaced<T: for<'a> Deserialize<'a>>(
+    namespace: &str,
+    key: &str,
+) -> Result<Option<CacheResult<T>>> {
+    get_cached(key, &namespace_dir(namespace)?)
+}
+
+pub fn get_cached_local_raw(key: &str) -> Result<Option<CacheResult<String>>> {
+    get_cached_raw(key, &local_cache_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_list_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        cache("fresh", vec!["a".to_string()], dir.path()).unwrap();
+        cache_raw("stale", "0123456789", dir.path()).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(dir.path().join("stale"))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(CACHE_TTL_SECS + 60))
+            .unwrap();
+        create_dir_all(dir.path().join("nested")).unwrap();
+
+        let mut entries = list_cached(dir.path()).unwrap();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+
+        assert_eq!(
+            entries.iter().map(|e| e.key.as_str()).collect::<Vec<_>>(),
+            vec!["fresh", "stale"]
+        );
+        assert_eq!(entries[0].size, r#"["a"]"#.len() as u64);
+        assert!(!entries[0].expired);
+        assert_eq!(entries[1].size, 10);
+        assert!(entries[1].expired);
+        assert!(entries[1].age_secs > CACHE_TTL_SECS);
+    }
+
+    #[test]
+    fn test_tombstone_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(get_cached::<String>("removed-user", dir.path())
+            .unwrap()
+            .is_none());
+
+        cache_tombstone("removed-user", dir.path()).unwrap();
+        let err = get_cached::<String>("removed-user", dir.path()).unwrap_err();
+        assert!(is_tombstone(&err), "{}", err);
+        assert!(is_tombstone(
+            &get_cached_raw("removed-user", dir.path()).unwrap_err()
+        ));
+
+        // once expired the tombstone is a plain miss
+        std::fs::File::options()
+            .write(true)
+            .open(dir.path().join("removed-user"))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(TOMBSTONE_TTL_SECS + 60))
+            .unwrap();
+        assert!(get_cached::<String>("removed-user", dir.path())
+            .unwrap()
+            .is_none());
+
+        // and caching a real value replaces it
+        cache("removed-user", "back again".to_string(), dir.path()).unwrap();
+        assert_eq!(
+            get_cached::<String>("removed-user", dir.path())
+                .unwrap()
+                .unwrap()
+                .value,
+            "back again"
+        );
+    }
+
+    #[test]
+    fn test_failed_write_keeps_previous_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let large: Vec<String> = (0..10_000).map(|n| format!("user-{}", n)).collect();
+        cache("users", large.clone(), dir.path()).unwrap();
+        assert!(!temporary_path(&dir.path().join("users")).exists());
+
+        // a directory in the way makes the write to the temporary path fail
+        create_dir_all(temporary_path(&dir.path().join("users"))).unwrap();
+        cache("users", vec!["only-one".to_string()], dir.path()).unwrap_err();
+
+        let cached = get_cached::<Vec<String>>("users", dir.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.value, large);
+        let keys: Vec<String> = list_cached(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|e| e.key)
+            .collect();
+        assert_eq!(keys, vec!["users"]);
+    }
+
+    #[test]
+    fn test_corrupt_value_is_a_miss() {
+        let dir = tempfile::tempdir().unwrap();
+        cache_raw("channels", r#"[{"id": "C1", "na"#, dir.path()).unwrap();
+        // the raw contents are still there for whoever wants them
+        assert!(get_cached_raw("channels", dir.path()).unwrap().is_some());
+
+        assert!(get_cached::<Vec<String>>("channels", dir.path())
+            .unwrap()
+            .is_none());
+        assert!(!dir.path().join("channels").exists());
+        assert!(get_cached_raw("channels", dir.path()).unwrap().is_none());
+
+        // a value of another shape is as good as corrupt
+        cache("channels", 42, dir.path()).unwrap();
+        assert!(get_cached::<Vec<String>>("channels", dir.path())
+            .unwrap()
+            .is_none());
+        assert!(!dir.path().join("channels").exists());
+    }
+
+    #[test]
+    fn test_clear_and_list_leave_the_files_of_other_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        cache("users", vec!["U1".to_string()], dir.path()).unwrap();
+        std::fs::write(dir.path().join("okta_token.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("pulumi_setup"), "").unwrap();
+
+        let keys: Vec<String> = list_cached(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|e| e.key)
+            .collect();
+        assert_eq!(keys, vec!["users"]);
+        assert_eq!(clear_cache(dir.path()).unwrap(), 1);
+        assert!(dir.path().join("okta_token.json").exists());
+        assert!(dir.path().join("pulumi_setup").exists());
+    }
+
+    #[test]
+    fn test_invalidate_and_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        cache("channels", vec!["C1".to_string()], dir.path()).unwrap();
+        cache("users", vec!["U1".to_string()], dir.path()).unwrap();
+        cache_tombstone("removed-user", dir.path()).unwrap();
+        create_dir_all(dir.path().join("nested")).unwrap();
+
+        invalidate("channels", dir.path()).unwrap();
+        assert!(get_cached::<Vec<String>>("channels", dir.path())
+            .unwrap()
+            .is_none());
+        // invalidating a key that isn't cached is fine
+        invalidate("channels", dir.path()).unwrap();
+        // but one outside the cache is refused
+        for key in ["/etc/passwd", "../../.ssh/id_ed25519", "slack/../../x", ""] {
+            assert!(invalidate(key, dir.path()).is_err(), "{:?}", key);
+        }
+
+        assert_eq!(clear_cache(dir.path()).unwrap(), 2);
+        assert!(list_cached(dir.path()).unwrap().is_empty());
+        assert!(dir.path().join("nested").exists());
+        assert_eq!(clear_cache(&dir.path().join("missing")).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_namespaces_keep_keys_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let (slack, notion) = (dir.path().join("slack"), dir.path().join("notion"));
+        create_dir_all(&slack).unwrap();
+        create_dir_all(&notion).unwrap();
+        cache("users", vec!["U1".to_string()], &slack).unwrap();
+        cache("users", vec!["N1".to_string()], &notion).unwrap();
+        cache("snoozed", vec![42], dir.path()).unwrap();
+
+        let users = |dir: &Path| {
+            get_cached::<Vec<String>>("users", dir)
+                .unwrap()
+                .unwrap()
+                .value
+        };
+        assert_eq!(users(&slack), vec!["U1"]);
+        assert_eq!(users(&notion), vec!["N1"]);
+        let mut keys: Vec<String> = list_cached(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|e| e.key)
+            .collect();
+        keys.sort();
+        assert_eq!(keys, vec!["notion/users", "slack/users", "snoozed"]);
+
+        // clearing one namespace leaves the others
+        assert_eq!(clear_cache(&slack).unwrap(), 1);
+        assert_eq!(users(&notion), vec!["N1"]);
+        assert_eq!(clear_cache(dir.path()).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_is_expired_with_skewed_clock() {
+        let dir = tempfile::tempdir().unwrap();
+        cache("users", vec!["U1".to_string()], dir.path()).unwrap();
+        let set_modified = |time: SystemTime| {
+            std::fs::File::options()
+                .write(true)
+                .open(dir.path().join("users"))
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+        };
+        let cached = || {
+            get_cached::<Vec<String>>("users", dir.path())
+                .unwrap()
+                .unwrap()
+        };
+
+        // modified after now, as when the clock was just set back
+        set_modified(SystemTime::now() + Duration::from_secs(3600));
+        assert!(!cached().is_expired());
+        set_modified(SystemTime::now() - Duration::from_secs(CACHE_TTL_SECS + 60));
+        assert!(cached().is_expired());
+    }
+
+    /// JSON written backwards, so it can't be read without the codec.
+    struct ReversedJson;
+
+    impl CacheCodec for ReversedJson {
+        fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+            let mut bytes = JsonCodec::encode(value)?;
+            bytes.reverse();
+            Ok(bytes)
+        }
+
+        fn decode<T: for<'a> Deserialize<'a>>(bytes: &[u8]) -> Result<T> {
+            let mut bytes = bytes.to_vec();
+            bytes.reverse();
+            JsonCodec::decode(&bytes)
+        }
+    }
+
+    #[test]
+    fn test_cache_with_codec() {
+        let dir = tempfile::tempdir().unwrap();
+        cache_with::<ReversedJson, _>("users", vec!["U1".to_string()], dir.path()).unwrap();
+        let cached = get_cached_with::<ReversedJson, Vec<String>>("users", dir.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.value, vec!["U1"]);
+        assert_eq!(
+            get_cached_raw("users", dir.path()).unwrap().unwrap().value,
+            r#"]"1U"["#
+        );
+        // read with the wrong codec it's as good as corrupt
+        assert!(get_cached::<Vec<String>>("users", dir.path())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_bincode_is_smaller_than_json() {
+        use crate::cli::slack::{Profile, SlackUser};
+
+        let users: Vec<SlackUser> = (0..1000)
+            .map(|i| SlackUser {
+                id: format!("U{:08}", i),
+                name: format!("user{}", i),
+                profile: Some(Profile {
+                    email: Some(format!("user{}@mystenlabs.com", i)),
+                }),
+            })
+            .collect();
+        let json = JsonCodec::encode(&users).unwrap().len();
+        let bincode = BincodeCodec::encode(&users).unwrap().len();
+        println!(
+            "1000 Slack users: {} bytes as JSON, {} as bincode",
+            json, bincode
+        );
+        // about a fifth smaller, mostly from the field names JSON repeats per user
+        assert!(bincode * 100 < json * 85, "{} vs {}", bincode, json);
+
+        let dir = tempfile::tempdir().unwrap();
+        cache_with::<BincodeCodec, _>("users", users.clone(), dir.path()).unwrap();
+        let cached = get_cached_with::<BincodeCodec, Vec<SlackUser>>("users", dir.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.value.len(), 1000);
+        assert_eq!(cached.value[999].id, users[999].id);
+        assert_eq!(
+            cached.value[999].profile.as_ref().unwrap().email,
+            users[999].profile.as_ref().unwrap().email
+        );
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        // about 1MB of JSON
+        let blob: Vec<String> = (0..40_000)
+            .map(|i| format!("user{:06}@mystenlabs.com", i))
+            .collect();
+        let plain = JsonCodec::encode(&blob).unwrap().len();
+        assert!(plain > 1_000_000, "{}", plain);
+
+        cache_with::<Compressed<JsonCodec>, _>("people", blob.clone(), dir.path()).unwrap();
+        let written = std::fs::read(dir.path().join("people")).unwrap();
+        assert!(written.starts_with(COMPRESSED_MAGIC));
+        assert!(written.len() < plain / 4, "{} vs {}", written.len(), plain);
+        let cached = get_cached_with::<Compressed<JsonCodec>, Vec<String>>("people", dir.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.value, blob);
+    }
+
+    #[test]
+    fn test_compressed_reads_plain_files() {
+        let dir = tempfile::tempdir().unwrap();
+        cache("users", vec!["U1".to_string()], dir.path()).unwrap();
+        let cached = get_cached_with::<Compressed<JsonCodec>, Vec<String>>("users", dir.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.value, vec!["U1"]);
+    }
+
+    #[test]
+    fn test_list_cached_missing_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(list_cached(&dir.path().join("missing")).unwrap().is_empty());
+    }
 }
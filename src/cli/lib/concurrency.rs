@@ -0,0 +1,103 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{anyhow, Result};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use once_cell::sync::OnceCell;
+use std::future::Future;
+
+/// How many requests of a batch operation run at once unless `--concurrency` says otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 5;
+
+static CONCURRENCY: OnceCell<usize> = OnceCell::new();
+
+/// Parse a `--concurrency` value, which has to be at least 1.
+pub fn parse_concurrency(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("concurrency must be at least 1".to_owned()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Set the concurrency used by batch operations. It can only be set once.
+pub fn set_concurrency(limit: usize) -> Result<()> {
+    if limit == 0 {
+        return Err(anyhow!("concurrency must be at least 1"));
+    }
+    CONCURRENCY
+        .set(limit)
+        .map_err(|_| anyhow!("concurrency is already set"))
+}
+
+/// The concurrency used by batch operations.
+pub fn concurrency() -> usize {
+    *CONCURRENCY.get().unwrap_or(&DEFAULT_CONCURRENCY)
+}
+
+/// Run `f` on every item, with at most `limit` calls in flight at once.
+///
+/// Stops at the first error, dropping the calls still in flight.
+pub async fn try_for_each_bounded<T, F, Fut>(
+    items: impl IntoIterator<Item = T>,
+    limit: usize,
+    f: F,
+) -> Result<()>
+where
+    F: FnMut(T) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    stream::iter(items)
+        .map(f)
+        .buffer_unordered(limit.max(1))
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_concurrency() {
+        assert_eq!(parse_concurrency("3"), Ok(3));
+        assert!(parse_concurrency("0").is_err());
+        assert!(parse_concurrency("-1").is_err());
+        assert!(parse_concurrency("lots").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_try_for_each_bounded_respects_limit() {
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let done = AtomicUsize::new(0);
+        try_for_each_bounded(0..20, 3, |_| async {
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            done.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(done.load(Ordering::SeqCst), 20);
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_try_for_each_bounded_stops_on_error() {
+        let err = try_for_each_bounded(0..5, 2, |i| async move {
+            if i == 1 {
+                Err(anyhow!("failed on {}", i))
+            } else {
+                Ok(())
+            }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "failed on 1");
+    }
+}
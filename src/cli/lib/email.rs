@@ -0,0 +1,30 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+/// Normalizes an email address for comparison by converting to lowercase and trimming whitespace
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Normalizes an email address and drops any plus-addressing tag from the local part,
+/// so `Jane.Doe+oncall@example.com` becomes `jane.doe@example.com`.
+pub fn canonicalize_email(email: &str) -> String {
+    let normalized = normalize_email(email);
+    match normalized.split_once('@') {
+        Some((local, domain)) => {
+            let local = local.split_once('+').map_or(local, |(base, _)| base);
+            format!("{}@{}", local, domain)
+        }
+        None => normalized,
+    }
+}
+
+/// Compares two email addresses after normalization
+pub fn emails_match(email1: &str, email2: &str) -> bool {
+    normalize_email(email1) == normalize_email(email2)
+}
+
+/// Compares two email addresses after canonicalization, ignoring plus-addressing tags
+pub fn emails_match_canonical(email1: &str, email2: &str) -> bool {
+    canonicalize_email(email1) == canonicalize_email(email2)
+}
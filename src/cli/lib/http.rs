@@ -0,0 +1,117 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use reqwest::{Client, ClientBuilder, NoProxy, Proxy};
+use tracing::debug;
+
+/// Proxy settings for outgoing HTTP requests, for operators behind corporate proxies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxyConfig {
+    /// The proxy for plain http requests, from `HTTP_PROXY`
+    pub http_proxy: Option<String>,
+    /// The proxy for https requests, from `HTTPS_PROXY`
+    pub https_proxy: Option<String>,
+    /// Comma separated hosts that bypass the proxies, from `NO_PROXY`
+    pub no_proxy: Option<String>,
+}
+
+impl ProxyConfig {
+    pub fn from_env() -> Self {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Read the settings with `lookup`, accepting both the upper and lower case
+    /// spelling of each variable.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name: &str| {
+            lookup(name)
+                .or_else(|| lookup(&name.to_lowercase()))
+                .filter(|value| !value.is_empty())
+        };
+        Self {
+            http_proxy: var("HTTP_PROXY"),
+            https_proxy: var("HTTPS_PROXY"),
+            no_proxy: var("NO_PROXY"),
+        }
+    }
+
+    /// Configure the proxies on a client builder.
+    pub fn apply(&self, mut builder: ClientBuilder) -> reqwest::Result<ClientBuilder> {
+        let no_proxy = || self.no_proxy.as_deref().and_then(NoProxy::from_string);
+        if let Some(url) = &self.http_proxy {
+            debug!("using http proxy {}", url);
+            builder = builder.proxy(Proxy::http(url)?.no_proxy(no_proxy()));
+        }
+        if let Some(url) = &self.https_proxy {
+            debug!("using https proxy {}", url);
+            builder = builder.proxy(Proxy::https(url)?.no_proxy(no_proxy()));
+        }
+        Ok(builder)
+    }
+}
+
+/// A client builder with the proxies from the environment applied.
+pub fn http_client_builder() -> reqwest::Result<ClientBuilder> {
+    ProxyConfig::from_env().apply(Client::builder())
+}
+
+/// A client with the proxies from the environment applied.
+pub fn build_http_client() -> reqwest::Result<Client> {
+    http_client_builder()?.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_proxy_config_from_lookup() {
+        let config = ProxyConfig::from_lookup(lookup(&[
+            ("https_proxy", "http://proxy.corp:3128"),
+            ("NO_PROXY", "localhost,.internal"),
+            ("HTTP_PROXY", ""),
+        ]));
+        assert_eq!(
+            config,
+            ProxyConfig {
+                http_proxy: None,
+                https_proxy: Some("http://proxy.corp:3128".to_owned()),
+                no_proxy: Some("localhost,.internal".to_owned()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_configured_proxy_is_used() {
+        let mut server = mockito::Server::new_async().await;
+        let proxied = server
+            .mock("GET", "/api/ping")
+            .with_body("pong")
+            .create_async()
+            .await;
+        let config = ProxyConfig::from_lookup(lookup(&[("HTTP_PROXY", &server.url())]));
+
+        // the host doesn't resolve, so the request only succeeds through the proxy
+        let client = config.apply(Client::builder()).unwrap().build().unwrap();
+        let body = client
+            .get("http://slack.invalid/api/ping")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+
+        assert_eq!(body, "pong");
+        proxied.assert_async().await;
+    }
+}
@@ -3,8 +3,12 @@
 
 mod autocomplete;
 pub mod cache;
+pub mod concurrency;
+pub mod email;
 pub mod gcp;
+pub mod http;
 mod oauth;
+pub mod rate_limit;
 
 pub use autocomplete::FilePathCompleter;
 pub use oauth::get_oauth_token;
@@ -1,6 +1,7 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+mod cache;
 pub mod ci;
 pub mod docker;
 mod env;
@@ -8,14 +9,18 @@ mod iam;
 mod incidents;
 pub mod lib;
 mod notion;
+mod people;
 pub mod pulumi;
 pub mod service;
 mod slack;
 
+pub use cache::{cache_cmd, CacheArgs};
 pub use ci::{ci_cmd, CIArgs};
 pub use docker::{docker_cmd, DockerArgs};
 pub use env::{load_environment, LoadEnvironmentArgs};
 pub use iam::{iam_cmd, IAMArgs};
 pub use incidents::{incidents_cmd, IncidentsArgs};
+pub use people::{people_cmd, PeopleArgs};
 pub use pulumi::{pulumi_cmd, PulumiArgs};
 pub use service::{service_cmd, ServiceArgs};
+pub use slack::{slack_cmd, SlackArgs};
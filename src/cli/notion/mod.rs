@@ -7,20 +7,50 @@ use ids::{AsIdentifier, PageId};
 use ids::{BlockId, DatabaseId};
 use models::block::Block;
 use models::error::ErrorResponse;
+use models::paging::PagingCursor;
 use models::search::{DatabaseQuery, SearchRequest};
 use models::PageCreateRequest;
 use models::{Database, ListResponse, Object, Page};
 use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::{header, Client, ClientBuilder, RequestBuilder};
+use reqwest::{header, Client, RequestBuilder};
 use tracing::Instrument;
 
+use crate::cli::lib::http::http_client_builder;
+
 pub mod ids;
 pub mod models;
 
 #[allow(unused_imports)]
 pub use chrono;
 
-const NOTION_API_VERSION: &str = "2022-02-22";
+/// The Notion-Version sent with every request unless overridden with `NOTION_VERSION`
+pub const NOTION_API_VERSION: &str = "2022-06-28";
+
+/// The Notion-Version to send: `override_version` if given and it looks like a date
+/// (e.g. "2022-06-28"), otherwise [`NOTION_API_VERSION`].
+#[allow(clippy::result_large_err)]
+pub fn parse_notion_version(override_version: Option<&str>) -> Result<String, Error> {
+    match override_version {
+        None => Ok(NOTION_API_VERSION.to_owned()),
+        Some(version) => {
+            let looks_like_date = version.len() == 10
+                && chrono::NaiveDate::parse_from_str(version, "%Y-%m-%d").is_ok();
+            if looks_like_date {
+                Ok(version.to_owned())
+            } else {
+                Err(Error::InvalidVersion {
+                    version: version.to_owned(),
+                })
+            }
+        }
+    }
+}
+
+/// The Notion-Version to send, read from the `NOTION_VERSION` env var.
+#[allow(clippy::result_large_err)]
+pub fn notion_version() -> Result<String, Error> {
+    parse_notion_version(std::env::var("NOTION_VERSION").ok().as_deref())
+}
 
 /// An wrapper Error type for all errors produced by the [`NotionApi`](NotionApi) client.
 #[derive(Debug, thiserror::Error)]
@@ -29,6 +59,13 @@ pub enum Error {
     #[error("Invalid Notion API Token: {}", source)]
     InvalidApiToken { source: header::InvalidHeaderValue },
 
+    #[error(
+        "Invalid Notion-Version {:?}, expected a date like {:?}",
+        version,
+        NOTION_API_VERSION
+    )]
+    InvalidVersion { version: String },
+
     #[error("Unable to build reqwest HTTP client: {}", source)]
     ErrorBuildingClient { source: reqwest::Error },
 
@@ -64,9 +101,10 @@ impl NotionApi {
     #[allow(clippy::result_large_err)]
     pub fn new(api_token: String) -> Result<Self, Error> {
         let mut headers = HeaderMap::new();
+        let version = notion_version()?;
         headers.insert(
             "Notion-Version",
-            HeaderValue::from_static(NOTION_API_VERSION),
+            HeaderValue::from_str(&version).map_err(|_| Error::InvalidVersion { version })?,
         );
 
         let mut auth_value = HeaderValue::from_str(&format!("Bearer {}", api_token))
@@ -74,7 +112,8 @@ impl NotionApi {
         auth_value.set_sensitive(true);
         headers.insert(header::AUTHORIZATION, auth_value);
 
-        let client = ClientBuilder::new()
+        let client = http_client_builder()
+            .map_err(|source| Error::ErrorBuildingClient { source })?
             .default_headers(headers)
             .build()
             .map_err(|source| Error::ErrorBuildingClient { source })?;
@@ -228,12 +267,23 @@ impl NotionApi {
         &self,
         block_id: T,
     ) -> Result<ListResponse<Block>, Error> {
-        let result = self
-            .make_json_request(self.client.get(format!(
-                "https://api.notion.com/v1/blocks/{block_id}/children",
-                block_id = block_id.as_id()
-            )))
-            .await?;
+        self.get_block_children_from(block_id, None).await
+    }
+
+    /// Get a page of the children of a block, starting at `start_cursor` when given.
+    pub async fn get_block_children_from<T: AsIdentifier<BlockId>>(
+        &self,
+        block_id: T,
+        start_cursor: Option<PagingCursor>,
+    ) -> Result<ListResponse<Block>, Error> {
+        let mut request = self.client.get(format!(
+            "https://api.notion.com/v1/blocks/{block_id}/children",
+            block_id = block_id.as_id()
+        ));
+        if let Some(cursor) = start_cursor {
+            request = request.query(&[("start_cursor", cursor)]);
+        }
+        let result = self.make_json_request(request).await?;
 
         match result {
             Object::List { list } => Ok(list.expect_blocks()?),
@@ -465,6 +465,22 @@ impl AsIdentifier<BlockId> for Block {
     }
 }
 
+impl Block {
+    /// The plain text of paragraph and bulleted list item blocks.
+    ///
+    /// Returns `None` for any other block type.
+    pub fn plain_text(&self) -> Option<String> {
+        let rich_text = match self {
+            Block::Paragraph { paragraph, .. } => &paragraph.rich_text,
+            Block::BulletedListItem {
+                bulleted_list_item, ..
+            } => &bulleted_list_item.rich_text,
+            _ => return None,
+        };
+        Some(rich_text.iter().map(|t| t.plain_text()).collect())
+    }
+}
+
 impl From<Block> for CreateBlock {
     fn from(val: Block) -> Self {
         match val {
@@ -302,4 +302,26 @@ mod test {
             }
         )
     }
+
+    #[test]
+    fn block_children_plain_text() {
+        let children: Object =
+            serde_json::from_str(include_str!("tests/block_children.json")).unwrap();
+        let blocks = match children {
+            Object::List { list } => list.expect_blocks().unwrap(),
+            other => panic!("expected a list, got {:?}", other),
+        };
+        assert_eq!(blocks.results.len(), 3);
+        assert_eq!(
+            blocks
+                .results
+                .iter()
+                .filter_map(Block::plain_text)
+                .collect::<Vec<_>>(),
+            vec![
+                "Validators stopped producing checkpoints".to_string(),
+                "Rolled back to v1.20.1".to_string(),
+            ]
+        );
+    }
 }
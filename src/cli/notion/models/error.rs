@@ -1,9 +1,24 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use once_cell::sync::Lazy;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
+/// Patterns Notion uses to name the offending property in validation error messages.
+static PROPERTY_PATTERNS: Lazy<Vec<Regex>> = Lazy::new(|| {
+    [
+        r"body\.properties\.(.+?)(?:\.[a-z_]+|\[\d+\])* should ",
+        r"^Could not find property with name or id: (.+)$",
+        r"^(.+?) is not a property that exists",
+        r"^(.+?) is expected to be ",
+    ]
+    .iter()
+    .map(|p| Regex::new(p).expect("invalid property pattern"))
+    .collect()
+});
+
 #[derive(Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Hash)]
 #[serde(transparent)]
 pub struct StatusCode(u16);
@@ -29,6 +44,21 @@ pub struct ErrorResponse {
     pub message: String,
 }
 
+impl ErrorResponse {
+    /// The name of the property a validation error is about, when Notion names one.
+    pub fn property(&self) -> Option<String> {
+        if self.code != ErrorCode::ValidationError {
+            return None;
+        }
+        PROPERTY_PATTERNS.iter().find_map(|pattern| {
+            pattern
+                .captures(&self.message)
+                .and_then(|c| c.get(1))
+                .map(|m| m.as_str().to_owned())
+        })
+    }
+}
+
 /// <https://developers.notion.com/reference/errors>
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
 #[serde(rename_all = "snake_case")]
@@ -71,4 +101,22 @@ mod tests {
             serde_json::from_str(include_str!("tests/unknown_error.json")).unwrap();
         assert_eq!(error.code, ErrorCode::Unknown)
     }
+
+    #[test]
+    fn validation_error_property() {
+        let error: ErrorResponse =
+            serde_json::from_str(include_str!("tests/validation_error.json")).unwrap();
+        assert_eq!(error.code, ErrorCode::ValidationError);
+        assert_eq!(error.property().as_deref(), Some("PoC(s)"));
+
+        let error: ErrorResponse = serde_json::from_str(include_str!("tests/error.json")).unwrap();
+        assert_eq!(error.property().as_deref(), Some("LastEditedTime"));
+    }
+
+    #[test]
+    fn non_validation_error_has_no_property() {
+        let error: ErrorResponse =
+            serde_json::from_str(include_str!("tests/unknown_error.json")).unwrap();
+        assert_eq!(error.property(), None);
+    }
 }
@@ -265,6 +265,7 @@ pub enum FormulaCondition {
 #[serde(rename_all = "snake_case")]
 #[allow(unused)]
 pub enum PropertyCondition {
+    Title(TextCondition),
     RichText(TextCondition),
     Number(NumberCondition),
     Checkbox(CheckboxCondition),
@@ -399,7 +400,7 @@ impl From<NotionSearch> for SearchRequest {
 mod tests {
     mod text_filters {
         use crate::cli::notion::models::search::PropertyCondition::{
-            Checkbox, Number, RichText, Select,
+            Checkbox, Number, RichText, Select, Title,
         };
         use crate::cli::notion::models::search::{
             CheckboxCondition, FilterCondition, NumberCondition, SelectCondition, TextCondition,
@@ -434,6 +435,20 @@ mod tests {
             Ok(())
         }
 
+        #[test]
+        fn title_property_starts_with() -> Result<(), Box<dyn std::error::Error>> {
+            let json = serde_json::to_value(&FilterCondition::Property {
+                property: "Name".to_string(),
+                condition: Title(TextCondition::StartsWith("42: ".to_string())),
+            })?;
+            assert_eq!(
+                dbg!(json),
+                json!({"property":"Name","title":{"starts_with":"42: "}})
+            );
+
+            Ok(())
+        }
+
         #[test]
         fn text_property_is_empty() -> Result<(), Box<dyn std::error::Error>> {
             let json = serde_json::to_value(&FilterCondition::Property {
@@ -0,0 +1,305 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use tracing::info;
+
+use crate::cli::incidents::notion::Notion;
+use crate::cli::incidents::notion::NotionPerson;
+use crate::cli::incidents::target::Target;
+use crate::cli::incidents::user::{combine_users, match_person, MatchMethod, User};
+use crate::cli::slack::Slack;
+use crate::cli::slack::SlackUser;
+use crate::DEBUG_MODE;
+
+#[derive(Parser, Debug, Clone)]
+pub struct PeopleArgs {
+    #[command(subcommand)]
+    action: PeopleAction,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum PeopleAction {
+    /// export the combined Slack and Notion user directory
+    #[command(name = "export", aliases=["e"])]
+    Export {
+        /// the format to write
+        #[arg(long, value_enum, default_value = "json")]
+        format: ExportFormat,
+        /// the file to write to [default: stdout]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// write a CSV of how each Notion person was matched to a Slack user
+    #[command(name = "reconcile")]
+    Reconcile {
+        /// the file to write to [default: stdout]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+/// One person in the exported directory.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+struct UserRow {
+    name: String,
+    slack_id: Option<String>,
+    slack_email: Option<String>,
+    notion_id: Option<String>,
+    notion_email: Option<String>,
+    system_presence: String,
+}
+
+impl From<&User> for UserRow {
+    fn from(user: &User) -> Self {
+        let slack = user.slack_user.as_ref();
+        let notion = user.notion_user.as_ref();
+        Self {
+            name: notion
+                .map(|u| u.name.clone())
+                .or_else(|| slack.map(|u| u.name.clone()))
+                .unwrap_or_default(),
+            slack_id: slack.map(|u| u.id.clone()),
+            slack_email: slack
+                .and_then(|u| u.profile.as_ref())
+                .and_then(|p| p.email.clone()),
+            notion_id: notion.map(|u| u.id.clone()),
+            notion_email: notion
+                .and_then(|u| u.person.as_ref())
+                .map(|p| p.email.clone()),
+            system_presence: user.system_presence(),
+        }
+    }
+}
+
+/// How one Notion person was matched to a Slack user, for directory admins to fix
+/// mismatches with.
+#[derive(Debug, Serialize, PartialEq)]
+struct MatchRow {
+    notion_id: String,
+    notion_name: String,
+    notion_email: Option<String>,
+    slack_id: Option<String>,
+    slack_name: Option<String>,
+    slack_email: Option<String>,
+    method: MatchMethod,
+    /// From 0 to 1, rounded to two decimals
+    confidence: f64,
+}
+
+fn match_rows(notion_people: &[NotionPerson], slack_users: &[SlackUser]) -> Vec<MatchRow> {
+    notion_people
+        .iter()
+        .map(|person| {
+            let (slack, method, confidence) = match_person(person, slack_users);
+            MatchRow {
+                notion_id: person.id.clone(),
+                notion_name: person.name.clone(),
+                notion_email: person.person.as_ref().map(|p| p.email.clone()),
+                slack_id: slack.map(|u| u.id.clone()),
+                slack_name: slack.map(|u| u.name.clone()),
+                slack_email: slack
+                    .and_then(|u| u.profile.as_ref())
+                    .and_then(|p| p.email.clone()),
+                method,
+                confidence: (confidence * 100.0).round() / 100.0,
+            }
+        })
+        .collect()
+}
+
+fn write_csv<T: Serialize>(rows: &[T], out: impl Write) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    for row in rows {
+        writer.serialize(row).context("writing csv row")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_rows(rows: &[UserRow], format: ExportFormat, out: impl Write) -> Result<()> {
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(out, rows).context("writing json")?;
+        }
+        ExportFormat::Csv => write_csv(rows, out)?,
+    }
+    Ok(())
+}
+
+pub async fn people_cmd(args: &PeopleArgs) -> Result<()> {
+    match &args.action {
+        PeopleAction::Export { format, output } => {
+            let slack = Slack::new().await?;
+            let notion = Notion::new(Target::resolve(None, *DEBUG_MODE));
+            let users = combine_users(notion.get_all_people().await?, &slack.users);
+            let rows: Vec<UserRow> = users.iter().map(UserRow::from).collect();
+            match output {
+                Some(path) => {
+                    let file = File::create(path)
+                        .with_context(|| format!("creating {}", path.display()))?;
+                    write_rows(&rows, *format, file)?;
+                    info!("Exported {} people to {}", rows.len(), path.display());
+                }
+                None => write_rows(&rows, *format, std::io::stdout())?,
+            }
+        }
+        PeopleAction::Reconcile { output } => {
+            let slack = Slack::new().await?;
+            let notion = Notion::new(Target::resolve(None, *DEBUG_MODE));
+            let rows = match_rows(&notion.get_all_people().await?, &slack.users);
+            match output {
+                Some(path) => {
+                    let file = File::create(path)
+                        .with_context(|| format!("creating {}", path.display()))?;
+                    write_csv(&rows, file)?;
+                    info!("Wrote {} matches to {}", rows.len(), path.display());
+                }
+                None => write_csv(&rows, std::io::stdout())?,
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::incidents::notion::{NotionPerson, NotionPersonDetails};
+    use crate::cli::slack::{Profile, SlackUser};
+
+    fn notion_person(id: &str, name: &str, email: Option<&str>) -> NotionPerson {
+        NotionPerson {
+            object: "user".to_owned(),
+            id: id.to_owned(),
+            name: name.to_owned(),
+            avatar_url: None,
+            person: email.map(|e| NotionPersonDetails {
+                email: e.to_owned(),
+            }),
+        }
+    }
+
+    fn slack_user(id: &str, name: &str, email: &str) -> SlackUser {
+        SlackUser {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            profile: Some(Profile {
+                email: Some(email.to_owned()),
+            }),
+        }
+    }
+
+    fn mixed_rows() -> Vec<UserRow> {
+        let users = combine_users(
+            vec![
+                notion_person("N1", "Jane Doe", Some("jane@mystenlabs.com")),
+                notion_person("N2", "Bot", None),
+            ],
+            &[
+                slack_user("U1", "jane", "Jane@mystenlabs.com"),
+                slack_user("U2", "john", "john@mystenlabs.com"),
+            ],
+        );
+        users.iter().map(UserRow::from).collect()
+    }
+
+    #[test]
+    fn test_export_rows_for_mixed_users() {
+        assert_eq!(
+            mixed_rows(),
+            vec![
+                UserRow {
+                    name: "Jane Doe".to_owned(),
+                    slack_id: Some("U1".to_owned()),
+                    slack_email: Some("Jane@mystenlabs.com".to_owned()),
+                    notion_id: Some("N1".to_owned()),
+                    notion_email: Some("jane@mystenlabs.com".to_owned()),
+                    system_presence: "Slack & Notion".to_owned(),
+                },
+                UserRow {
+                    name: "Bot".to_owned(),
+                    slack_id: None,
+                    slack_email: None,
+                    notion_id: Some("N2".to_owned()),
+                    notion_email: None,
+                    system_presence: "Notion".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_rows_csv() {
+        let mut out = vec![];
+        write_rows(&mixed_rows(), ExportFormat::Csv, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name,slack_id,slack_email,notion_id,notion_email,system_presence\n\
+             Jane Doe,U1,Jane@mystenlabs.com,N1,jane@mystenlabs.com,Slack & Notion\n\
+             Bot,,,N2,,Notion\n"
+        );
+    }
+
+    #[test]
+    fn test_write_rows_json() {
+        let mut out = vec![];
+        write_rows(&mixed_rows(), ExportFormat::Json, &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[0]["slack_id"], "U1");
+        assert_eq!(json[1]["slack_id"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_match_rows_methods_and_confidence() {
+        let rows = match_rows(
+            &[
+                notion_person("N1", "Jane Doe", Some("jane@mystenlabs.com")),
+                notion_person("N2", "Jon Smith", Some("jon@example.com")),
+                notion_person("N3", "Bot", None),
+            ],
+            &[
+                slack_user("U1", "jane", "Jane@mystenlabs.com"),
+                slack_user("U2", "jsmith", "john.smith@mystenlabs.com"),
+            ],
+        );
+        let columns: Vec<_> = rows
+            .iter()
+            .map(|r| {
+                (
+                    r.notion_id.as_str(),
+                    r.slack_id.as_deref(),
+                    r.method,
+                    r.confidence,
+                )
+            })
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("N1", Some("U1"), MatchMethod::EmailExact, 1.0),
+                ("N2", Some("U2"), MatchMethod::NameFuzzy, 0.97),
+                ("N3", None, MatchMethod::None, 0.0),
+            ]
+        );
+
+        let mut out = vec![];
+        write_csv(&rows[1..2], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "notion_id,notion_name,notion_email,slack_id,slack_name,slack_email,method,confidence\n\
+             N2,Jon Smith,jon@example.com,U2,jsmith,john.smith@mystenlabs.com,name-fuzzy,0.97\n"
+        );
+    }
+}
@@ -67,18 +67,11 @@ fn get_pulumi_namespace_from_cmd(stack: &str) -> String {
 
 fn get_pulumi_namespace(project_name: &str) -> String {
     let stack = get_pulumi_stack(project_name);
-    let cached_ns = get_cached_local::<String>(&get_ns_cache_key(&stack));
-
-    cached_ns
-        .map(|ca| {
-            // check if the cached entry is older than 1 day, if so, refresh it
-            if ca.is_expired() {
-                get_pulumi_namespace_from_cmd(&stack)
-            } else {
-                ca.value
-            }
-        })
-        .unwrap_or_else(|_| get_pulumi_namespace_from_cmd(&stack))
+    match get_cached_local::<String>(&get_ns_cache_key(&stack)) {
+        // refresh the cached entry once it is older than 1 day
+        Ok(Some(ca)) if !ca.is_expired() => ca.value,
+        _ => get_pulumi_namespace_from_cmd(&stack),
+    }
 }
 
 fn find_workspace_file(project_name: &str) -> PathBuf {
@@ -113,15 +106,12 @@ fn find_workspace_file(project_name: &str) -> PathBuf {
 }
 
 fn get_pulumi_stack(project_name: &str) -> String {
-    let workspace_file = get_cached_local::<String>(PULUMI_WORKSPACE_FILE_CACHE_KEY)
-        .map(|cached_workspace_file| {
-            if cached_workspace_file.is_expired() {
-                find_workspace_file(project_name)
-            } else {
-                PathBuf::from(cached_workspace_file.value)
-            }
-        })
-        .unwrap_or_else(|_| find_workspace_file(project_name));
+    let workspace_file = match get_cached_local::<String>(PULUMI_WORKSPACE_FILE_CACHE_KEY) {
+        Ok(Some(cached_workspace_file)) if !cached_workspace_file.is_expired() => {
+            PathBuf::from(cached_workspace_file.value)
+        }
+        _ => find_workspace_file(project_name),
+    };
     let contents = std::fs::read_to_string(workspace_file).expect("Failed to read workspace file");
     let json: serde_json::Value =
         serde_json::from_str(&contents).expect("Failed to parse workspace file as JSON");
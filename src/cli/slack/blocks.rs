@@ -0,0 +1,106 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde_json::{json, Value};
+
+/// Builds the `blocks` of a Block Kit message.
+#[derive(Debug, Default, Clone)]
+pub struct Blocks {
+    blocks: Vec<Value>,
+}
+
+/// Slack rejects a section whose text is longer than this.
+const MAX_SECTION_TEXT: usize = 3000;
+
+fn mrkdwn(text: &str) -> Value {
+    let text = match text.char_indices().nth(MAX_SECTION_TEXT - 1) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_owned(),
+    };
+    json!({ "type": "mrkdwn", "text": text })
+}
+
+impl Blocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A paragraph of mrkdwn text.
+    pub fn section(mut self, text: &str) -> Self {
+        self.blocks
+            .push(json!({ "type": "section", "text": mrkdwn(text) }));
+        self
+    }
+
+    /// A paragraph of mrkdwn text with a button beside it that opens `url`.
+    pub fn section_with_link(mut self, text: &str, label: &str, url: &str) -> Self {
+        self.blocks.push(json!({
+            "type": "section",
+            "text": mrkdwn(text),
+            "accessory": {
+                "type": "button",
+                "text": { "type": "plain_text", "text": label },
+                "url": url,
+            },
+        }));
+        self
+    }
+
+    /// A horizontal rule.
+    pub fn divider(mut self) -> Self {
+        self.blocks.push(json!({ "type": "divider" }));
+        self
+    }
+
+    /// Small, grey mrkdwn text.
+    pub fn context(mut self, text: &str) -> Self {
+        self.blocks
+            .push(json!({ "type": "context", "elements": [mrkdwn(text)] }));
+        self
+    }
+
+    /// The `blocks` array for `chat.postMessage`.
+    pub fn build(self) -> Value {
+        Value::Array(self.blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_blocks() {
+        let blocks = Blocks::new()
+            .section("*Incidents for review*")
+            .section_with_link("1234 RPC latency", "Open", "https://pagerduty.com/1234")
+            .divider()
+            .context("2 incidents")
+            .build();
+        assert_eq!(
+            blocks,
+            json!([
+                { "type": "section", "text": { "type": "mrkdwn", "text": "*Incidents for review*" } },
+                {
+                    "type": "section",
+                    "text": { "type": "mrkdwn", "text": "1234 RPC latency" },
+                    "accessory": {
+                        "type": "button",
+                        "text": { "type": "plain_text", "text": "Open" },
+                        "url": "https://pagerduty.com/1234",
+                    },
+                },
+                { "type": "divider" },
+                { "type": "context", "elements": [{ "type": "mrkdwn", "text": "2 incidents" }] },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_long_section_is_truncated() {
+        let blocks = Blocks::new().section(&"x".repeat(5000)).build();
+        let text = blocks[0]["text"]["text"].as_str().unwrap();
+        assert_eq!(text.chars().count(), MAX_SECTION_TEXT);
+        assert!(text.ends_with('…'));
+    }
+}
@@ -1,127 +1,296 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod blocks;
 mod slack_api;
 
 use anyhow::Result;
-use futures::future::Either;
+use chrono::{DateTime, Utc};
+use clap::Parser;
 use reqwest::{header, Client};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::fs::File;
-use std::path::PathBuf;
-use tracing::debug;
+use std::fs::create_dir_all;
+use tracing::{debug, warn};
 
 /// Reexport for convenience
 pub use slack_api::*;
 
-use crate::LOCAL_CACHE_DIR;
+use crate::cli::lib::cache::{
+    cache_local_namespaced, cache_raw, get_cached_local_namespaced, get_cached_with, namespace_dir,
+    BincodeCodec, CacheCodec, SLACK_NAMESPACE,
+};
+use crate::cli::lib::http::http_client_builder;
+
+/// How long a channel's last activity is cached for, in seconds.
+const ACTIVITY_TTL_SECS: u64 = 3600;
+
+#[derive(Parser, Debug, Clone)]
+pub struct SlackArgs {
+    #[command(subcommand)]
+    action: SlackAction,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum SlackAction {
+    /// post a harmless test message to check the bot can post in a channel
+    #[command(name = "send-test")]
+    SendTest {
+        /// the channel to post in, by name or id
+        #[arg(long)]
+        channel: String,
+    },
+    /// print the recent messages of a channel, oldest first, e.g. to assemble an
+    /// incident's timeline
+    History {
+        /// the id of the channel
+        #[arg(long)]
+        channel: String,
+        /// how many of the most recent messages to print
+        #[arg(long, default_value = "100")]
+        limit: usize,
+        /// only print messages posted after this Slack timestamp
+        #[arg(long, value_name = "TS")]
+        oldest: Option<String>,
+    },
+}
+
+pub async fn slack_cmd(args: &SlackArgs) -> Result<()> {
+    match &args.action {
+        SlackAction::SendTest { channel } => {
+            let posted = send_test_message(&bot_client(), &SlackConfig::default(), channel).await?;
+            println!("Posted the test message to {} (ts {})", channel, posted.ts);
+        }
+        SlackAction::History {
+            channel,
+            limit,
+            oldest,
+        } => {
+            let messages = get_channel_history(
+                &bot_client(),
+                &SlackConfig::default(),
+                channel,
+                *limit,
+                oldest.as_deref(),
+            )
+            .await?;
+            for message in messages.iter().rev() {
+                println!(
+                    "{} {}: {}",
+                    message.ts,
+                    message.user.as_deref().unwrap_or("(bot)"),
+                    message.text
+                );
+            }
+        }
+    }
+    Ok(())
+}
 
 #[derive(Debug, Default)]
 pub struct Slack {
     client: Client,
+    config: SlackConfig,
     pub channels: Vec<Channel>,
     pub users: Vec<SlackUser>,
+    /// Why `users` is only part of the directory, if pagination failed partway
+    pub users_incomplete: Option<String>,
 }
 
-fn get_serialize_filepath(subname: &str) -> PathBuf {
-    dirs::home_dir()
-        .expect("HOME env var not set")
-        .join(LOCAL_CACHE_DIR)
-        .join(subname)
-}
-
-/// Serialize the obj into ~/.suiop/{subname} so we can cache it across
-/// executions
+/// Serialize the obj into the slack namespace of the local cache, so we can
+/// cache it across executions. It's written as bincode, which is smaller and
+/// quicker to read back than JSON for the whole user directory.
 pub fn serialize_to_file<T: Serialize>(subname: &str, obj: &Vec<T>) -> Result<()> {
-    let filepath = get_serialize_filepath(subname);
-    // Ensure the parent directory exists
-    if let Some(parent) = filepath.parent() {
-        std::fs::create_dir_all(parent)?;
-    }
-    let file = File::create(filepath.as_path())?;
-    serde_json::to_writer(file, obj)?;
+    let cache_dir = namespace_dir(SLACK_NAMESPACE)?;
+    create_dir_all(&cache_dir)?;
+    cache_raw(subname, BincodeCodec::encode(obj)?, &cache_dir)?;
     Ok(())
 }
 
-/// Check if the file in ~/.suiop/{subname} is less than 1 day old
-/// and if so, deserialize the value from it.
+/// Deserialize the value cached under `subname` in the slack namespace of the
+/// local cache, if it is less than 1 day old.
 ///
-/// Otherwise return None
+/// Otherwise return None, as for a file written as JSON by an older version.
 pub fn deserialize_from_file<T: DeserializeOwned>(subname: &str) -> Option<Vec<T>> {
-    let force_refresh = std::env::var("FORCE_REFRESH").is_ok();
-    let mut result = None;
-    let file_path = get_serialize_filepath(subname);
-    if force_refresh {
-        result = None;
-    } else if let Ok(metadata) = file_path.metadata() {
-        if let Ok(modified) = metadata.modified() {
-            if let Ok(elapsed) = modified.elapsed() {
-                // 1 day
-                if elapsed.as_secs() < 24 * 60 * 60 {
-                    if let Ok(file) = File::open(file_path) {
-                        if let Ok(obj) = serde_json::from_reader::<_, Vec<T>>(file) {
-                            debug!("Using cached {}", subname);
-                            result = Some(obj);
-                        }
-                    }
-                }
-            }
+    if std::env::var("FORCE_REFRESH").is_ok() {
+        return None;
+    }
+    let cached = namespace_dir(SLACK_NAMESPACE)
+        .and_then(|dir| get_cached_with::<BincodeCodec, Vec<T>>(subname, &dir));
+    match cached {
+        Ok(Some(cached)) if !cached.is_expired() => {
+            debug!("Using cached {}", subname);
+            Some(cached.value)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            debug!("Couldn't read the cached {}: {:#}", subname, e);
+            None
         }
     }
-    result
+}
+
+/// A client that authenticates as the bot with `SLACK_BOT_TOKEN`.
+fn bot_client() -> Client {
+    let token = std::env::var("SLACK_BOT_TOKEN").expect(
+        "Please set SLACK_BOT_TOKEN env var ('slack bot token (incidentbot)' in 1password)",
+    );
+    debug!("using slack token {}", token);
+    let mut headers = header::HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        header::HeaderValue::from_str(format!("Bearer {}", token).as_str())
+            .expect("failed to add Bearer token for slack client"),
+    );
+    http_client_builder()
+        .expect("failed to configure proxy for slack client")
+        .default_headers(headers)
+        .build()
+        .expect("failed to build reqwest client")
 }
 
 impl Slack {
-    pub async fn new() -> Self {
-        let token = std::env::var("SLACK_BOT_TOKEN").expect(
-            "Please set SLACK_BOT_TOKEN env var ('slack bot token (incidentbot)' in 1password)",
-        );
-        debug!("using slack token {}", token);
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::AUTHORIZATION,
-            header::HeaderValue::from_str(format!("Bearer {}", token).as_str())
-                .expect("failed to add Bearer token for slack client"),
-        );
-        let client = reqwest::ClientBuilder::new()
-            .default_headers(headers)
-            .build()
-            .expect("failed to build reqwest client");
-        let channels = deserialize_from_file("channels")
-            .map_or_else(
-                || {
-                    Either::Left(async {
-                        let channels = get_channels(&client).await.expect("Failed to get channels");
-                        serialize_to_file("channels", &channels)
-                            .expect("Failed to serialize channels");
-                        channels
-                    })
-                },
-                |v| Either::Right(async { v }),
-            )
-            .await;
-        let users = deserialize_from_file("users")
-            .map_or_else(
-                || {
-                    Either::Left(async {
-                        let users = get_users(&client).await.expect("Failed to get users");
-                        serialize_to_file("users", &users).expect("Failed to serialize users");
-                        users
-                    })
-                },
-                |u| Either::Right(async { u }),
-            )
-            .await;
-        Self {
+    pub async fn new() -> Result<Self> {
+        Self::with_config(SlackConfig::default()).await
+    }
+
+    /// Build a client that sends its requests according to `config`, failing if the
+    /// bot token doesn't work
+    pub async fn with_config(config: SlackConfig) -> Result<Self> {
+        let client = bot_client();
+        verify(&client, &config).await?;
+        let channels = match deserialize_from_file("channels") {
+            Some(channels) => channels,
+            None => {
+                let channels = get_channels(&client, &config, DEFAULT_CONVERSATION_TYPES).await?;
+                serialize_to_file("channels", &channels)?;
+                channels
+            }
+        };
+        let (users, users_incomplete) = match deserialize_from_file("users") {
+            Some(users) => (users, None),
+            None => {
+                let users = get_users(&client, &config).await?;
+                // a partial directory isn't cached, so the next run fetches it again
+                match &users.incomplete {
+                    Some(reason) => {
+                        warn!("Only fetched {} Slack users: {}", users.items.len(), reason)
+                    }
+                    None => serialize_to_file("users", &users.items)?,
+                }
+                (users.items, users.incomplete)
+            }
+        };
+        Ok(Self {
             client,
+            config,
             channels,
             users,
+            users_incomplete,
+        })
+    }
+
+    /// Fetch the users from Slack again, replacing the cached copy
+    pub async fn refresh_users(&self) -> Result<Vec<SlackUser>> {
+        let users = get_users(&self.client, &self.config).await?.complete()?;
+        serialize_to_file("users", &users)?;
+        Ok(users)
+    }
+
+    /// Find the user group with the given handle
+    pub async fn find_usergroup(&self, handle: &str) -> Result<UserGroup> {
+        Ok(slack_api::find_usergroup(&self.client, &self.config, handle).await?)
+    }
+
+    /// When the channel with the given id last had a message, cached for
+    /// [`ACTIVITY_TTL_SECS`] so a review doesn't fetch it again for every incident.
+    pub async fn last_activity(&self, channel: &str) -> Result<Option<DateTime<Utc>>> {
+        let key = format!("slack_activity_{}", channel);
+        if let Some(cached) =
+            get_cached_local_namespaced::<Option<DateTime<Utc>>>(SLACK_NAMESPACE, &key)?
+        {
+            let age_secs = cached
+                .metadata
+                .modified()?
+                .elapsed()
+                .map_or(0, |age| age.as_secs());
+            if age_secs < ACTIVITY_TTL_SECS {
+                return Ok(cached.value);
+            }
         }
+        let messages =
+            slack_api::get_channel_history(&self.client, &self.config, channel, 1, None).await?;
+        cache_local_namespaced(SLACK_NAMESPACE, &key, latest_message_time(&messages))
+    }
+
+    /// Post a message, in the thread of `thread_ts` if given
+    pub async fn send_message(
+        &self,
+        channel: &str,
+        message: &str,
+        thread_ts: Option<&str>,
+    ) -> Result<PostedMessage> {
+        Ok(
+            slack_api::send_message(&self.client, &self.config, channel, message, thread_ts)
+                .await?,
+        )
+    }
+
+    /// Post a Block Kit message with `text` as its fallback
+    pub async fn send_blocks(
+        &self,
+        channel: &str,
+        text: &str,
+        blocks: &serde_json::Value,
+    ) -> Result<PostedMessage> {
+        Ok(slack_api::send_blocks(&self.client, &self.config, channel, text, blocks).await?)
+    }
+
+    /// Post a message in the channel only the given user sees, returning its timestamp
+    pub async fn send_ephemeral(&self, channel: &str, user_id: &str, text: &str) -> Result<String> {
+        Ok(slack_api::send_ephemeral(&self.client, &self.config, channel, user_id, text).await?)
+    }
+
+    /// Open a direct message with the given user, returning its channel id
+    pub async fn open_dm(&self, user_id: &str) -> Result<String> {
+        Ok(slack_api::open_dm(&self.client, &self.config, user_id).await?)
+    }
+
+    /// React to the message posted at `ts` with the given emoji
+    pub async fn add_reaction(&self, channel: &str, ts: &str, emoji: &str) -> Result<()> {
+        Ok(slack_api::add_reaction(&self.client, &self.config, channel, ts, emoji).await?)
+    }
+
+    /// Find the Slack user with the given email, or `None` if nobody has it
+    #[allow(dead_code)]
+    pub async fn lookup_user_by_email(&self, email: &str) -> Result<Option<SlackUser>> {
+        Ok(slack_api::lookup_user_by_email(&self.client, &self.config, email).await?)
+    }
+
+    /// Replace the text of the message posted at `ts`, returning its timestamp
+    #[allow(dead_code)]
+    pub async fn update_message(&self, channel: &str, ts: &str, text: &str) -> Result<String> {
+        Ok(slack_api::update_message(&self.client, &self.config, channel, ts, text).await?)
+    }
+
+    /// Archive the channel with the given id
+    pub async fn archive_channel(&self, channel: &str) -> Result<()> {
+        Ok(slack_api::archive_channel(&self.client, &self.config, channel).await?)
     }
 
-    pub async fn send_message(self, channel: &str, message: &str) -> Result<()> {
-        slack_api::send_message(&self.client, channel, message).await
+    /// Upload a file to the given channel id, returning the uploaded file id
+    #[allow(dead_code)]
+    pub async fn upload_file(
+        &self,
+        channel: &str,
+        filename: &str,
+        bytes: Vec<u8>,
+        title: &str,
+    ) -> Result<String> {
+        Ok(
+            slack_api::upload_file(&self.client, &self.config, channel, filename, bytes, title)
+                .await?,
+        )
     }
 }
 
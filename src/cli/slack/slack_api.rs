@@ -2,25 +2,149 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::anyhow;
-use anyhow::Context;
-use anyhow::Result;
-use reqwest::Client;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Serialize;
 use std::fmt::Display;
 use std::fmt::Formatter;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tracing::{debug, info};
+use std::future::Future;
+use std::time::Duration;
+use tracing::{debug, info, warn};
 
-const CHANNELS_URL: &str = "https://slack.com/api/conversations.list";
+const SLACK_API_URL: &str = "https://slack.com/api";
+
+/// How many more times a failed page is requested before pagination stops on it.
+const PAGE_RETRIES: u32 = 2;
+
+/// How many times a rate limited request is sent before giving up, by default.
+const RATE_LIMIT_ATTEMPTS: u32 = 3;
+
+/// How many items a page of a list API holds, by default.
+const PAGE_LIMIT: u32 = 200;
+
+/// The most items Slack returns in one page.
+const MAX_PAGE_LIMIT: u32 = 1000;
+
+/// Where to send Slack API requests.
+#[derive(Debug, Clone)]
+pub struct SlackConfig {
+    /// The base url of the Slack web API, without a trailing slash
+    pub base_url: String,
+    /// How many times a request Slack rate limits is sent before giving up
+    pub max_attempts: u32,
+    /// How many items to ask for in each page of a list API, at most 1000
+    pub page_limit: u32,
+}
+
+impl Default for SlackConfig {
+    fn default() -> Self {
+        Self {
+            base_url: SLACK_API_URL.to_owned(),
+            max_attempts: RATE_LIMIT_ATTEMPTS,
+            page_limit: PAGE_LIMIT,
+        }
+    }
+}
+
+impl SlackConfig {
+    /// The url of a Slack web API method, e.g. "chat.postMessage"
+    fn url(&self, method: &str) -> String {
+        format!("{}/{}", self.base_url, method)
+    }
+
+    /// The `limit` query parameter for list APIs.
+    fn limit_param(&self) -> String {
+        self.page_limit.clamp(1, MAX_PAGE_LIMIT).to_string()
+    }
+}
+
+/// What went wrong talking to Slack.
+#[derive(Debug, thiserror::Error)]
+pub enum SlackError {
+    #[error(
+        "the Slack token was rejected: check that SLACK_BOT_TOKEN is set to a valid bot token"
+    )]
+    NotAuthed,
+
+    #[error("Slack is rate limiting suiop{}", retry_after.map(|secs| format!(", retry after {}s", secs)).unwrap_or_default())]
+    RateLimited { retry_after: Option<u64> },
+
+    #[error("channel_not_found: there is no such channel, or the bot can't see it")]
+    ChannelNotFound,
+
+    #[error("{0}")]
+    ApiError(String),
+
+    #[error("Error sending a Slack request: {0}")]
+    Http(#[from] reqwest::Error),
+}
+
+impl SlackError {
+    /// The error for a response whose `ok` is false, from the API's `error` field.
+    ///
+    /// Errors without a variant of their own are explained, after `context`.
+    fn from_api(
+        context: impl Display,
+        method: &str,
+        error: Option<&str>,
+        needed: Option<&str>,
+    ) -> Self {
+        match error {
+            Some("not_authed" | "invalid_auth" | "token_revoked" | "account_inactive") => {
+                Self::NotAuthed
+            }
+            Some("ratelimited") => Self::RateLimited { retry_after: None },
+            Some("channel_not_found") => Self::ChannelNotFound,
+            error => Self::ApiError(format!(
+                "{}: {}",
+                context,
+                describe_error(method, error.unwrap_or("API returned not OK"), needed)
+            )),
+        }
+    }
+}
+
+/// Parse the json body of a response from the given API method.
+async fn parse_json<T: DeserializeOwned>(
+    response: Response,
+    method: &str,
+) -> Result<T, SlackError> {
+    response
+        .json()
+        .await
+        .map_err(|e| SlackError::ApiError(format!("parsing json from {} api: {}", method, e)))
+}
+
+/// The scopes the bot token needs for everything suiop does in Slack
+const REQUIRED_SCOPES: &[&str] = &["channels:read", "groups:read", "chat:write", "users:read"];
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct UsersResponse {
     ok: bool,
+    error: Option<String>,
+    /// The scope the token lacks, for `missing_scope` errors
+    needed: Option<String>,
     members: Option<Vec<SlackUser>>,
     response_metadata: Option<ResponseMetadata>,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct LookupByEmailResponse {
+    ok: bool,
+    error: Option<String>,
+    needed: Option<String>,
+    user: Option<SlackUser>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct AuthTestResponse {
+    ok: bool,
+    error: Option<String>,
+    needed: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct SlackUser {
     pub id: String,
@@ -42,9 +166,50 @@ impl Display for SlackUser {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Channel {
     pub id: String,
+    /// Empty for direct messages, which have no name
+    #[serde(default)]
     pub name: String,
 }
 
+/// A kind of conversation `conversations.list` can return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum ConversationType {
+    PublicChannel,
+    /// Needs the `groups:read` scope
+    PrivateChannel,
+    /// A group direct message, which needs the `mpim:read` scope
+    Mpim,
+    /// A direct message, which needs the `im:read` scope
+    Im,
+}
+
+impl ConversationType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::PublicChannel => "public_channel",
+            Self::PrivateChannel => "private_channel",
+            Self::Mpim => "mpim",
+            Self::Im => "im",
+        }
+    }
+}
+
+/// The conversations searched for incident channels, which may be private.
+pub const DEFAULT_CONVERSATION_TYPES: &[ConversationType] = &[
+    ConversationType::PublicChannel,
+    ConversationType::PrivateChannel,
+];
+
+/// The `types` query parameter for `conversations.list`.
+fn types_param(types: &[ConversationType]) -> String {
+    types
+        .iter()
+        .map(|t| t.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 struct ResponseMetadata {
     next_cursor: Option<String>,
@@ -58,134 +223,1921 @@ struct ConversationsResponse {
     response_metadata: Option<ResponseMetadata>,
 }
 
+/// A Slack user group, such as an on-call rotation.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UserGroup {
+    pub id: String,
+    /// What the group is mentioned by in the Slack client, without the `@`
+    pub handle: String,
+    pub name: String,
+}
+
+impl UserGroup {
+    /// The markup that mentions the group in a message
+    pub fn mention(&self) -> String {
+        format!("<!subteam^{}>", self.id)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct UserGroupsResponse {
+    ok: bool,
+    error: Option<String>,
+    usergroups: Option<Vec<UserGroup>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct HistoryResponse {
+    ok: bool,
+    error: Option<String>,
+    needed: Option<String>,
+    messages: Option<Vec<SlackMessage>>,
+    response_metadata: Option<ResponseMetadata>,
+}
+
+/// A message in a channel's history.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SlackMessage {
+    /// When the message was posted, as Slack's "seconds.micros" timestamp
+    pub ts: String,
+    /// The id of the user who posted it, `None` for bots and integrations
+    pub user: Option<String>,
+    #[serde(default)]
+    pub text: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 struct SendMessageBody {
     channel: String,
     text: String,
-    ts: String,
     mrkdwn: bool,
+    /// The root message to reply to, to post in its thread
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_ts: Option<String>,
+    /// Block Kit blocks, shown instead of `text`, which is kept for notifications
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocks: Option<serde_json::Value>,
 }
 
-pub async fn get_channels(client: &Client) -> Result<Vec<Channel>> {
-    let mut channels: Vec<Channel> = vec![];
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct UploadUrlResponse {
+    ok: bool,
+    error: Option<String>,
+    upload_url: Option<String>,
+    file_id: Option<String>,
+}
 
-    let mut result: ConversationsResponse = client
-        .get(CHANNELS_URL)
-        .send()
-        .await
-        .map_err(|e| anyhow!(e))?
-        .json()
-        .await?;
-    let new_channels = result
-        .clone()
-        .channels
-        .unwrap_or_else(|| panic!("Expected channels to exist for {:?}", result))
-        .clone();
-    channels.extend(new_channels.into_iter());
-    if result.response_metadata.is_none() {
-        debug!("No pagination in channels response");
-        return Ok(channels);
-    }
-    while let Some(cursor) = result
-        .response_metadata
-        .expect("Expected response metadata")
-        .next_cursor
-    {
-        if cursor.is_empty() {
-            break;
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct CompleteUploadFile {
+    id: String,
+    title: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct CompleteUploadBody {
+    files: Vec<CompleteUploadFile>,
+    channel_id: String,
+}
+
+/// A response from one of Slack's cursor-paginated list APIs.
+trait Paginated {
+    type Item;
+
+    /// Split the response into this page's items and its pagination metadata,
+    /// failing if the API reported an error.
+    fn into_page(self) -> Result<(Vec<Self::Item>, Option<ResponseMetadata>), SlackError>;
+}
+
+impl Paginated for ConversationsResponse {
+    type Item = Channel;
+
+    fn into_page(self) -> Result<(Vec<Channel>, Option<ResponseMetadata>), SlackError> {
+        match self.channels {
+            Some(channels) if self.ok => Ok((channels, self.response_metadata)),
+            _ => Err(SlackError::from_api(
+                "Failed to get channels",
+                "conversations.list",
+                Some(self.error.as_deref().unwrap_or("no channels in response")),
+                None,
+            )),
         }
-        result = client
-            .get(CHANNELS_URL)
-            .query(&[("cursor", cursor)])
-            .send()
-            .await
-            .map_err(|e| anyhow!(e))?
-            .json()
-            .await
-            .context("parsing json from channels api")?;
-        let extra_channels = result
-            .clone()
-            .channels
-            .unwrap_or_else(|| panic!("Expected channels to exist for {:?}", result))
-            .clone();
-        channels.extend(extra_channels.into_iter());
     }
-    channels = channels.iter().map(|c| (*c).clone()).collect();
-    Ok(channels)
 }
 
-pub async fn get_users(client: &Client) -> Result<Vec<SlackUser>> {
-    let url = "https://slack.com/api/users.list";
-    let mut all_users = Vec::new();
-    let mut cursor: Option<String> = None;
-    let mut has_more = true;
+impl Paginated for UsersResponse {
+    type Item = SlackUser;
+
+    fn into_page(self) -> Result<(Vec<SlackUser>, Option<ResponseMetadata>), SlackError> {
+        if !self.ok {
+            return Err(SlackError::from_api(
+                "Failed to get users",
+                "users.list",
+                self.error.as_deref(),
+                self.needed.as_deref(),
+            ));
+        }
+        Ok((self.members.unwrap_or_default(), self.response_metadata))
+    }
+}
 
-    while has_more {
-        let mut request = client.get(url);
+/// The cursor for the next page, if there is one.
+///
+/// Slack signals the last page with either no metadata, no cursor, or an empty cursor.
+fn next_cursor(metadata: Option<ResponseMetadata>) -> Option<String> {
+    metadata
+        .and_then(|m| m.next_cursor)
+        .filter(|cursor| !cursor.is_empty())
+}
 
-        if let Some(ref cursor_value) = cursor {
-            request = request.query(&[("cursor", cursor_value)]);
+/// Collect every item from a cursor-paginated Slack API.
+///
+/// `fetch` is called with the cursor for each page, starting with `None`.
+async fn paginate<R, F, Fut>(mut fetch: F) -> Result<Vec<R::Item>, SlackError>
+where
+    R: Paginated,
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<R, SlackError>>,
+{
+    let mut items = vec![];
+    let mut cursor = None;
+    loop {
+        let (page, metadata) = fetch(cursor).await?.into_page()?;
+        items.extend(page);
+        cursor = next_cursor(metadata);
+        if cursor.is_none() {
+            debug!("No more pages");
+            return Ok(items);
         }
+    }
+}
 
-        let response = request
-            .send()
-            .await
-            .map_err(|e| anyhow!(e))?
-            .json::<UsersResponse>()
-            .await?;
+/// Items collected from a cursor-paginated Slack API, which may stop short of
+/// the last page.
+#[derive(Debug)]
+pub struct Collected<T> {
+    pub items: Vec<T>,
+    /// Why the pages after `items` couldn't be fetched, if they couldn't
+    pub incomplete: Option<String>,
+}
 
-        if !response.ok {
-            return Err(anyhow::anyhow!("Failed to get users: API returned not OK"));
+impl<T> Collected<T> {
+    /// The items, or an error if any pages are missing.
+    pub fn complete(self) -> anyhow::Result<Vec<T>> {
+        match self.incomplete {
+            Some(reason) => Err(anyhow!(
+                "only {} items were fetched before pagination failed: {}",
+                self.items.len(),
+                reason
+            )),
+            None => Ok(self.items),
         }
+    }
+}
 
-        if let Some(members) = response.members {
-            if *crate::DEBUG_MODE {
-                info!("Retrieved {} users from Slack API", members.len());
+/// Like [`paginate`], but a page that fails is retried from its cursor, and if
+/// it keeps failing the pages fetched so far are returned, marked incomplete.
+///
+/// Only a failure on the first page is an error, as there is nothing to return.
+async fn paginate_resumable<R, F, Fut>(mut fetch: F) -> Result<Collected<R::Item>, SlackError>
+where
+    R: Paginated,
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<R, SlackError>>,
+{
+    let mut items = vec![];
+    let mut cursor = None;
+    loop {
+        let mut attempt = 0;
+        let page = loop {
+            match fetch(cursor.clone()).await.and_then(R::into_page) {
+                Err(e) if attempt < PAGE_RETRIES => {
+                    attempt += 1;
+                    warn!("Retrying a Slack page (attempt {}): {:#}", attempt, e);
+                    tokio::time::sleep(Duration::from_millis(250 * attempt as u64)).await;
+                }
+                result => break result,
+            }
+        };
+        match page {
+            Ok((page, metadata)) => {
+                items.extend(page);
+                cursor = next_cursor(metadata);
+                if cursor.is_none() {
+                    debug!("No more pages");
+                    return Ok(Collected {
+                        items,
+                        incomplete: None,
+                    });
+                }
+            }
+            Err(e) if items.is_empty() => return Err(e),
+            Err(e) => {
+                return Ok(Collected {
+                    items,
+                    incomplete: Some(format!("{:#}", e)),
+                })
             }
-            all_users.extend(members);
         }
+    }
+}
 
-        // Check if there are more results
-        if let Some(metadata) = response.response_metadata {
-            if let Some(next_cursor) = metadata.next_cursor {
-                if !next_cursor.is_empty() {
-                    cursor = Some(next_cursor);
-                    has_more = true;
-                } else {
-                    has_more = false;
-                }
-            } else {
-                has_more = false;
+/// Send the request once, failing with [`SlackError::RateLimited`] if Slack
+/// rate limits it.
+async fn send_once(request: RequestBuilder) -> Result<Response, SlackError> {
+    let response = request.send().await?;
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return Ok(response);
+    }
+    let retry_after = response
+        .headers()
+        .get("retry-after")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok());
+    Err(SlackError::RateLimited { retry_after })
+}
+
+/// Send the request, waiting as long as Slack's `Retry-After` header says and
+/// sending it again whenever it's rate limited, up to `max_attempts` times in all.
+async fn send_with_retry(
+    request: RequestBuilder,
+    max_attempts: u32,
+) -> Result<Response, SlackError> {
+    let mut attempt = 1;
+    loop {
+        let retry = request.try_clone().ok_or_else(|| {
+            SlackError::ApiError("a Slack request with a streamed body can't be retried".to_owned())
+        })?;
+        match send_once(retry).await {
+            Err(SlackError::RateLimited { retry_after }) if attempt < max_attempts => {
+                let wait_secs = retry_after.unwrap_or(1);
+                warn!(
+                    "Slack rate limited a request, retrying in {}s (attempt {} of {})",
+                    wait_secs,
+                    attempt + 1,
+                    max_attempts
+                );
+                tokio::time::sleep(Duration::from_secs(wait_secs)).await;
+                attempt += 1;
             }
-        } else {
-            has_more = false;
+            result => return result,
         }
     }
+}
+
+/// Explain a Slack API error, saying how to fix the token when it lacks permissions.
+fn describe_error(method: &str, error: &str, needed: Option<&str>) -> String {
+    match error {
+        "missing_scope" => format!(
+            "the Slack token can't call {}: add the {} scope to the app under OAuth & Permissions and reinstall it",
+            method,
+            needed.unwrap_or("required")
+        ),
+        "not_in_channel" => format!(
+            "the bot can't call {} because it isn't in the channel: invite it with /invite in the channel",
+            method
+        ),
+        "user_not_in_channel" => format!(
+            "{} can only show a message to a member of the channel: join it first",
+            method
+        ),
+        "not_allowed_token_type" => format!(
+            "the Slack token can't call {}: set SLACK_BOT_TOKEN to the app's bot token (xoxb-...)",
+            method
+        ),
+        other => other.to_owned(),
+    }
+}
+
+/// Check that the token works and has every scope suiop needs, so a missing
+/// scope is reported up front rather than partway through fetching.
+pub async fn verify(client: &Client, config: &SlackConfig) -> Result<(), SlackError> {
+    let response = client.get(config.url("auth.test")).send().await?;
+    let scopes: Option<Vec<String>> = response
+        .headers()
+        .get("x-oauth-scopes")
+        .and_then(|scopes| scopes.to_str().ok())
+        .map(|scopes| scopes.split(',').map(|s| s.trim().to_owned()).collect());
+    let auth: AuthTestResponse = parse_json(response, "auth.test").await?;
+    if !auth.ok {
+        return Err(SlackError::from_api(
+            "Slack token check failed",
+            "auth.test",
+            auth.error.as_deref(),
+            auth.needed.as_deref(),
+        ));
+    }
+    // tokens that don't report their scopes are trusted to have them
+    let Some(scopes) = scopes else {
+        return Ok(());
+    };
+    let missing: Vec<&str> = REQUIRED_SCOPES
+        .iter()
+        .copied()
+        .filter(|required| !scopes.iter().any(|s| s == required))
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(SlackError::ApiError(format!(
+            "the Slack token is missing the {} scope(s): add them to the app under OAuth & Permissions and reinstall it",
+            missing.join(", ")
+        )))
+    }
+}
+
+/// Fetch every conversation of the given types the bot can see.
+pub async fn get_channels(
+    client: &Client,
+    config: &SlackConfig,
+    types: &[ConversationType],
+) -> Result<Vec<Channel>, SlackError> {
+    let url = config.url("conversations.list");
+    let types = types_param(types);
+    let limit = config.limit_param();
+    paginate(|cursor| {
+        let mut request = client
+            .get(&url)
+            .query(&[("types", &types), ("limit", &limit)]);
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
+        }
+        async move {
+            let response = send_with_retry(request, config.max_attempts).await?;
+            parse_json::<ConversationsResponse>(response, "conversations.list").await
+        }
+    })
+    .await
+}
+
+/// Fetch the user directory, keeping the users fetched so far if a later page
+/// fails even after retries.
+pub async fn get_users(
+    client: &Client,
+    config: &SlackConfig,
+) -> Result<Collected<SlackUser>, SlackError> {
+    let url = config.url("users.list");
+    let limit = config.limit_param();
+    let all_users = paginate_resumable(|cursor| {
+        let mut request = client.get(&url).query(&[("limit", &limit)]);
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
+        }
+        async move {
+            let response = send_with_retry(request, config.max_attempts).await?;
+            let response = parse_json::<UsersResponse>(response, "users.list").await?;
+            if *crate::DEBUG_MODE {
+                info!(
+                    "Retrieved {} users from Slack API",
+                    response.members.as_ref().map_or(0, Vec::len)
+                );
+            }
+            Ok(response)
+        }
+    })
+    .await?;
 
     if *crate::DEBUG_MODE {
-        info!("Total users retrieved from Slack: {}", all_users.len());
+        info!(
+            "Total users retrieved from Slack: {}",
+            all_users.items.len()
+        );
     }
 
     Ok(all_users)
 }
 
-pub async fn send_message(client: &Client, channel: &str, message: &str) -> Result<()> {
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards")
-        .as_millis();
+pub async fn get_usergroups(
+    client: &Client,
+    config: &SlackConfig,
+) -> Result<Vec<UserGroup>, SlackError> {
+    let response = client.get(config.url("usergroups.list")).send().await?;
+    let response: UserGroupsResponse = parse_json(response, "usergroups.list").await?;
+    match response.usergroups {
+        Some(groups) if response.ok => Ok(groups),
+        _ => Err(SlackError::from_api(
+            "Failed to get user groups",
+            "usergroups.list",
+            Some(
+                response
+                    .error
+                    .as_deref()
+                    .unwrap_or("no user groups in response"),
+            ),
+            None,
+        )),
+    }
+}
+
+/// Find the user group with the given handle, with or without its leading `@`.
+pub async fn find_usergroup(
+    client: &Client,
+    config: &SlackConfig,
+    handle: &str,
+) -> Result<UserGroup, SlackError> {
+    let handle = handle.trim_start_matches('@');
+    get_usergroups(client, config)
+        .await?
+        .into_iter()
+        .find(|g| g.handle == handle)
+        .ok_or_else(|| SlackError::ApiError(format!("there is no Slack user group @{}", handle)))
+}
+
+/// Find the Slack user with the given email, or `None` if nobody has it, without
+/// fetching the whole directory.
+///
+/// Needs the `users:read.email` scope.
+pub async fn lookup_user_by_email(
+    client: &Client,
+    config: &SlackConfig,
+    email: &str,
+) -> Result<Option<SlackUser>, SlackError> {
+    let response = send_with_retry(
+        client
+            .get(config.url("users.lookupByEmail"))
+            .query(&[("email", email)]),
+        config.max_attempts,
+    )
+    .await?;
+    let response: LookupByEmailResponse = parse_json(response, "users.lookupByEmail").await?;
+    match (response.ok, response.error.as_deref()) {
+        (true, _) => Ok(response.user),
+        (false, Some("users_not_found")) => Ok(None),
+        (false, error) => Err(SlackError::from_api(
+            format!("Failed to look up the Slack user of {}", email),
+            "users.lookupByEmail",
+            error,
+            response.needed.as_deref(),
+        )),
+    }
+}
+
+/// Fetch up to `limit` of the most recent messages in the channel with the given
+/// id, newest first, going back no further than the `oldest` timestamp if given.
+///
+/// Pages are fetched until `limit` messages are collected or the history runs out.
+pub async fn get_channel_history(
+    client: &Client,
+    config: &SlackConfig,
+    channel: &str,
+    limit: usize,
+    oldest: Option<&str>,
+) -> Result<Vec<SlackMessage>, SlackError> {
+    let mut messages = vec![];
+    let mut cursor = None;
+    while messages.len() < limit {
+        let page_limit =
+            (limit - messages.len()).min(config.page_limit.clamp(1, MAX_PAGE_LIMIT) as usize);
+        let mut request = client.get(config.url("conversations.history")).query(&[
+            ("channel", channel.to_owned()),
+            ("limit", page_limit.to_string()),
+        ]);
+        if let Some(oldest) = oldest {
+            request = request.query(&[("oldest", oldest)]);
+        }
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
+        }
+        let response = send_with_retry(request, config.max_attempts).await?;
+        let response: HistoryResponse = parse_json(response, "conversations.history").await?;
+        if !response.ok {
+            return Err(SlackError::from_api(
+                format!("Failed to get the history of {}", channel),
+                "conversations.history",
+                response.error.as_deref(),
+                response.needed.as_deref(),
+            ));
+        }
+        messages.extend(response.messages.unwrap_or_default());
+        cursor = next_cursor(response.response_metadata);
+        if cursor.is_none() {
+            break;
+        }
+    }
+    messages.truncate(limit);
+    Ok(messages)
+}
+
+/// When the most recent of `messages` was posted, if any have a valid timestamp.
+pub fn latest_message_time(messages: &[SlackMessage]) -> Option<DateTime<Utc>> {
+    messages
+        .iter()
+        .filter_map(|m| {
+            let (secs, micros) = m.ts.split_once('.').unwrap_or((&m.ts, "0"));
+            DateTime::from_timestamp(secs.parse().ok()?, micros.parse::<u32>().ok()? * 1000)
+        })
+        .max()
+}
+
+/// A message Slack accepted: the id of the channel it was posted in, whatever the
+/// channel was given as, and the timestamp Slack identifies it by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostedMessage {
+    pub channel: String,
+    pub ts: String,
+}
+
+/// Post a message to the channel, in the thread of `thread_ts` if given.
+pub async fn send_message(
+    client: &Client,
+    config: &SlackConfig,
+    channel: &str,
+    message: &str,
+    thread_ts: Option<&str>,
+) -> Result<PostedMessage, SlackError> {
     let message_body = SendMessageBody {
         channel: channel.to_owned(),
         text: message.to_owned(),
-        ts: timestamp.to_string(),
         mrkdwn: true,
+        thread_ts: thread_ts.map(str::to_owned),
+        blocks: None,
     };
-    let url = "https://slack.com/api/chat.postMessage";
-    let response = client.post(url).json(&message_body).send().await?;
-    let response = response.json::<serde_json::Value>().await?;
-    if response["ok"].as_bool().expect("ok was not a bool") {
-        Ok(())
+    post_message(client, config, &message_body).await
+}
+
+/// Post a Block Kit message to the channel. `text` is what notifications and
+/// clients that can't show blocks display instead.
+pub async fn send_blocks(
+    client: &Client,
+    config: &SlackConfig,
+    channel: &str,
+    text: &str,
+    blocks: &serde_json::Value,
+) -> Result<PostedMessage, SlackError> {
+    let message_body = SendMessageBody {
+        channel: channel.to_owned(),
+        text: text.to_owned(),
+        mrkdwn: true,
+        thread_ts: None,
+        blocks: Some(blocks.clone()),
+    };
+    post_message(client, config, &message_body).await
+}
+
+/// Post the message, and if the bot isn't in the channel, join it and post again.
+///
+/// Only public channels can be joined, so for a private one the error says to
+/// invite the bot instead.
+async fn post_message(
+    client: &Client,
+    config: &SlackConfig,
+    message_body: &SendMessageBody,
+) -> Result<PostedMessage, SlackError> {
+    let mut joined = false;
+    loop {
+        let response = send_with_retry(
+            client
+                .post(config.url("chat.postMessage"))
+                .json(message_body),
+            config.max_attempts,
+        )
+        .await?;
+        let response: serde_json::Value = parse_json(response, "chat.postMessage").await?;
+        if response["ok"].as_bool().unwrap_or_default() {
+            return Ok(PostedMessage {
+                channel: response["channel"]
+                    .as_str()
+                    .unwrap_or(&message_body.channel)
+                    .to_owned(),
+                ts: response["ts"].as_str().unwrap_or_default().to_owned(),
+            });
+        }
+        let error = response["error"].as_str();
+        if error == Some("not_in_channel") && !joined {
+            match join_channel(client, config, &message_body.channel).await {
+                Ok(()) => {
+                    info!("Joined {} to post in it", message_body.channel);
+                    joined = true;
+                    continue;
+                }
+                Err(e) => debug!("Couldn't join {}: {}", message_body.channel, e),
+            }
+        }
+        return Err(SlackError::from_api(
+            format!("Failed to send message to {}", message_body.channel),
+            "chat.postMessage",
+            error,
+            response["needed"].as_str(),
+        ));
+    }
+}
+
+/// Join the public channel given by its id or name. `conversations.join` only
+/// takes ids, so a name is looked up first.
+///
+/// Needs the `channels:join` scope.
+async fn join_channel(
+    client: &Client,
+    config: &SlackConfig,
+    channel: &str,
+) -> Result<(), SlackError> {
+    let id = public_channel_id(client, config, channel).await?;
+    let response = send_with_retry(
+        client
+            .post(config.url("conversations.join"))
+            .json(&serde_json::json!({ "channel": id })),
+        config.max_attempts,
+    )
+    .await?;
+    let response: serde_json::Value = parse_json(response, "conversations.join").await?;
+    if response["ok"].as_bool().unwrap_or_default() {
+        return Ok(());
+    }
+    Err(SlackError::from_api(
+        format!("Failed to join {}", channel),
+        "conversations.join",
+        response["error"].as_str(),
+        response["needed"].as_str(),
+    ))
+}
+
+/// The id of the public channel with the given id or name, with or without the
+/// leading `#`.
+async fn public_channel_id(
+    client: &Client,
+    config: &SlackConfig,
+    channel: &str,
+) -> Result<String, SlackError> {
+    let channel = channel.trim_start_matches('#');
+    get_channels(client, config, &[ConversationType::PublicChannel])
+        .await?
+        .into_iter()
+        .find(|c| c.id == channel || c.name == channel)
+        .map(|c| c.id)
+        .ok_or(SlackError::ChannelNotFound)
+}
+
+/// Post a message in the channel that only the user with the given id sees,
+/// returning its timestamp. The user has to be in the channel, and the message
+/// doesn't survive a reload of their Slack client.
+pub async fn send_ephemeral(
+    client: &Client,
+    config: &SlackConfig,
+    channel: &str,
+    user_id: &str,
+    text: &str,
+) -> Result<String, SlackError> {
+    let response = send_with_retry(
+        client
+            .post(config.url("chat.postEphemeral"))
+            .json(&serde_json::json!({
+                "channel": channel,
+                "user": user_id,
+                "text": text,
+                "mrkdwn": true,
+            })),
+        config.max_attempts,
+    )
+    .await?;
+    let response: serde_json::Value = parse_json(response, "chat.postEphemeral").await?;
+    if response["ok"].as_bool().unwrap_or_default() {
+        Ok(response["message_ts"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned())
     } else {
-        Err(anyhow!("Failed to send message: {}", response))
+        Err(SlackError::from_api(
+            format!("Failed to preview a message to {} in {}", user_id, channel),
+            "chat.postEphemeral",
+            response["error"].as_str(),
+            response["needed"].as_str(),
+        ))
+    }
+}
+
+/// Open a direct message conversation with the user with the given id, returning
+/// the id of its channel, which messages can be sent to like any other.
+///
+/// Needs the `im:write` scope.
+pub async fn open_dm(
+    client: &Client,
+    config: &SlackConfig,
+    user_id: &str,
+) -> Result<String, SlackError> {
+    let response = send_with_retry(
+        client
+            .post(config.url("conversations.open"))
+            .json(&serde_json::json!({ "users": user_id })),
+        config.max_attempts,
+    )
+    .await?;
+    let response: serde_json::Value = parse_json(response, "conversations.open").await?;
+    if !response["ok"].as_bool().unwrap_or_default() {
+        return Err(SlackError::from_api(
+            format!("Failed to open a direct message with {}", user_id),
+            "conversations.open",
+            response["error"].as_str(),
+            response["needed"].as_str(),
+        ));
+    }
+    response["channel"]["id"]
+        .as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| SlackError::ApiError("conversations.open returned no channel id".to_owned()))
+}
+
+/// React to the message posted at `ts` in the channel with the given id, with an
+/// emoji name like `white_check_mark`, with or without its colons. Reacting with
+/// an emoji the bot already reacted with succeeds.
+///
+/// Needs the `reactions:write` scope.
+pub async fn add_reaction(
+    client: &Client,
+    config: &SlackConfig,
+    channel: &str,
+    ts: &str,
+    emoji: &str,
+) -> Result<(), SlackError> {
+    let response = send_with_retry(
+        client
+            .post(config.url("reactions.add"))
+            .json(&serde_json::json!({
+                "channel": channel,
+                "timestamp": ts,
+                "name": emoji.trim_matches(':'),
+            })),
+        config.max_attempts,
+    )
+    .await?;
+    let response: serde_json::Value = parse_json(response, "reactions.add").await?;
+    let error = response["error"].as_str();
+    if response["ok"].as_bool().unwrap_or_default() || error == Some("already_reacted") {
+        return Ok(());
+    }
+    Err(SlackError::from_api(
+        format!("Failed to react to message {} in {}", ts, channel),
+        "reactions.add",
+        error,
+        response["needed"].as_str(),
+    ))
+}
+
+/// Replace the text of the message posted at `ts` in the channel with the given
+/// id, returning the message's timestamp.
+///
+/// Slack only lets the bot edit messages it posted itself.
+pub async fn update_message(
+    client: &Client,
+    config: &SlackConfig,
+    channel: &str,
+    ts: &str,
+    text: &str,
+) -> Result<String, SlackError> {
+    let response = send_with_retry(
+        client
+            .post(config.url("chat.update"))
+            .json(&serde_json::json!({
+                "channel": channel,
+                "ts": ts,
+                "text": text,
+            })),
+        config.max_attempts,
+    )
+    .await?;
+    let response: serde_json::Value = parse_json(response, "chat.update").await?;
+    if response["ok"].as_bool().unwrap_or_default() {
+        return Ok(response["ts"].as_str().unwrap_or(ts).to_owned());
+    }
+    let context = format!("Failed to update message {} in {}", ts, channel);
+    let reason = match response["error"].as_str() {
+        Some("message_not_found") => "there is no such message, it may have been deleted",
+        Some("cant_update_message") => "the bot can only edit messages it posted",
+        error => {
+            return Err(SlackError::from_api(
+                context,
+                "chat.update",
+                error,
+                response["needed"].as_str(),
+            ))
+        }
+    };
+    Err(SlackError::ApiError(format!("{}: {}", context, reason)))
+}
+
+/// The message `suiop slack send-test` posts.
+pub const TEST_MESSAGE: &str = "suiop connectivity test";
+
+/// Post [`TEST_MESSAGE`] to the channel, a name with or without the leading `#`
+/// or an id.
+pub async fn send_test_message(
+    client: &Client,
+    config: &SlackConfig,
+    channel: &str,
+) -> Result<PostedMessage, SlackError> {
+    send_message(
+        client,
+        config,
+        channel.trim_start_matches('#'),
+        TEST_MESSAGE,
+        None,
+    )
+    .await
+}
+
+/// Archive the channel with the given id. A channel that is already archived is
+/// left as it is.
+pub async fn archive_channel(
+    client: &Client,
+    config: &SlackConfig,
+    channel: &str,
+) -> Result<(), SlackError> {
+    let response = client
+        .post(config.url("conversations.archive"))
+        .json(&serde_json::json!({ "channel": channel }))
+        .send()
+        .await?;
+    let response: serde_json::Value = parse_json(response, "conversations.archive").await?;
+    let error = response["error"].as_str();
+    if response["ok"].as_bool().unwrap_or_default() || error == Some("already_archived") {
+        return Ok(());
+    }
+    Err(SlackError::from_api(
+        format!("Failed to archive {}", channel),
+        "conversations.archive",
+        error,
+        response["needed"].as_str(),
+    ))
+}
+
+/// Upload a file and share it in the given channel id, returning the file id.
+///
+/// Uses the two-step external upload flow: request an upload url, send the bytes
+/// to it, then complete the upload to share the file.
+pub async fn upload_file(
+    client: &Client,
+    config: &SlackConfig,
+    channel: &str,
+    filename: &str,
+    bytes: Vec<u8>,
+    title: &str,
+) -> Result<String, SlackError> {
+    let response = client
+        .get(config.url("files.getUploadURLExternal"))
+        .query(&[
+            ("filename", filename.to_owned()),
+            ("length", bytes.len().to_string()),
+        ])
+        .send()
+        .await?;
+    let response: UploadUrlResponse = parse_json(response, "files.getUploadURLExternal").await?;
+    if !response.ok {
+        return Err(SlackError::from_api(
+            "Failed to get upload url",
+            "files.getUploadURLExternal",
+            response.error.as_deref(),
+            None,
+        ));
+    }
+    let (upload_url, file_id) = response.upload_url.zip(response.file_id).ok_or_else(|| {
+        SlackError::ApiError("upload url response is missing the url or file id".to_owned())
+    })?;
+    debug!("uploading {} to {}", filename, upload_url);
+
+    let upload = client.post(upload_url).body(bytes).send().await?;
+    if !upload.status().is_success() {
+        return Err(SlackError::ApiError(format!(
+            "Failed to upload file: {}",
+            upload.text().await.unwrap_or_default()
+        )));
+    }
+
+    let complete_body = CompleteUploadBody {
+        files: vec![CompleteUploadFile {
+            id: file_id.clone(),
+            title: title.to_owned(),
+        }],
+        channel_id: channel.to_owned(),
+    };
+    let response = client
+        .post(config.url("files.completeUploadExternal"))
+        .json(&complete_body)
+        .send()
+        .await?;
+    let response: serde_json::Value = parse_json(response, "files.completeUploadExternal").await?;
+    if response["ok"].as_bool().unwrap_or(false) {
+        Ok(file_id)
+    } else {
+        Err(SlackError::ApiError(format!(
+            "Failed to complete file upload: {}",
+            response
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+    use std::cell::RefCell;
+
+    fn mock_config(server: &mockito::Server) -> SlackConfig {
+        SlackConfig {
+            base_url: server.url(),
+            max_attempts: RATE_LIMIT_ATTEMPTS,
+            page_limit: PAGE_LIMIT,
+        }
+    }
+
+    /// Paginate over canned json pages, recording the cursor each page was requested with.
+    async fn paginate_canned<R>(
+        pages: &[&str],
+        requested: &RefCell<Vec<Option<String>>>,
+    ) -> Result<Vec<R::Item>, SlackError>
+    where
+        R: Paginated + for<'a> Deserialize<'a>,
+    {
+        paginate(|cursor| {
+            let index = requested.borrow().len();
+            requested.borrow_mut().push(cursor);
+            let page = pages[index];
+            async move { Ok(serde_json::from_str::<R>(page).unwrap()) }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_get_channels_pagination() {
+        let requested = RefCell::new(vec![]);
+        let channels = paginate_canned::<ConversationsResponse>(
+            &[
+                r#"{"ok": true, "channels": [{"id": "C1", "name": "incident-1"}], "response_metadata": {"next_cursor": "page2"}}"#,
+                r#"{"ok": true, "channels": [{"id": "C2", "name": "incident-2"}], "response_metadata": {"next_cursor": ""}}"#,
+            ],
+            &requested,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            channels.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(),
+            vec!["C1", "C2"]
+        );
+        assert_eq!(*requested.borrow(), vec![None, Some("page2".to_owned())]);
+
+        let requested = RefCell::new(vec![]);
+        let channels = paginate_canned::<ConversationsResponse>(
+            &[r#"{"ok": true, "channels": [{"id": "C1", "name": "incident-1"}]}"#],
+            &requested,
+        )
+        .await
+        .unwrap();
+        assert_eq!(channels.len(), 1);
+        assert_eq!(requested.borrow().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_channels_includes_private_channels() {
+        let mut server = mockito::Server::new_async().await;
+        let list = server
+            .mock("GET", "/conversations.list")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded(
+                    "types".to_owned(),
+                    "public_channel,private_channel".to_owned(),
+                ),
+                Matcher::UrlEncoded("limit".to_owned(), "200".to_owned()),
+            ]))
+            .with_body(
+                r#"{"ok": true, "channels": [{"id": "C1", "name": "incident-1", "is_private": false}, {"id": "G2", "name": "incident-2", "is_private": true}]}"#,
+            )
+            .create_async()
+            .await;
+        let channels = get_channels(
+            &Client::new(),
+            &mock_config(&server),
+            DEFAULT_CONVERSATION_TYPES,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            channels.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(),
+            vec!["C1", "G2"]
+        );
+        list.assert_async().await;
+
+        let dms = server
+            .mock("GET", "/conversations.list")
+            .match_query(Matcher::UrlEncoded("types".to_owned(), "im".to_owned()))
+            .with_body(r#"{"ok": true, "channels": [{"id": "D1", "user": "U1"}]}"#)
+            .create_async()
+            .await;
+        let channels = get_channels(
+            &Client::new(),
+            &mock_config(&server),
+            &[ConversationType::Im],
+        )
+        .await
+        .unwrap();
+        assert_eq!(channels[0].name, "");
+        dms.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_users_page_limit_is_capped() {
+        let mut server = mockito::Server::new_async().await;
+        let list = server
+            .mock("GET", "/users.list")
+            .match_query(Matcher::UrlEncoded("limit".to_owned(), "1000".to_owned()))
+            .with_body(r#"{"ok": true, "members": [{"id": "U1", "name": "jane"}]}"#)
+            .create_async()
+            .await;
+        let config = SlackConfig {
+            page_limit: 5000,
+            ..mock_config(&server)
+        };
+        let users = get_users(&Client::new(), &config).await.unwrap();
+        assert_eq!(users.items.len(), 1);
+        list.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_users_pagination() {
+        let requested = RefCell::new(vec![]);
+        let users = paginate_canned::<UsersResponse>(
+            &[
+                r#"{"ok": true, "members": [{"id": "U1", "name": "jane"}], "response_metadata": {"next_cursor": "page2"}}"#,
+                r#"{"ok": true, "members": [{"id": "U2", "name": "john"}]}"#,
+            ],
+            &requested,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            users.iter().map(|u| u.id.as_str()).collect::<Vec<_>>(),
+            vec!["U1", "U2"]
+        );
+        assert_eq!(*requested.borrow(), vec![None, Some("page2".to_owned())]);
+
+        let requested = RefCell::new(vec![]);
+        let users = paginate_canned::<UsersResponse>(
+            &[r#"{"ok": true, "members": [{"id": "U1", "name": "jane"}], "response_metadata": {"next_cursor": ""}}"#],
+            &requested,
+        )
+        .await
+        .unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(requested.borrow().len(), 1);
+    }
+
+    /// Paginate over canned pages, where `None` is a page that couldn't be fetched.
+    async fn paginate_resumable_canned(
+        pages: &[Option<&str>],
+        requested: &RefCell<Vec<Option<String>>>,
+    ) -> Result<Collected<SlackUser>, SlackError> {
+        paginate_resumable(|cursor| {
+            let index = requested.borrow().len();
+            requested.borrow_mut().push(cursor);
+            let page = pages[index];
+            async move {
+                let page =
+                    page.ok_or_else(|| SlackError::ApiError("connection reset".to_owned()))?;
+                Ok(serde_json::from_str::<UsersResponse>(page).unwrap())
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_users_pagination_resumes_and_flags_partial() {
+        let first = r#"{"ok": true, "members": [{"id": "U1", "name": "jane"}], "response_metadata": {"next_cursor": "page2"}}"#;
+        let second = r#"{"ok": true, "members": [{"id": "U2", "name": "john"}]}"#;
+
+        // a page that fails once is retried from its cursor
+        let requested = RefCell::new(vec![]);
+        let users = paginate_resumable_canned(&[Some(first), None, Some(second)], &requested)
+            .await
+            .unwrap();
+        assert!(users.incomplete.is_none());
+        assert_eq!(users.items.len(), 2);
+        assert_eq!(
+            *requested.borrow(),
+            vec![None, Some("page2".to_owned()), Some("page2".to_owned())]
+        );
+
+        // a page that keeps failing leaves the users fetched so far, flagged
+        let requested = RefCell::new(vec![]);
+        let users = paginate_resumable_canned(&[Some(first), None, None, None], &requested)
+            .await
+            .unwrap();
+        assert_eq!(users.items.len(), 1);
+        assert!(users
+            .incomplete
+            .as_deref()
+            .unwrap()
+            .contains("connection reset"));
+        assert!(users
+            .complete()
+            .unwrap_err()
+            .to_string()
+            .contains("only 1 items were fetched"));
+
+        // with nothing fetched, the failure is an error
+        let requested = RefCell::new(vec![]);
+        assert!(paginate_resumable_canned(&[None, None, None], &requested)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pagination_surfaces_api_errors() {
+        let requested = RefCell::new(vec![]);
+        let err = paginate_canned::<ConversationsResponse>(
+            &[r#"{"ok": false, "error": "invalid_auth"}"#],
+            &requested,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, SlackError::NotAuthed));
+    }
+
+    #[tokio::test]
+    async fn test_find_usergroup() {
+        let mut server = mockito::Server::new_async().await;
+        let list = server
+            .mock("GET", "/usergroups.list")
+            .with_body(
+                r#"{"ok": true, "usergroups": [
+                    {"id": "S0614TZR7", "handle": "infra-oncall", "name": "Infra on-call"},
+                    {"id": "S0615G0KT", "handle": "security", "name": "Security"}
+                ]}"#,
+            )
+            .expect(3)
+            .create_async()
+            .await;
+        let config = mock_config(&server);
+
+        let group = find_usergroup(&Client::new(), &config, "@infra-oncall")
+            .await
+            .unwrap();
+        assert_eq!(group.mention(), "<!subteam^S0614TZR7>");
+        let group = find_usergroup(&Client::new(), &config, "security")
+            .await
+            .unwrap();
+        assert_eq!(group.id, "S0615G0KT");
+        let err = find_usergroup(&Client::new(), &config, "nobody")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "there is no Slack user group @nobody");
+        list.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_archive_channel() {
+        let mut server = mockito::Server::new_async().await;
+        let archive = server
+            .mock("POST", "/conversations.archive")
+            .match_body(Matcher::Json(serde_json::json!({ "channel": "C456" })))
+            .with_body(r#"{"ok": true}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/conversations.archive")
+            .match_body(Matcher::Json(serde_json::json!({ "channel": "C789" })))
+            .with_body(r#"{"ok": false, "error": "already_archived"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/conversations.archive")
+            .match_body(Matcher::Json(serde_json::json!({ "channel": "C000" })))
+            .with_body(r#"{"ok": false, "error": "missing_scope", "needed": "channels:manage"}"#)
+            .create_async()
+            .await;
+        let config = mock_config(&server);
+
+        archive_channel(&Client::new(), &config, "C456")
+            .await
+            .unwrap();
+        archive.assert_async().await;
+        archive_channel(&Client::new(), &config, "C789")
+            .await
+            .unwrap();
+        let err = archive_channel(&Client::new(), &config, "C000")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("add the channels:manage scope"));
+    }
+
+    #[tokio::test]
+    async fn test_get_history_latest_ts() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/conversations.history")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("channel".into(), "C456".into()),
+                Matcher::UrlEncoded("limit".into(), "1".into()),
+            ]))
+            .with_body(
+                r#"{"ok": true, "messages": [{"type": "message", "user": "U1", "text": "fixed", "ts": "1700000000.000100"}], "has_more": true}"#,
+            )
+            .create_async()
+            .await;
+
+        let messages = get_channel_history(&Client::new(), &mock_config(&server), "C456", 1, None)
+            .await
+            .unwrap();
+        assert_eq!(messages[0].user.as_deref(), Some("U1"));
+        assert_eq!(messages[0].text, "fixed");
+        let latest = latest_message_time(&messages).unwrap();
+        assert_eq!(latest.timestamp(), 1_700_000_000);
+        assert_eq!(latest.timestamp_subsec_micros(), 100);
+        assert_eq!(latest_message_time(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_channel_history_pages_until_limit() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("GET", "/conversations.history")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("limit".into(), "3".into()),
+                Matcher::UrlEncoded("oldest".into(), "1699999999.000000".into()),
+            ]))
+            .with_body(
+                r#"{"ok": true, "messages": [{"ts": "3.0", "user": "U1", "text": "resolved"}, {"ts": "2.0", "bot_id": "B1", "text": "alert"}], "response_metadata": {"next_cursor": "page2"}}"#,
+            )
+            .create_async()
+            .await;
+        // the second page only asks for what's left of the limit
+        let second = server
+            .mock("GET", "/conversations.history")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("limit".into(), "1".into()),
+                Matcher::UrlEncoded("cursor".into(), "page2".into()),
+            ]))
+            .with_body(
+                r#"{"ok": true, "messages": [{"ts": "1.0", "user": "U2", "text": "looking"}], "response_metadata": {"next_cursor": "page3"}}"#,
+            )
+            .create_async()
+            .await;
+
+        let messages = get_channel_history(
+            &Client::new(),
+            &mock_config(&server),
+            "C456",
+            3,
+            Some("1699999999.000000"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            messages.iter().map(|m| m.ts.as_str()).collect::<Vec<_>>(),
+            vec!["3.0", "2.0", "1.0"]
+        );
+        assert_eq!(messages[1].user, None);
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_message_round_trip() {
+        let mut server = mockito::Server::new_async().await;
+        let post = server
+            .mock("POST", "/chat.postMessage")
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "channel": "C456",
+                "text": "Incidents for review",
+                "mrkdwn": true,
+            })))
+            .with_body(r#"{"ok": true, "channel": "C456", "ts": "1700000000.000100"}"#)
+            .create_async()
+            .await;
+
+        let ts = send_message(
+            &Client::new(),
+            &mock_config(&server),
+            "C456",
+            "Incidents for review",
+            None,
+        )
+        .await
+        .unwrap()
+        .ts;
+        assert_eq!(ts, "1700000000.000100");
+        post.assert_async().await;
+
+        let reply = server
+            .mock("POST", "/chat.postMessage")
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "channel": "C456",
+                "thread_ts": "1700000000.000100",
+            })))
+            .with_body(r#"{"ok": true, "channel": "C456", "ts": "1700000000.000200"}"#)
+            .create_async()
+            .await;
+        let ts = send_message(
+            &Client::new(),
+            &mock_config(&server),
+            "C456",
+            "• 1234 RPC latency",
+            Some("1700000000.000100"),
+        )
+        .await
+        .unwrap()
+        .ts;
+        assert_eq!(ts, "1700000000.000200");
+        reply.assert_async().await;
+
+        server
+            .mock("POST", "/chat.postMessage")
+            .with_body(r#"{"ok": false, "error": "channel_not_found"}"#)
+            .create_async()
+            .await;
+        let err = send_message(&Client::new(), &mock_config(&server), "C0", "hi", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SlackError::ChannelNotFound));
+    }
+
+    #[test]
+    fn test_send_message_body_has_no_ts() {
+        let body = |thread_ts: Option<&str>| {
+            serde_json::to_value(SendMessageBody {
+                channel: "C456".to_owned(),
+                text: "hi".to_owned(),
+                mrkdwn: true,
+                thread_ts: thread_ts.map(str::to_owned),
+                blocks: None,
+            })
+            .unwrap()
+        };
+        // the ts of a message is only ever what Slack returns for it
+        assert_eq!(
+            body(None),
+            serde_json::json!({ "channel": "C456", "text": "hi", "mrkdwn": true })
+        );
+        let reply = body(Some("1700000000.000100"));
+        assert!(reply.get("ts").is_none());
+        assert_eq!(reply["thread_ts"], "1700000000.000100");
+    }
+
+    #[tokio::test]
+    async fn test_send_message_joins_the_channel_and_retries() {
+        let mut server = mockito::Server::new_async().await;
+        // each post is answered by the first of these still expecting a request
+        let refused = server
+            .mock("POST", "/chat.postMessage")
+            .with_body(r#"{"ok": false, "error": "not_in_channel"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let posted = server
+            .mock("POST", "/chat.postMessage")
+            .with_body(r#"{"ok": true, "channel": "C456", "ts": "1700000000.000100"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let list = server
+            .mock("GET", "/conversations.list")
+            .match_query(Matcher::Any)
+            .with_body(
+                r#"{"ok": true, "channels": [{"id": "C123", "name": "general"}, {"id": "C456", "name": "incident-postmortems"}]}"#,
+            )
+            .create_async()
+            .await;
+        let join = server
+            .mock("POST", "/conversations.join")
+            .match_body(Matcher::Json(serde_json::json!({ "channel": "C456" })))
+            .with_body(r#"{"ok": true, "channel": {"id": "C456"}}"#)
+            .create_async()
+            .await;
+
+        let ts = send_message(&Client::new(), &mock_config(&server), "C456", "hi", None)
+            .await
+            .unwrap()
+            .ts;
+        assert_eq!(ts, "1700000000.000100");
+        refused.assert_async().await;
+        list.assert_async().await;
+        join.assert_async().await;
+        posted.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_message_to_a_channel_name_joins_it_by_id() {
+        let mut server = mockito::Server::new_async().await;
+        let refused = server
+            .mock("POST", "/chat.postMessage")
+            .match_body(Matcher::PartialJson(
+                serde_json::json!({ "channel": "incident-postmortems" }),
+            ))
+            .with_body(r#"{"ok": false, "error": "not_in_channel"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let posted = server
+            .mock("POST", "/chat.postMessage")
+            .with_body(r#"{"ok": true, "channel": "C456", "ts": "1700000000.000100"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let list = server
+            .mock("GET", "/conversations.list")
+            .match_query(Matcher::Any)
+            .with_body(
+                r#"{"ok": true, "channels": [{"id": "C123", "name": "general"}, {"id": "C456", "name": "incident-postmortems"}]}"#,
+            )
+            .create_async()
+            .await;
+        // conversations.join only takes the id
+        let join = server
+            .mock("POST", "/conversations.join")
+            .match_body(Matcher::Json(serde_json::json!({ "channel": "C456" })))
+            .with_body(r#"{"ok": true, "channel": {"id": "C456"}}"#)
+            .create_async()
+            .await;
+
+        let message = send_message(
+            &Client::new(),
+            &mock_config(&server),
+            "incident-postmortems",
+            "hi",
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(message.channel, "C456");
+        refused.assert_async().await;
+        list.assert_async().await;
+        join.assert_async().await;
+        posted.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_blocks() {
+        let mut server = mockito::Server::new_async().await;
+        let blocks = serde_json::json!([{ "type": "divider" }]);
+        let post = server
+            .mock("POST", "/chat.postMessage")
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "channel": "C456",
+                "text": "Incidents for review",
+                "blocks": [{ "type": "divider" }],
+            })))
+            .with_body(r#"{"ok": true, "channel": "C456", "ts": "1700000000.000100"}"#)
+            .create_async()
+            .await;
+        let ts = send_blocks(
+            &Client::new(),
+            &mock_config(&server),
+            "C456",
+            "Incidents for review",
+            &blocks,
+        )
+        .await
+        .unwrap()
+        .ts;
+        assert_eq!(ts, "1700000000.000100");
+        post.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_ephemeral() {
+        let mut server = mockito::Server::new_async().await;
+        let post = server
+            .mock("POST", "/chat.postEphemeral")
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "channel": "incident-postmortems",
+                "user": "U123",
+                "text": "Incidents for review",
+            })))
+            .with_body(r#"{"ok": true, "message_ts": "1700000000.000100"}"#)
+            .create_async()
+            .await;
+        let config = mock_config(&server);
+        let ts = send_ephemeral(
+            &Client::new(),
+            &config,
+            "incident-postmortems",
+            "U123",
+            "Incidents for review",
+        )
+        .await
+        .unwrap();
+        assert_eq!(ts, "1700000000.000100");
+        post.assert_async().await;
+
+        server
+            .mock("POST", "/chat.postEphemeral")
+            .with_body(r#"{"ok": false, "error": "user_not_in_channel"}"#)
+            .create_async()
+            .await;
+        let err = send_ephemeral(&Client::new(), &config, "general", "U123", "hi")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to preview a message to U123 in general: chat.postEphemeral can only show a message to a member of the channel: join it first"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_open_dm() {
+        let mut server = mockito::Server::new_async().await;
+        let open = server
+            .mock("POST", "/conversations.open")
+            .match_body(Matcher::Json(serde_json::json!({ "users": "U123" })))
+            .with_body(r#"{"ok": true, "channel": {"id": "D456"}}"#)
+            .create_async()
+            .await;
+        let config = mock_config(&server);
+        assert_eq!(
+            open_dm(&Client::new(), &config, "U123").await.unwrap(),
+            "D456"
+        );
+        open.assert_async().await;
+
+        server
+            .mock("POST", "/conversations.open")
+            .match_body(Matcher::Json(serde_json::json!({ "users": "U0" })))
+            .with_body(r#"{"ok": false, "error": "missing_scope", "needed": "im:write"}"#)
+            .create_async()
+            .await;
+        let err = open_dm(&Client::new(), &config, "U0").await.unwrap_err();
+        assert!(err.to_string().contains("add the im:write scope"));
+    }
+
+    #[tokio::test]
+    async fn test_add_reaction() {
+        let mut server = mockito::Server::new_async().await;
+        let react = server
+            .mock("POST", "/reactions.add")
+            .match_body(Matcher::Json(serde_json::json!({
+                "channel": "C456",
+                "timestamp": "1700000000.000100",
+                "name": "white_check_mark",
+            })))
+            .with_body(r#"{"ok": true}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let config = mock_config(&server);
+        add_reaction(
+            &Client::new(),
+            &config,
+            "C456",
+            "1700000000.000100",
+            ":white_check_mark:",
+        )
+        .await
+        .unwrap();
+        add_reaction(
+            &Client::new(),
+            &config,
+            "C456",
+            "1700000000.000100",
+            "white_check_mark",
+        )
+        .await
+        .unwrap();
+        react.assert_async().await;
+
+        server
+            .mock("POST", "/reactions.add")
+            .match_body(Matcher::PartialJson(serde_json::json!({ "name": "eyes" })))
+            .with_body(r#"{"ok": false, "error": "already_reacted"}"#)
+            .create_async()
+            .await;
+        add_reaction(&Client::new(), &config, "C456", "1.0", "eyes")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_message() {
+        let mut server = mockito::Server::new_async().await;
+        let update = server
+            .mock("POST", "/chat.update")
+            .match_body(Matcher::Json(serde_json::json!({
+                "channel": "C456",
+                "ts": "1700000000.000100",
+                "text": "Incidents for review (revised)",
+            })))
+            .with_body(r#"{"ok": true, "channel": "C456", "ts": "1700000000.000100"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/chat.update")
+            .match_body(Matcher::PartialJson(serde_json::json!({ "ts": "1.0" })))
+            .with_body(r#"{"ok": false, "error": "message_not_found"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/chat.update")
+            .match_body(Matcher::PartialJson(serde_json::json!({ "ts": "2.0" })))
+            .with_body(r#"{"ok": false, "error": "cant_update_message"}"#)
+            .create_async()
+            .await;
+        let config = mock_config(&server);
+
+        let ts = update_message(
+            &Client::new(),
+            &config,
+            "C456",
+            "1700000000.000100",
+            "Incidents for review (revised)",
+        )
+        .await
+        .unwrap();
+        assert_eq!(ts, "1700000000.000100");
+        update.assert_async().await;
+
+        let err = update_message(&Client::new(), &config, "C456", "1.0", "hi")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to update message 1.0 in C456: there is no such message, it may have been deleted"
+        );
+        let err = update_message(&Client::new(), &config, "C456", "2.0", "hi")
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("the bot can only edit messages it posted"));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_user_by_email() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/users.lookupByEmail")
+            .match_query(Matcher::UrlEncoded(
+                "email".into(),
+                "jane@mystenlabs.com".into(),
+            ))
+            .with_body(
+                r#"{"ok": true, "user": {"id": "U1", "name": "jane", "profile": {"email": "jane@mystenlabs.com"}}}"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/users.lookupByEmail")
+            .match_query(Matcher::UrlEncoded(
+                "email".into(),
+                "nobody@mystenlabs.com".into(),
+            ))
+            .with_body(r#"{"ok": false, "error": "users_not_found"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/users.lookupByEmail")
+            .match_query(Matcher::UrlEncoded(
+                "email".into(),
+                "john@mystenlabs.com".into(),
+            ))
+            .with_body(r#"{"ok": false, "error": "missing_scope", "needed": "users:read.email"}"#)
+            .create_async()
+            .await;
+        let config = mock_config(&server);
+
+        let user = lookup_user_by_email(&Client::new(), &config, "jane@mystenlabs.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.id, "U1");
+        assert!(
+            lookup_user_by_email(&Client::new(), &config, "nobody@mystenlabs.com")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let err = lookup_user_by_email(&Client::new(), &config, "john@mystenlabs.com")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("add the users:read.email scope"));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_request_is_retried() {
+        let mut server = mockito::Server::new_async().await;
+        let limited = server
+            .mock("POST", "/chat.postMessage")
+            .with_status(429)
+            .with_header("Retry-After", "0")
+            .expect(1)
+            .create_async()
+            .await;
+        let post = server
+            .mock("POST", "/chat.postMessage")
+            .with_body(r#"{"ok": true, "channel": "C456", "ts": "1700000000.000100"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let ts = send_message(&Client::new(), &mock_config(&server), "C456", "hi", None)
+            .await
+            .unwrap()
+            .ts;
+        assert_eq!(ts, "1700000000.000100");
+        limited.assert_async().await;
+        post.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_gives_up_after_max_attempts() {
+        let mut server = mockito::Server::new_async().await;
+        let limited = server
+            .mock("GET", "/conversations.list")
+            .match_query(Matcher::Any)
+            .with_status(429)
+            .with_header("Retry-After", "0")
+            .expect(2)
+            .create_async()
+            .await;
+        let config = SlackConfig {
+            max_attempts: 2,
+            ..mock_config(&server)
+        };
+
+        let err = get_channels(&Client::new(), &config, DEFAULT_CONVERSATION_TYPES)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            SlackError::RateLimited {
+                retry_after: Some(0)
+            }
+        ));
+        limited.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_test_message() {
+        let mut server = mockito::Server::new_async().await;
+        let post = server
+            .mock("POST", "/chat.postMessage")
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "channel": "ops-bots",
+                "text": TEST_MESSAGE,
+            })))
+            .with_body(r#"{"ok": true, "channel": "C456", "ts": "1700000000.000100"}"#)
+            .create_async()
+            .await;
+        let posted = send_test_message(&Client::new(), &mock_config(&server), "#ops-bots")
+            .await
+            .unwrap();
+        // the channel is reported by its id, not the name it was posted to
+        assert_eq!(
+            posted,
+            PostedMessage {
+                channel: "C456".to_owned(),
+                ts: "1700000000.000100".to_owned(),
+            }
+        );
+        post.assert_async().await;
+
+        server
+            .mock("POST", "/chat.postMessage")
+            .match_body(Matcher::PartialJson(
+                serde_json::json!({ "channel": "general" }),
+            ))
+            .with_body(r#"{"ok": false, "error": "not_in_channel"}"#)
+            .create_async()
+            .await;
+        // a private channel can't be joined
+        server
+            .mock("POST", "/conversations.join")
+            .with_body(r#"{"ok": false, "error": "method_not_supported_for_channel_type"}"#)
+            .create_async()
+            .await;
+        let err = send_test_message(&Client::new(), &mock_config(&server), "general")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to send message to general: the bot can't call chat.postMessage because it isn't in the channel: invite it with /invite in the channel"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_file_handshake() {
+        let mut server = mockito::Server::new_async().await;
+        let get_url = server
+            .mock("GET", "/files.getUploadURLExternal")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("filename".into(), "incidents.csv".into()),
+                Matcher::UrlEncoded("length".into(), "11".into()),
+            ]))
+            .with_body(format!(
+                r#"{{"ok": true, "upload_url": "{}/upload/F123", "file_id": "F123"}}"#,
+                server.url()
+            ))
+            .create_async()
+            .await;
+        let upload = server
+            .mock("POST", "/upload/F123")
+            .match_body("number,name")
+            .create_async()
+            .await;
+        let complete = server
+            .mock("POST", "/files.completeUploadExternal")
+            .match_body(Matcher::Json(serde_json::json!({
+                "files": [{"id": "F123", "title": "Incident export"}],
+                "channel_id": "C456",
+            })))
+            .with_body(r#"{"ok": true, "files": [{"id": "F123"}]}"#)
+            .create_async()
+            .await;
+
+        let file_id = upload_file(
+            &Client::new(),
+            &mock_config(&server),
+            "C456",
+            "incidents.csv",
+            b"number,name".to_vec(),
+            "Incident export",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(file_id, "F123");
+        get_url.assert_async().await;
+        upload.assert_async().await;
+        complete.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_upload_file_reports_upload_url_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/files.getUploadURLExternal")
+            .match_query(Matcher::Any)
+            .with_body(r#"{"ok": false, "error": "missing_scope"}"#)
+            .create_async()
+            .await;
+
+        let err = upload_file(
+            &Client::new(),
+            &mock_config(&server),
+            "C456",
+            "incidents.csv",
+            b"number,name".to_vec(),
+            "Incident export",
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Failed to get upload url: the Slack token can't call files.getUploadURLExternal: add the required scope to the app under OAuth & Permissions and reinstall it"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_missing_scopes() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/auth.test")
+            .with_header("x-oauth-scopes", "channels:read,groups:read,chat:write")
+            .with_body(r#"{"ok": true, "user_id": "U0BOT"}"#)
+            .create_async()
+            .await;
+
+        let err = verify(&Client::new(), &mock_config(&server))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the Slack token is missing the users:read scope(s): add them to the app under OAuth & Permissions and reinstall it"
+        );
+
+        server
+            .mock("GET", "/auth.test")
+            .with_header(
+                "x-oauth-scopes",
+                "channels:read, groups:read, chat:write, users:read",
+            )
+            .with_body(r#"{"ok": true, "user_id": "U0BOT"}"#)
+            .create_async()
+            .await;
+        verify(&Client::new(), &mock_config(&server)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_wrong_token_type() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/auth.test")
+            .with_body(r#"{"ok": false, "error": "not_allowed_token_type"}"#)
+            .create_async()
+            .await;
+
+        let err = verify(&Client::new(), &mock_config(&server))
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("set SLACK_BOT_TOKEN to the app's bot token (xoxb-...)"));
+    }
+
+    #[test]
+    fn test_users_missing_scope_is_actionable() {
+        let response: UsersResponse = serde_json::from_str(
+            r#"{"ok": false, "error": "missing_scope", "needed": "users:read", "provided": "chat:write"}"#,
+        )
+        .unwrap();
+        let err = response.into_page().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to get users: the Slack token can't call users.list: add the users:read scope to the app under OAuth & Permissions and reinstall it"
+        );
     }
 }
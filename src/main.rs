@@ -5,11 +5,14 @@ use anyhow::Result;
 use clap::Parser;
 use suioplib::{
     cli::{
+        cache_cmd,
         ci::{image_cmd, ImageAction, ImageArgs, ImageBuildArgs, ImageQueryArgs},
-        ci_cmd, docker_cmd, iam_cmd, incidents_cmd, load_environment, pulumi_cmd,
+        ci_cmd, docker_cmd, iam_cmd, incidents_cmd,
+        lib::concurrency::{parse_concurrency, set_concurrency, DEFAULT_CONCURRENCY},
+        load_environment, people_cmd, pulumi_cmd,
         service::ServiceAction,
-        service_cmd, CIArgs, DockerArgs, IAMArgs, IncidentsArgs, LoadEnvironmentArgs, PulumiArgs,
-        ServiceArgs,
+        service_cmd, slack_cmd, CIArgs, CacheArgs, DockerArgs, IAMArgs, IncidentsArgs,
+        LoadEnvironmentArgs, PeopleArgs, PulumiArgs, ServiceArgs, SlackArgs,
     },
     DEBUG_MODE,
 };
@@ -25,10 +28,15 @@ pub(crate) struct SuiOpArgs {
     /// The resource type we're operating on.
     #[command(subcommand)]
     resource: Resource,
+    /// how many requests batch operations (e.g. Notion inserts) run at once
+    #[arg(long, global = true, default_value_t = DEFAULT_CONCURRENCY, value_parser = parse_concurrency)]
+    concurrency: usize,
 }
 
 #[derive(clap::Subcommand, Debug)]
 pub(crate) enum Resource {
+    #[clap()]
+    Cache(CacheArgs),
     #[clap(aliases = ["d"])]
     Docker(DockerArgs),
     #[clap()]
@@ -39,11 +47,15 @@ pub(crate) enum Resource {
     Image(Box<ImageQueryArgs>),
     #[clap(aliases = ["b", "build"])]
     BuildImage(Box<ImageBuildArgs>),
+    #[clap()]
+    People(PeopleArgs),
     #[clap(aliases = ["p"])]
     Pulumi(PulumiArgs),
     #[clap(aliases = ["s", "svc"])]
     Service(ServiceArgs),
     #[clap()]
+    Slack(SlackArgs),
+    #[clap()]
     CI(CIArgs),
     #[clap(name="load-env", aliases = ["e", "env"])]
     LoadEnvironment(LoadEnvironmentArgs),
@@ -68,7 +80,11 @@ async fn main() -> Result<()> {
     }
 
     let args = SuiOpArgs::parse();
+    set_concurrency(args.concurrency)?;
     match args.resource {
+        Resource::Cache(args) => {
+            cache_cmd(&args).await?;
+        }
         Resource::Docker(args) => {
             docker_cmd(&args).await?;
         }
@@ -90,12 +106,18 @@ async fn main() -> Result<()> {
             })
             .await?;
         }
+        Resource::People(args) => {
+            people_cmd(&args).await?;
+        }
         Resource::Pulumi(args) => {
             pulumi_cmd(&args)?;
         }
         Resource::Service(args) => {
             service_cmd(&args).await?;
         }
+        Resource::Slack(args) => {
+            slack_cmd(&args).await?;
+        }
         Resource::CI(args) => {
             ci_cmd(&args).await?;
         }
@@ -3,6 +3,9 @@
 
 #[cfg(test)]
 mod tests {
+    use suioplib::cli::lib::email::{
+        canonicalize_email, emails_match, emails_match_canonical, normalize_email,
+    };
     use suioplib::cli::lib::utils::validate_project_name;
 
     #[test]
@@ -34,4 +37,48 @@ mod tests {
         let name = "a123456789012345678901234567890";
         assert!(validate_project_name(name).is_err());
     }
+
+    #[test]
+    fn test_normalize_email() {
+        assert_eq!(
+            normalize_email("  jane@mystenlabs.com \n"),
+            "jane@mystenlabs.com"
+        );
+        assert_eq!(
+            normalize_email("Jane.Doe@MystenLabs.com"),
+            "jane.doe@mystenlabs.com"
+        );
+    }
+
+    #[test]
+    fn test_emails_match_ignores_case_and_whitespace() {
+        assert!(emails_match("Jane@MystenLabs.com", " jane@mystenlabs.com"));
+        assert!(!emails_match("jane@mystenlabs.com", "john@mystenlabs.com"));
+        // plus-addressing only matches when canonicalizing
+        assert!(!emails_match(
+            "jane+oncall@mystenlabs.com",
+            "jane@mystenlabs.com"
+        ));
+    }
+
+    #[test]
+    fn test_canonicalize_email_plus_addressing() {
+        assert_eq!(
+            canonicalize_email("Jane.Doe+OnCall@mystenlabs.com"),
+            "jane.doe@mystenlabs.com"
+        );
+        assert_eq!(
+            canonicalize_email("jane@mystenlabs.com"),
+            "jane@mystenlabs.com"
+        );
+        assert_eq!(canonicalize_email("not-an-email+x"), "not-an-email+x");
+        assert!(emails_match_canonical(
+            "jane+oncall@mystenlabs.com",
+            "JANE@mystenlabs.com"
+        ));
+        assert!(!emails_match_canonical(
+            "jane+oncall@mystenlabs.com",
+            "jane@sui.io"
+        ));
+    }
 }